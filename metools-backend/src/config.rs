@@ -1,7 +1,7 @@
 use std::env;
 
 use surrealdb::{
-    engine::remote::ws::{Client, Ws},
+    engine::any::{self, Any},
     opt::auth::Root,
     Surreal,
 };
@@ -13,11 +13,32 @@ pub struct DBConfig {
     pub surrealdb_password: String,
     pub surrealdb_ns: String,
     pub surrealdb_db: String,
+    /// Connection handed out instead of opening a new one, tests use it to share an in-memory
+    /// database between services.
+    #[cfg(test)]
+    pub connection: Option<Surreal<Any>>,
 }
 
 impl DBConfig {
-    pub async fn get_connection(&self) -> Surreal<Client> {
-        let db = Surreal::new::<Ws>(self.surrealdb_url.as_str())
+    pub fn init() -> Self {
+        Self {
+            surrealdb_url: env::var("SURREALDB_URL").unwrap_or(String::from("localhost:8080")),
+            surrealdb_username: env::var("SURREALDB_USERNAME").unwrap_or(String::from("root")),
+            surrealdb_password: env::var("SURREALDB_PASSWORD").unwrap_or(String::from("root")),
+            surrealdb_ns: env::var("SURREALDB_NS").unwrap_or(String::from("ns")),
+            surrealdb_db: env::var("SURREALDB_DB").unwrap_or(String::from("db")),
+            #[cfg(test)]
+            connection: None,
+        }
+    }
+
+    pub async fn get_connection(&self) -> Surreal<Any> {
+        #[cfg(test)]
+        if let Some(connection) = &self.connection {
+            return connection.clone();
+        }
+
+        let db = any::connect(format!("ws://{}", self.surrealdb_url))
             .await
             .unwrap();
 
@@ -36,6 +57,139 @@ impl DBConfig {
     }
 }
 
+/// Telegram bot settings, the channel is off when no bot token is set.
+#[derive(Debug, Clone)]
+pub struct TelegramConfig {
+    pub api_url: String,
+    pub bot_token: String,
+    pub bot_username: String,
+}
+
+impl TelegramConfig {
+    pub fn init() -> Option<Self> {
+        let bot_token = env::var("TELEGRAM_BOT_TOKEN").ok()?;
+        let api_url =
+            env::var("TELEGRAM_API_URL").unwrap_or(String::from("https://api.telegram.org"));
+        let bot_username = env::var("TELEGRAM_BOT_USERNAME")
+            .expect("TELEGRAM_BOT_USERNAME must be set with TELEGRAM_BOT_TOKEN");
+
+        Some(Self {
+            api_url,
+            bot_token,
+            bot_username,
+        })
+    }
+}
+
+/// Limits of a plan, applied to users by their role.
+#[derive(Debug, Clone)]
+pub struct TaskQuota {
+    pub max_active_tasks: usize,
+    /// Shortest allowed time between checks of a task, in seconds.
+    pub min_check_interval: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskQuotas {
+    pub user: TaskQuota,
+    pub premium: TaskQuota,
+}
+
+impl TaskQuotas {
+    pub fn init() -> Self {
+        let max_active_tasks = env::var("RZD_MAX_ACTIVE_TASKS").unwrap_or(String::from("10"));
+        let min_check_interval = env::var("RZD_MIN_CHECK_INTERVAL").unwrap_or(String::from("900")); // In seconds
+        let premium_max_active_tasks =
+            env::var("RZD_PREMIUM_MAX_ACTIVE_TASKS").unwrap_or(String::from("100"));
+        let premium_min_check_interval =
+            env::var("RZD_PREMIUM_MIN_CHECK_INTERVAL").unwrap_or(String::from("300")); // In seconds
+
+        Self {
+            user: TaskQuota {
+                max_active_tasks: max_active_tasks.parse::<usize>().unwrap(),
+                min_check_interval: min_check_interval.parse::<u64>().unwrap(),
+            },
+            premium: TaskQuota {
+                max_active_tasks: premium_max_active_tasks.parse::<usize>().unwrap(),
+                min_check_interval: premium_min_check_interval.parse::<u64>().unwrap(),
+            },
+        }
+    }
+
+    pub fn for_role(&self, role: &str) -> &TaskQuota {
+        match role {
+            "premium" => &self.premium,
+            _ => &self.user,
+        }
+    }
+}
+
+/// Limits on requests to RZD, protecting both the upstream and the poller.
+#[derive(Debug, Clone)]
+pub struct RzdLimitsConfig {
+    /// Requests per second allowed on average.
+    pub rate_limit: f64,
+    /// Requests allowed at once after a quiet period.
+    pub rate_burst: u32,
+    /// Retries of a request that timed out or was answered with 429 or 5xx.
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled after each retry.
+    pub retry_delay: u64,
+    /// Requests in a row failing with transient errors that open the circuit.
+    pub circuit_failures: u32,
+    /// Seconds the circuit stays open before a trial request.
+    pub circuit_open_duration: u64,
+}
+
+impl RzdLimitsConfig {
+    pub fn init() -> Self {
+        let rate_limit = env::var("RZD_RATE_LIMIT").unwrap_or(String::from("2"));
+        let rate_burst = env::var("RZD_RATE_BURST").unwrap_or(String::from("5"));
+        let max_retries = env::var("RZD_MAX_RETRIES").unwrap_or(String::from("3"));
+        let retry_delay = env::var("RZD_RETRY_DELAY").unwrap_or(String::from("500")); // In milliseconds
+        let circuit_failures = env::var("RZD_CIRCUIT_FAILURES").unwrap_or(String::from("5"));
+        let circuit_open_duration =
+            env::var("RZD_CIRCUIT_OPEN_DURATION").unwrap_or(String::from("300")); // In seconds
+
+        Self {
+            rate_limit: rate_limit.parse::<f64>().unwrap(),
+            rate_burst: rate_burst.parse::<u32>().unwrap(),
+            max_retries: max_retries.parse::<u32>().unwrap(),
+            retry_delay: retry_delay.parse::<u64>().unwrap(),
+            circuit_failures: circuit_failures.parse::<u32>().unwrap(),
+            circuit_open_duration: circuit_open_duration.parse::<u64>().unwrap(),
+        }
+    }
+}
+
+/// Settings of the HTTP client talking to the RZD timetable API.
+#[derive(Debug, Clone)]
+pub struct RzdHttpConfig {
+    pub api_url: String,
+    /// Timeout of a single HTTP request in seconds.
+    pub request_timeout: u64,
+    /// Delay before asking RZD for the result of a RID in milliseconds.
+    pub rid_poll_delay: u64,
+    /// Requests for the result of a RID before giving up.
+    pub rid_max_polls: u32,
+}
+
+impl RzdHttpConfig {
+    pub fn init() -> Self {
+        let api_url = env::var("RZD_API_URL").unwrap_or(String::from("https://pass.rzd.ru"));
+        let request_timeout = env::var("RZD_REQUEST_TIMEOUT").unwrap_or(String::from("30")); // In seconds
+        let rid_poll_delay = env::var("RZD_RID_POLL_DELAY").unwrap_or(String::from("1000")); // In milliseconds
+        let rid_max_polls = env::var("RZD_RID_MAX_POLLS").unwrap_or(String::from("10"));
+
+        Self {
+            api_url,
+            request_timeout: request_timeout.parse::<u64>().unwrap(),
+            rid_poll_delay: rid_poll_delay.parse::<u64>().unwrap(),
+            rid_max_polls: rid_max_polls.parse::<u32>().unwrap(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub db: DBConfig,
@@ -46,9 +200,27 @@ pub struct Config {
     pub run_migrations: bool,
     pub smtp_from: String,
     pub smtp_hostname: String,
-    pub smtp_port: usize,
+    pub smtp_port: u16,
     pub smtp_username: String,
     pub smtp_password: String,
+    pub rzd_http: RzdHttpConfig,
+    pub rzd_fixtures_path: Option<String>,
+    pub rzd_poll_interval: u64,
+    pub rzd_scheduler_tick: u64,
+    pub rzd_max_checks_per_run: usize,
+    pub rzd_max_failed_checks: u32,
+    pub rzd_limits: RzdLimitsConfig,
+    pub rzd_checks_retention_days: u32,
+    pub rzd_prices_retention_days: u32,
+    pub rzd_quotas: TaskQuotas,
+    pub telegram: Option<TelegramConfig>,
+    pub webhook_max_attempts: u32,
+    pub webhook_retry_delay: u64,
+    pub webhook_deliveries_retention_days: u32,
+    pub notifications_digest_interval: u64,
+    pub notifications_max_attempts: u32,
+    pub notifications_retry_delay: u64,
+    pub notifications_outbox_retention_days: u32,
 }
 
 impl Config {
@@ -56,11 +228,6 @@ impl Config {
         let http_address = env::var("HTTP_ADDRESS").unwrap_or(String::from("0.0.0.0:8000"));
         let service_url =
             env::var("SERVICE_URL").unwrap_or(format!("http://{}", http_address.clone()));
-        let surrealdb_url = env::var("SURREALDB_URL").unwrap_or(String::from("localhost:8080"));
-        let surrealdb_username = env::var("SURREALDB_USERNAME").unwrap_or(String::from("root"));
-        let surrealdb_password = env::var("SURREALDB_PASSWORD").unwrap_or(String::from("root"));
-        let surrealdb_ns = env::var("SURREALDB_NS").unwrap_or(String::from("ns"));
-        let surrealdb_db = env::var("SURREALDB_DB").unwrap_or(String::from("db"));
         let jwt_secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
         let jwt_maxage = env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set"); // In minutes
         let run_migrations = env::var("RUN_MIGRATIONS").unwrap_or(String::from("false"));
@@ -69,15 +236,31 @@ impl Config {
         let smtp_port = env::var("SMTP_PORT").unwrap_or(String::from("587")); // Default port is 587
         let smtp_username = env::var("SMTP_USERNAME").expect("SMTP_USERNAME must be set");
         let smtp_password = env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set");
+        let rzd_fixtures_path = env::var("RZD_FIXTURES_PATH").ok(); // Serve RZD answers from fixtures
+        let rzd_poll_interval = env::var("RZD_POLL_INTERVAL").unwrap_or(String::from("300")); // In seconds, for tasks without their own interval
+        let rzd_scheduler_tick = env::var("RZD_SCHEDULER_TICK").unwrap_or(String::from("30")); // In seconds, how often due tasks are looked up
+        let rzd_max_checks_per_run =
+            env::var("RZD_MAX_CHECKS_PER_RUN").unwrap_or(String::from("100")); // Due tasks checked per tick, the rest wait for the next one
+        let rzd_max_failed_checks = env::var("RZD_MAX_FAILED_CHECKS").unwrap_or(String::from("10")); // Errors in a row before a task fails
+        let rzd_checks_retention_days =
+            env::var("RZD_CHECKS_RETENTION_DAYS").unwrap_or(String::from("30")); // How long check history is kept
+        let rzd_prices_retention_days =
+            env::var("RZD_PRICES_RETENTION_DAYS").unwrap_or(String::from("90")); // How long price series are kept
+        let webhook_max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS").unwrap_or(String::from("6")); // Attempts before a delivery fails
+        let webhook_retry_delay = env::var("WEBHOOK_RETRY_DELAY").unwrap_or(String::from("30")); // In seconds, doubled after each attempt
+        let webhook_deliveries_retention_days =
+            env::var("WEBHOOK_DELIVERIES_RETENTION_DAYS").unwrap_or(String::from("30")); // How long the delivery log is kept
+        let notifications_digest_interval =
+            env::var("NOTIFICATIONS_DIGEST_INTERVAL").unwrap_or(String::from("3600")); // In seconds
+        let notifications_max_attempts =
+            env::var("NOTIFICATIONS_MAX_ATTEMPTS").unwrap_or(String::from("8")); // Attempts before a message is dead
+        let notifications_retry_delay =
+            env::var("NOTIFICATIONS_RETRY_DELAY").unwrap_or(String::from("60")); // In seconds, doubled after each attempt
+        let notifications_outbox_retention_days =
+            env::var("NOTIFICATIONS_OUTBOX_RETENTION_DAYS").unwrap_or(String::from("30")); // How long sent and dead messages are kept
 
         Self {
-            db: DBConfig {
-                surrealdb_url,
-                surrealdb_username,
-                surrealdb_password,
-                surrealdb_ns,
-                surrealdb_db,
-            },
+            db: DBConfig::init(),
             http_address,
             service_url,
             jwt_secret,
@@ -85,9 +268,31 @@ impl Config {
             run_migrations: run_migrations.parse::<bool>().unwrap(),
             smtp_from,
             smtp_hostname,
-            smtp_port: smtp_port.parse::<usize>().unwrap(),
+            smtp_port: smtp_port.parse::<u16>().unwrap(),
             smtp_username,
             smtp_password,
+            rzd_http: RzdHttpConfig::init(),
+            rzd_fixtures_path,
+            rzd_poll_interval: rzd_poll_interval.parse::<u64>().unwrap(),
+            rzd_scheduler_tick: rzd_scheduler_tick.parse::<u64>().unwrap(),
+            rzd_max_checks_per_run: rzd_max_checks_per_run.parse::<usize>().unwrap(),
+            rzd_max_failed_checks: rzd_max_failed_checks.parse::<u32>().unwrap(),
+            rzd_limits: RzdLimitsConfig::init(),
+            rzd_checks_retention_days: rzd_checks_retention_days.parse::<u32>().unwrap(),
+            rzd_prices_retention_days: rzd_prices_retention_days.parse::<u32>().unwrap(),
+            rzd_quotas: TaskQuotas::init(),
+            telegram: TelegramConfig::init(),
+            webhook_max_attempts: webhook_max_attempts.parse::<u32>().unwrap(),
+            webhook_retry_delay: webhook_retry_delay.parse::<u64>().unwrap(),
+            webhook_deliveries_retention_days: webhook_deliveries_retention_days
+                .parse::<u32>()
+                .unwrap(),
+            notifications_digest_interval: notifications_digest_interval.parse::<u64>().unwrap(),
+            notifications_max_attempts: notifications_max_attempts.parse::<u32>().unwrap(),
+            notifications_retry_delay: notifications_retry_delay.parse::<u64>().unwrap(),
+            notifications_outbox_retention_days: notifications_outbox_retention_days
+                .parse::<u32>()
+                .unwrap(),
         }
     }
 }
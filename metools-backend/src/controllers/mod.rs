@@ -1,4 +1,5 @@
 mod middlewares;
+pub mod notifications;
 pub mod rzd;
 pub mod schema;
 pub mod users;
@@ -0,0 +1,2 @@
+pub(crate) mod telegram;
+pub(crate) mod webhooks;
@@ -0,0 +1,195 @@
+use actix_web::{
+    body::BoxBody,
+    delete, get,
+    http::{header::ContentType, StatusCode},
+    post, web, HttpResponse, ResponseError,
+};
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::{
+    controllers::{
+        middlewares::UserMiddleware,
+        schema::{AppState, ResponseTelegramChat, ResponseTelegramLink, ResponseUnlinkTelegram},
+    },
+    models::telegram::{TelegramChat, TelegramDBError},
+    services::telegram::bot::{TelegramLink, TelegramServiceError},
+};
+
+#[derive(Debug, Display)]
+enum TelegramError {
+    TelegramDisabled,
+    TelegramServiceError(TelegramServiceError),
+}
+
+impl ResponseError for TelegramError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::TelegramDisabled => StatusCode::SERVICE_UNAVAILABLE,
+            Self::TelegramServiceError(error) => match error {
+                TelegramServiceError::TelegramDBError(error) => match error {
+                    TelegramDBError::ChatNotFound => StatusCode::NOT_FOUND,
+                    TelegramDBError::LinkCodeNotFound | TelegramDBError::UnknownError(_) => {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    }
+                },
+                TelegramServiceError::TelegramClientError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        match self {
+            Self::TelegramDisabled => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(
+                    json!({"error": "Telegram notifications are not configured", "status": "telegram_disabled"})
+                        .to_string(),
+                ),
+            Self::TelegramServiceError(TelegramServiceError::TelegramDBError(
+                TelegramDBError::ChatNotFound,
+            )) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(
+                    json!({"error": "Telegram chat is not linked", "status": "not_found"})
+                        .to_string(),
+                ),
+            Self::TelegramServiceError(_) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseTelegramChatData {
+    pub username: Option<String>,
+    pub linked_at: DateTime<Utc>,
+}
+
+impl From<TelegramChat> for ResponseTelegramChatData {
+    fn from(value: TelegramChat) -> Self {
+        Self {
+            username: value.username,
+            linked_at: value.created_at.to_utc(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseTelegramLinkData {
+    /// Send `/start <code>` to the bot if the link can not be opened.
+    pub code: String,
+    pub link: String,
+    pub valid_until: DateTime<Utc>,
+}
+
+impl From<TelegramLink> for ResponseTelegramLinkData {
+    fn from(value: TelegramLink) -> Self {
+        Self {
+            code: value.code,
+            link: value.link,
+            valid_until: value.valid_until,
+        }
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseTelegramChat),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Telegram chat is not linked", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse),
+    (status = SERVICE_UNAVAILABLE, description = "Telegram notifications are not configured", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[get("/api/v1/notifications/telegram")]
+pub async fn get_telegram_chat(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseTelegramChat>, TelegramError> {
+    let user_id = user.user_id;
+    let telegram_service = match &state.telegram_service {
+        Some(telegram_service) => telegram_service,
+        None => return Err(TelegramError::TelegramDisabled),
+    };
+
+    let r = telegram_service.get_chat_for_user(user_id).await;
+
+    match r {
+        Ok(chat) => Ok(web::Json(ResponseTelegramChat {
+            status: "success".to_string(),
+            data: chat.into(),
+        })),
+        Err(err) => Err(TelegramError::TelegramServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseTelegramLink),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse),
+    (status = SERVICE_UNAVAILABLE, description = "Telegram notifications are not configured", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[post("/api/v1/notifications/telegram/link")]
+pub async fn create_telegram_link(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseTelegramLink>, TelegramError> {
+    let user_id = user.user_id;
+    let telegram_service = match &state.telegram_service {
+        Some(telegram_service) => telegram_service,
+        None => return Err(TelegramError::TelegramDisabled),
+    };
+
+    let r = telegram_service.create_link_for_user(user_id).await;
+
+    match r {
+        Ok(link) => Ok(web::Json(ResponseTelegramLink {
+            status: "success".to_string(),
+            data: link.into(),
+        })),
+        Err(err) => Err(TelegramError::TelegramServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseUnlinkTelegram),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Telegram chat is not linked", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse),
+    (status = SERVICE_UNAVAILABLE, description = "Telegram notifications are not configured", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[delete("/api/v1/notifications/telegram")]
+pub async fn unlink_telegram_chat(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseUnlinkTelegram>, TelegramError> {
+    let user_id = user.user_id;
+    let telegram_service = match &state.telegram_service {
+        Some(telegram_service) => telegram_service,
+        None => return Err(TelegramError::TelegramDisabled),
+    };
+
+    let r = telegram_service.unlink_chat_for_user(user_id).await;
+
+    match r {
+        Ok(()) => Ok(web::Json(ResponseUnlinkTelegram {
+            status: "success".to_string(),
+            data: String::from("success"),
+        })),
+        Err(err) => Err(TelegramError::TelegramServiceError(err)),
+    }
+}
@@ -0,0 +1,368 @@
+use actix_web::{
+    body::BoxBody,
+    delete, get,
+    http::{header::ContentType, StatusCode},
+    post, web, HttpResponse, ResponseError,
+};
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::{
+    controllers::{
+        middlewares::UserMiddleware,
+        schema::{
+            AppState, ResponseCreateWebhook, ResponseDeleteWebhook, ResponseListWebhookDeliveries,
+            ResponseListWebhooks, ResponseWebhookSecret,
+        },
+    },
+    models::webhooks::{Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhooksDBError},
+    services::webhooks::WebhooksServiceError,
+    utils::{
+        pagination::{EncodedCursor, DEFAULT_PAGE_LIMIT},
+        thing::Base64EncodedThing,
+    },
+};
+
+#[derive(Debug, Display)]
+enum WebhooksError {
+    InvalidInputData(ValidationErrors),
+    WebhooksServiceError(WebhooksServiceError),
+}
+
+impl ResponseError for WebhooksError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidInputData(_) => StatusCode::BAD_REQUEST,
+            Self::WebhooksServiceError(error) => match error {
+                WebhooksServiceError::WebhooksDBError(error) => match error {
+                    WebhooksDBError::WebhookNotFound => StatusCode::NOT_FOUND,
+                    WebhooksDBError::SecretNotFound | WebhooksDBError::UnknownError(_) => {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    }
+                },
+                WebhooksServiceError::WebhooksLimitReached(_) => StatusCode::TOO_MANY_REQUESTS,
+                WebhooksServiceError::ForbiddenUrl(_) => StatusCode::BAD_REQUEST,
+            },
+        }
+    }
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        match self {
+            Self::InvalidInputData(_errors) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Invalid input data", "status": "invalid_data"}).to_string()),
+            Self::WebhooksServiceError(WebhooksServiceError::WebhooksDBError(
+                WebhooksDBError::WebhookNotFound,
+            )) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Webhook not found", "status": "not_found"}).to_string()),
+            Self::WebhooksServiceError(WebhooksServiceError::WebhooksLimitReached(limit)) => {
+                HttpResponse::build(self.status_code())
+                    .insert_header(ContentType::json())
+                    .body(
+                        json!({"error": format!("Webhooks limit of {limit} is reached"), "status": "webhooks_limit_reached"})
+                            .to_string(),
+                    )
+            }
+            Self::WebhooksServiceError(WebhooksServiceError::ForbiddenUrl(_)) => {
+                HttpResponse::build(self.status_code())
+                    .insert_header(ContentType::json())
+                    .body(
+                        json!({"error": "Webhook URL must point to a public host", "status": "invalid_webhook_url"})
+                            .to_string(),
+                    )
+            }
+            Self::WebhooksServiceError(_) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
+        }
+    }
+}
+
+fn validate_webhook_url(url: &str) -> Result<(), ValidationError> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        return Ok(());
+    }
+    Err(ValidationError::new("invalid_webhook_url"))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct CreateWebhookData {
+    /// Events are posted here as JSON.
+    #[validate(url, length(max = 2048), custom(function = "validate_webhook_url"))]
+    pub url: String,
+}
+
+#[derive(Deserialize, Clone)]
+struct WebhookPathData {
+    webhook_id: Base64EncodedThing,
+}
+
+#[derive(Deserialize, Validate)]
+struct ListWebhookDeliveriesData {
+    cursor: Option<EncodedCursor>,
+    #[validate(range(min = 1, max = 100))]
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseWebhookData {
+    #[schema(value_type = String)]
+    pub id: Base64EncodedThing,
+    pub created_at: DateTime<Utc>,
+    pub url: String,
+}
+
+impl From<Webhook> for ResponseWebhookData {
+    fn from(value: Webhook) -> Self {
+        Self {
+            id: Base64EncodedThing(value.id),
+            created_at: value.created_at.to_utc(),
+            url: value.url,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseWebhookSecretData {
+    /// Key of the HMAC-SHA256 signature sent in `X-MeTools-Signature`.
+    pub secret: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseWebhookDeliveryData {
+    #[schema(value_type = String)]
+    pub id: Base64EncodedThing,
+    pub created_at: DateTime<Utc>,
+    pub event: String,
+    pub payload: Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    /// When the next attempt is made while the delivery is pending.
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl From<WebhookDelivery> for ResponseWebhookDeliveryData {
+    fn from(value: WebhookDelivery) -> Self {
+        Self {
+            id: Base64EncodedThing(value.id),
+            created_at: value.created_at.to_utc(),
+            event: value.event,
+            payload: value.payload,
+            status: value.status,
+            attempts: value.attempts,
+            next_attempt_at: value.next_attempt_at.to_utc(),
+            last_attempt_at: value.last_attempt_at.map(|at| at.to_utc()),
+            response_status: value.response_status,
+            error: value.error,
+        }
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseListWebhooks),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[get("/api/v1/notifications/webhooks")]
+pub async fn list_webhooks(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseListWebhooks>, WebhooksError> {
+    let user_id = user.user_id;
+    let r = state.webhooks_service.list_webhooks_for_user(user_id).await;
+
+    match r {
+        Ok(webhooks) => Ok(web::Json(ResponseListWebhooks {
+            status: "success".to_string(),
+            data: webhooks
+                .into_iter()
+                .map(ResponseWebhookData::from)
+                .collect(),
+        })),
+        Err(err) => Err(WebhooksError::WebhooksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    request_body = CreateWebhookData,
+    responses(
+    (status = OK, description = "OK", body = ResponseCreateWebhook),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = TOO_MANY_REQUESTS, description = "Webhooks limit is reached", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[post("/api/v1/notifications/webhooks")]
+pub async fn create_webhook(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    data: web::Json<CreateWebhookData>,
+) -> Result<web::Json<ResponseCreateWebhook>, WebhooksError> {
+    let user_id = user.user_id;
+    match data.validate() {
+        Ok(_) => {
+            let r = state
+                .webhooks_service
+                .create_webhook_for_user(user_id, data.url.clone())
+                .await;
+
+            match r {
+                Ok(webhook) => Ok(web::Json(ResponseCreateWebhook {
+                    status: "success".to_string(),
+                    data: webhook.into(),
+                })),
+                Err(err) => Err(WebhooksError::WebhooksServiceError(err)),
+            }
+        }
+        Err(err) => Err(WebhooksError::InvalidInputData(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("webhook_id" = String, Path, description = "Base64 encoded webhook id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseDeleteWebhook),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Webhook not found for user", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[delete("/api/v1/notifications/webhooks/{webhook_id}")]
+pub async fn delete_webhook(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    path: web::Path<WebhookPathData>,
+) -> Result<web::Json<ResponseDeleteWebhook>, WebhooksError> {
+    let user_id = user.user_id;
+    let r = state
+        .webhooks_service
+        .delete_webhook_for_user(user_id, path.webhook_id.0.clone())
+        .await;
+
+    match r {
+        Ok(()) => Ok(web::Json(ResponseDeleteWebhook {
+            status: "success".to_string(),
+            data: String::from("success"),
+        })),
+        Err(err) => Err(WebhooksError::WebhooksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseWebhookSecret),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[get("/api/v1/notifications/webhooks/secret")]
+pub async fn get_webhook_secret(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseWebhookSecret>, WebhooksError> {
+    let user_id = user.user_id;
+    let r = state.webhooks_service.get_secret_for_user(user_id).await;
+
+    match r {
+        Ok(secret) => Ok(web::Json(ResponseWebhookSecret {
+            status: "success".to_string(),
+            data: ResponseWebhookSecretData { secret },
+        })),
+        Err(err) => Err(WebhooksError::WebhooksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseWebhookSecret),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[post("/api/v1/notifications/webhooks/secret/rotate")]
+pub async fn rotate_webhook_secret(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseWebhookSecret>, WebhooksError> {
+    let user_id = user.user_id;
+    let r = state.webhooks_service.rotate_secret_for_user(user_id).await;
+
+    match r {
+        Ok(secret) => Ok(web::Json(ResponseWebhookSecret {
+            status: "success".to_string(),
+            data: ResponseWebhookSecretData { secret },
+        })),
+        Err(err) => Err(WebhooksError::WebhooksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(
+    ("webhook_id" = String, Path, description = "Base64 encoded webhook id"),
+    ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+    ("limit" = Option<usize>, Query, description = "Max number of deliveries, 20 by default"),
+    ("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),
+    ),
+    responses(
+    (status = OK, description = "OK", body = ResponseListWebhookDeliveries),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Webhook not found for user", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "notifications"
+)]
+#[get("/api/v1/notifications/webhooks/{webhook_id}/deliveries")]
+pub async fn list_webhook_deliveries(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    path: web::Path<WebhookPathData>,
+    data: web::Query<ListWebhookDeliveriesData>,
+) -> Result<web::Json<ResponseListWebhookDeliveries>, WebhooksError> {
+    let user_id = user.user_id;
+    match data.validate() {
+        Ok(_) => {
+            let r = state
+                .webhooks_service
+                .list_deliveries_for_user(
+                    user_id,
+                    path.webhook_id.0.clone(),
+                    data.cursor.clone().map(|cursor| cursor.0),
+                    data.limit.unwrap_or(DEFAULT_PAGE_LIMIT),
+                )
+                .await;
+
+            match r {
+                Ok((deliveries, next_cursor)) => Ok(web::Json(ResponseListWebhookDeliveries {
+                    status: "success".to_string(),
+                    data: deliveries
+                        .into_iter()
+                        .map(ResponseWebhookDeliveryData::from)
+                        .collect(),
+                    next_cursor,
+                })),
+                Err(err) => Err(WebhooksError::WebhooksServiceError(err)),
+            }
+        }
+        Err(err) => Err(WebhooksError::InvalidInputData(err)),
+    }
+}
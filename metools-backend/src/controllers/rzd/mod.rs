@@ -1 +1,3 @@
+pub(crate) mod monitoring;
+pub(crate) mod stations;
 pub(crate) mod tasks;
@@ -0,0 +1,513 @@
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::{CloseReason, Closed, Message, MessageStream, Session};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::ToSchema;
+
+use crate::{
+    controllers::{
+        middlewares::UserMiddleware, rzd::tasks::ResponseListTasksData, schema::AppState,
+    },
+    models::rzd::tasks::TasksDBError,
+    services::{events::TaskEvent, tasks::TasksServiceError},
+    utils::string::{encode_thing_to_base64_string, try_decode_from_base64_to_thing},
+};
+
+/// How often the server pings the client.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Connections without any message from the client for this long are closed.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+const MAX_SUBSCRIBED_TASKS: usize = 100;
+
+/// Message from the client, task ids are base64 encoded.
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Starts sending events of the tasks, answered with the current state of each task.
+    /// Clients send it again after reconnecting.
+    Subscribe {
+        task_ids: Vec<String>,
+    },
+    Unsubscribe {
+        task_ids: Vec<String>,
+    },
+    Ping,
+}
+
+/// Message from the server.
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed {
+        task_ids: Vec<String>,
+    },
+    Unsubscribed {
+        task_ids: Vec<String>,
+    },
+    /// Current state of a task sent on subscription.
+    Task {
+        task: Box<ResponseListTasksData>,
+    },
+    /// Check result, price change or status transition of a subscribed task.
+    Event(TaskEvent),
+    /// Events were missed because the client was too slow, it should resubscribe.
+    Lagged {
+        skipped: u64,
+    },
+    Pong,
+    Error {
+        error: String,
+        status: String,
+        task_id: Option<String>,
+    },
+}
+
+impl ServerMessage {
+    fn error(error: &str, status: &str, task_id: Option<String>) -> Self {
+        Self::Error {
+            error: error.to_string(),
+            status: status.to_string(),
+            task_id,
+        }
+    }
+}
+
+/// The id events carry for a task id sent by the client, which may spell the record differently.
+fn canonical_task_id(task_id: &str) -> Option<(Thing, String)> {
+    let thing = try_decode_from_base64_to_thing(task_id)?;
+    let canonical = encode_thing_to_base64_string(thing.clone());
+    Some((thing, canonical))
+}
+
+async fn send(session: &mut Session, message: ServerMessage) -> Result<(), Closed> {
+    match serde_json::to_string(&message) {
+        Ok(text) => session.text(text).await,
+        Err(err) => {
+            log::error!("Error on serializing websocket message: {err}");
+            Ok(())
+        }
+    }
+}
+
+/// Subscribes to the tasks that belong to the user and sends their current state.
+async fn subscribe(
+    state: &AppState,
+    session: &mut Session,
+    user_id: &Thing,
+    subscriptions: &mut BTreeSet<String>,
+    task_ids: Vec<String>,
+) -> Result<(), Closed> {
+    let mut subscribed = vec![];
+    for task_id in task_ids {
+        let (thing, canonical) = match canonical_task_id(task_id.as_str()) {
+            Some(ids) => ids,
+            None => {
+                send(
+                    session,
+                    ServerMessage::error("Invalid task id", "invalid_data", Some(task_id)),
+                )
+                .await?;
+                continue;
+            }
+        };
+        if subscriptions.len() >= MAX_SUBSCRIBED_TASKS && !subscriptions.contains(&canonical) {
+            let error = format!("At most {MAX_SUBSCRIBED_TASKS} tasks can be subscribed");
+            send(
+                session,
+                ServerMessage::error(error.as_str(), "subscriptions_limit_reached", Some(task_id)),
+            )
+            .await?;
+            continue;
+        }
+
+        let r = state
+            .tasks_service
+            .get_task_for_user(user_id.clone(), thing)
+            .await;
+        let message = match r {
+            Ok(task) => {
+                subscriptions.insert(canonical.clone());
+                subscribed.push(canonical);
+                ServerMessage::Task {
+                    task: Box::new(task.into()),
+                }
+            }
+            Err(TasksServiceError::TasksDBError(TasksDBError::TaskNotFound)) => {
+                ServerMessage::error("Task not found", "not_found", Some(task_id))
+            }
+            Err(err) => {
+                log::error!("Error on subscribing to task {task_id}: {err}");
+                ServerMessage::error("Unknown error", "unknown_error", Some(task_id))
+            }
+        };
+        send(session, message).await?;
+    }
+
+    send(
+        session,
+        ServerMessage::Subscribed {
+            task_ids: subscribed,
+        },
+    )
+    .await
+}
+
+async fn handle_text(
+    state: &AppState,
+    session: &mut Session,
+    user_id: &Thing,
+    subscriptions: &mut BTreeSet<String>,
+    text: &str,
+) -> Result<(), Closed> {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe { task_ids }) => {
+            subscribe(state, session, user_id, subscriptions, task_ids).await
+        }
+        Ok(ClientMessage::Unsubscribe { task_ids }) => {
+            for task_id in task_ids.iter() {
+                if let Some((_, canonical)) = canonical_task_id(task_id.as_str()) {
+                    subscriptions.remove(&canonical);
+                }
+            }
+            send(session, ServerMessage::Unsubscribed { task_ids }).await
+        }
+        Ok(ClientMessage::Ping) => send(session, ServerMessage::Pong).await,
+        Err(_) => {
+            send(
+                session,
+                ServerMessage::error("Invalid message", "invalid_data", None),
+            )
+            .await
+        }
+    }
+}
+
+async fn run_session(
+    state: web::Data<AppState>,
+    user_id: Thing,
+    mut session: Session,
+    mut messages: MessageStream,
+) {
+    let mut events = state.task_events_service.receiver();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    let mut subscriptions: BTreeSet<String> = BTreeSet::new();
+
+    let close_reason: Option<CloseReason> = loop {
+        let r = tokio::select! {
+            message = messages.recv() => {
+                last_seen = Instant::now();
+                match message {
+                    Some(Ok(Message::Text(text))) => {
+                        handle_text(&state, &mut session, &user_id, &mut subscriptions, &text).await
+                    }
+                    Some(Ok(Message::Ping(bytes))) => session.pong(&bytes).await,
+                    Some(Ok(Message::Close(reason))) => break reason,
+                    Some(Ok(_)) => Ok(()),
+                    Some(Err(err)) => {
+                        log::warn!("Error on reading websocket message: {err}");
+                        break None;
+                    }
+                    None => break None,
+                }
+            }
+            event = events.recv() => match event {
+                Ok((event_user_id, event))
+                    if event_user_id == user_id && subscriptions.contains(&event.task_id) =>
+                {
+                    send(&mut session, ServerMessage::Event(event)).await
+                }
+                Ok(_) => Ok(()),
+                Err(RecvError::Lagged(skipped)) => {
+                    send(&mut session, ServerMessage::Lagged { skipped }).await
+                }
+                Err(RecvError::Closed) => break None,
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    break None;
+                }
+                session.ping(b"").await
+            }
+        };
+        if r.is_err() {
+            // The client is gone, nothing to close
+            return;
+        }
+    };
+
+    let _ = session.close(close_reason).await;
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = SWITCHING_PROTOCOLS, description = "WebSocket with JSON messages, the client sends ClientMessage and receives ServerMessage"),
+    (status = BAD_REQUEST, description = "Not a WebSocket handshake"),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[get("/api/v1/rzd/tasks/ws")]
+pub async fn monitor_tasks(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    actix_web::rt::spawn(run_session(state, user.user_id, session, messages));
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, HttpServer};
+    use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+    use chrono::{Days, Utc};
+    use futures_util::{SinkExt, StreamExt};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use lettre::SmtpTransport;
+    use prometheus::Registry;
+    use serde_json::{json, Value};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{
+        connect_async, tungstenite::client::IntoClientRequest, MaybeTlsStream, WebSocketStream,
+    };
+
+    use super::*;
+    use crate::{
+        config::{RzdLimitsConfig, TaskQuota, TaskQuotas},
+        controllers::users::users::TokenClaims,
+        models::{
+            rzd::tasks::{DayTask, TaskData, TaskFilters, TaskStatus},
+            users::{insert_new_user, set_user_verified},
+        },
+        services::{
+            dispatcher::{DispatcherMetrics, NotificationsDispatcher},
+            events::{TaskEventKind, TaskEventsService},
+            mailer::MailerService,
+            notifications::NotificationsService,
+            rzd::guard::RzdGuard,
+            stations::StationsService,
+            tasks::TasksService,
+            users::UsersService,
+            webhooks::WebhooksService,
+        },
+        utils::test_db::test_db_config,
+    };
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    const JWT_SECRET: &str = "secret";
+
+    #[test]
+    fn canonical_task_id_matches_event_ids() {
+        let thing = Thing::from(("rzd_tasks", "abc"));
+        let event_id = encode_thing_to_base64_string(thing.clone());
+        // Same record with the key in brackets
+        let spelled_out = BASE64_URL_SAFE_NO_PAD.encode("rzd_tasks:⟨abc⟩");
+        assert_ne!(spelled_out, event_id);
+
+        assert_eq!(
+            canonical_task_id(spelled_out.as_str()),
+            Some((thing, event_id))
+        );
+        assert_eq!(canonical_task_id("not an id"), None);
+    }
+
+    /// State of the app with a verified user.
+    async fn app_state() -> (web::Data<AppState>, Thing) {
+        let db = test_db_config().await;
+        let conn = db.get_connection().await;
+        let user = insert_new_user(
+            &conn,
+            String::from("user"),
+            String::from("user@example.com"),
+            String::from("password"),
+        )
+        .await
+        .unwrap();
+        set_user_verified(&conn, user.id.clone()).await.unwrap();
+        let mailer = MailerService::init(
+            SmtpTransport::unencrypted_localhost(),
+            String::from("metools@example.com"),
+            String::from("http://localhost"),
+        );
+        let dispatcher = NotificationsDispatcher::init(
+            db.clone(),
+            mailer.clone(),
+            None,
+            3,
+            60,
+            DispatcherMetrics::init(&Registry::new()),
+        );
+        let webhooks = WebhooksService::init(db.clone(), 3, 60);
+        let quota = TaskQuota {
+            max_active_tasks: 10,
+            min_check_interval: 300,
+        };
+        let events = TaskEventsService::init();
+        let limits = RzdLimitsConfig {
+            rate_limit: 1.0,
+            rate_burst: 1,
+            max_retries: 0,
+            retry_delay: 0,
+            circuit_failures: 2,
+            circuit_open_duration: 60,
+        };
+        let state = AppState {
+            users_service: UsersService::init(db.clone(), mailer, dispatcher.clone()),
+            tasks_service: TasksService::init(
+                db.clone(),
+                TaskQuotas {
+                    user: quota.clone(),
+                    premium: quota,
+                },
+                events.clone(),
+            ),
+            stations_service: StationsService::init(db.clone()),
+            telegram_service: None,
+            webhooks_service: webhooks.clone(),
+            notifications_service: NotificationsService::init(
+                db.clone(),
+                dispatcher,
+                webhooks,
+                3600,
+            ),
+            task_events_service: events,
+            rzd_guard: RzdGuard::init(limits, &Registry::new()),
+            jwt_secret: String::from(JWT_SECRET),
+            jwt_maxage: 60,
+        };
+        (web::Data::new(state), user.id)
+    }
+
+    /// Serves the websocket route on a free port and returns its address.
+    fn serve(state: web::Data<AppState>) -> String {
+        let server =
+            HttpServer::new(move || App::new().app_data(state.clone()).service(monitor_tasks))
+                .workers(1)
+                .bind(("127.0.0.1", 0))
+                .unwrap();
+        let address = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        format!("ws://{address}/api/v1/rzd/tasks/ws")
+    }
+
+    async fn connect(url: &str, user_id: Thing) -> Client {
+        let now = Utc::now().timestamp() as usize;
+        let claims = TokenClaims {
+            sub: encode_thing_to_base64_string(user_id),
+            iat: now,
+            exp: now + 60,
+        };
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(JWT_SECRET.as_ref()),
+        )
+        .unwrap();
+        let mut request = url.into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("X-API-AUTH-TOKEN", token.parse().unwrap());
+        let (client, _) = connect_async(request).await.unwrap();
+        client
+    }
+
+    async fn send_json(client: &mut Client, message: Value) {
+        client
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                message.to_string(),
+            ))
+            .await
+            .unwrap();
+    }
+
+    /// Next text message from the server, pings are skipped.
+    async fn receive(client: &mut Client) -> Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = message {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn subscribed_clients_receive_task_events() {
+        let (state, user_id) = app_state().await;
+        let data = TaskData::Day(DayTask {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from("2000000"),
+            date: Utc::now().date_naive() + Days::new(1),
+        });
+        let task = state
+            .tasks_service
+            .create_task_for_user(user_id.clone(), data, TaskFilters::default(), None, None)
+            .await
+            .unwrap();
+        let task_id = encode_thing_to_base64_string(task.id.clone());
+        // The client spells the record differently from the events
+        let spelled_out =
+            BASE64_URL_SAFE_NO_PAD.encode(format!("{}:⟨{}⟩", task.id.tb, task.id.id.to_raw()));
+        let missing = encode_thing_to_base64_string(Thing::from(("rzd_tasks", "missing")));
+        let url = serve(state.clone());
+        let mut client = connect(url.as_str(), user_id.clone()).await;
+
+        send_json(
+            &mut client,
+            json!({"type": "subscribe", "task_ids": [spelled_out, missing, "bm90LWEtdGhpbmc"]}),
+        )
+        .await;
+        let message = receive(&mut client).await;
+        assert_eq!(message["type"], "task");
+        assert_eq!(message["task"]["id"], task_id.as_str());
+        let message = receive(&mut client).await;
+        assert_eq!(message["status"], "not_found");
+        assert_eq!(message["task_id"], missing.as_str());
+        let message = receive(&mut client).await;
+        assert_eq!(message["type"], "error");
+        assert_eq!(message["status"], "invalid_data");
+        assert_eq!(
+            receive(&mut client).await,
+            json!({"type": "subscribed", "task_ids": [task_id]})
+        );
+
+        let status_changed = TaskEventKind::StatusChanged {
+            status: TaskStatus::Paused,
+        };
+        state
+            .task_events_service
+            .publish(user_id.clone(), task.id.clone(), status_changed.clone());
+        let message = receive(&mut client).await;
+        assert_eq!(message["type"], "event");
+        assert_eq!(message["task_id"], task_id.as_str());
+        assert_eq!(message["event"], "status_changed");
+
+        // Nothing arrives after unsubscribing, the pong comes first
+        send_json(
+            &mut client,
+            json!({"type": "unsubscribe", "task_ids": [spelled_out]}),
+        )
+        .await;
+        assert_eq!(receive(&mut client).await["type"], "unsubscribed");
+        state
+            .task_events_service
+            .publish(user_id, task.id, status_changed);
+        send_json(&mut client, json!({"type": "ping"})).await;
+        assert_eq!(receive(&mut client).await, json!({"type": "pong"}));
+    }
+}
@@ -0,0 +1,86 @@
+use actix_web::{
+    body::BoxBody,
+    get,
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse, ResponseError,
+};
+use derive_more::Display;
+use serde::Deserialize;
+use serde_json::json;
+use validator::{Validate, ValidationErrors};
+
+use crate::{
+    controllers::schema::{AppState, ResponseSearchStations},
+    services::stations::StationsServiceError,
+};
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+#[derive(Debug, Display)]
+enum StationsError {
+    InvalidInputData(ValidationErrors),
+    StationsServiceError(StationsServiceError),
+}
+
+impl ResponseError for StationsError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidInputData(_) => StatusCode::BAD_REQUEST,
+            Self::StationsServiceError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        match self {
+            Self::InvalidInputData(_errors) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Invalid input data", "status": "invalid_data"}).to_string()),
+            Self::StationsServiceError(_) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct SearchStationsData {
+    #[validate(length(min = 2, max = 100))]
+    q: String,
+    #[validate(range(min = 1, max = 100))]
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    params(
+    ("q" = String, Query, description = "Part of a station name in Russian or Latin"),
+    ("limit" = Option<usize>, Query, description = "Max number of stations, 20 by default"),
+    ),
+    responses(
+    (status = OK, description = "OK", body = ResponseSearchStations),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+tag = "stations"
+)]
+#[get("/api/v1/rzd/stations")]
+pub async fn search_stations(
+    state: web::Data<AppState>,
+    data: web::Query<SearchStationsData>,
+) -> Result<web::Json<ResponseSearchStations>, StationsError> {
+    match data.validate() {
+        Ok(_) => {
+            let r = state
+                .stations_service
+                .search_stations(data.q.clone(), data.limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+                .await;
+
+            match r {
+                Ok(stations) => Ok(web::Json(ResponseSearchStations {
+                    status: "success".to_string(),
+                    data: stations,
+                })),
+                Err(err) => Err(StationsError::StationsServiceError(err)),
+            }
+        }
+        Err(err) => Err(StationsError::InvalidInputData(err)),
+    }
+}
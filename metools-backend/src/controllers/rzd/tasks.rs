@@ -1,26 +1,46 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use actix_web::{
     body::BoxBody,
     delete, get,
-    http::{header::ContentType, StatusCode},
-    post, web, HttpResponse, ResponseError,
+    http::{
+        header::{CacheControl, CacheDirective, ContentEncoding, ContentType},
+        StatusCode,
+    },
+    patch, post, put, web, HttpResponse, ResponseError,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use derive_more::Display;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use serde_json::json;
 use utoipa::ToSchema;
-use validator::{Validate, ValidateArgs, ValidationError, ValidationErrors};
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use crate::{
     controllers::{
         middlewares::UserMiddleware,
-        schema::{AppState, ResponseCreateTask, ResponseDeleteTaskByIdForUser, ResponseListTasks},
+        schema::{
+            AppState, ResponseChangeTaskStatusForUser, ResponseCreateTask,
+            ResponseDeleteTaskByIdForUser, ResponseGetTask, ResponseListTaskChecks,
+            ResponseListTasks, ResponseTaskPrices, ResponseUpdateTask,
+        },
+    },
+    models::{
+        notifications::TaskNotificationOverrides,
+        rzd::{
+            task_checks::TaskCheckRecord,
+            task_prices::TaskPrice,
+            tasks::{
+                validate_station_code, validate_task_filters, PriceAlert, Task, TaskCheck,
+                TaskData, TaskFilters, TaskStatus, TaskType, TasksDBError, TasksQuery, TripLeg,
+            },
+        },
     },
-    models::rzd::tasks::{Task, TasksDBError},
     services::tasks::TasksServiceError,
-    utils::thing::Base64EncodedThing,
+    utils::{
+        pagination::{EncodedCursor, SortOrder, DEFAULT_PAGE_LIMIT},
+        thing::Base64EncodedThing,
+    },
 };
 
 #[derive(Debug, Display)]
@@ -36,9 +56,19 @@ impl ResponseError for TasksError {
             Self::TasksServiceError(error) => match error {
                 TasksServiceError::TasksDBError(error) => match error {
                     TasksDBError::NoDeletedTask => StatusCode::NOT_FOUND,
+                    TasksDBError::TaskNotFound => StatusCode::NOT_FOUND,
+                    TasksDBError::ActiveTasksLimitReached(_) => StatusCode::TOO_MANY_REQUESTS,
                     TasksDBError::UnknownError(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 },
-                TasksServiceError::UnknownError => StatusCode::INTERNAL_SERVER_ERROR,
+                TasksServiceError::InvalidTaskStatus(_) => StatusCode::CONFLICT,
+                TasksServiceError::StationsDBError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                TasksServiceError::TaskChecksDBError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                TasksServiceError::TaskPricesDBError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                TasksServiceError::UnknownStationCodes(_) => StatusCode::BAD_REQUEST,
+                TasksServiceError::InvalidTaskFilters(_) => StatusCode::BAD_REQUEST,
+                TasksServiceError::UsersDBError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                TasksServiceError::ActiveTasksLimitReached(_) => StatusCode::TOO_MANY_REQUESTS,
+                TasksServiceError::CheckIntervalTooShort(_) => StatusCode::FORBIDDEN,
             },
         }
     }
@@ -49,11 +79,22 @@ impl ResponseError for TasksError {
                 .body(json!({"error": "Invalid input data", "status": "invalid_data"}).to_string()),
             Self::TasksServiceError(error) => match error {
                 TasksServiceError::TasksDBError(error) => match error {
-                    TasksDBError::NoDeletedTask => HttpResponse::build(self.status_code())
-                        .insert_header(ContentType::json())
-                        .body(
-                            json!({"error": "Task not found", "status": "not_found"}).to_string(),
-                        ),
+                    TasksDBError::NoDeletedTask | TasksDBError::TaskNotFound => {
+                        HttpResponse::build(self.status_code())
+                            .insert_header(ContentType::json())
+                            .body(
+                                json!({"error": "Task not found", "status": "not_found"})
+                                    .to_string(),
+                            )
+                    }
+                    TasksDBError::ActiveTasksLimitReached(limit) => {
+                        HttpResponse::build(self.status_code())
+                            .insert_header(ContentType::json())
+                            .body(
+                                json!({"error": format!("Active tasks limit of {limit} is reached"), "status": "tasks_limit_reached"})
+                                    .to_string(),
+                            )
+                    }
                     TasksDBError::UnknownError(_) => HttpResponse::build(self.status_code())
                         .insert_header(ContentType::json())
                         .body(
@@ -61,81 +102,255 @@ impl ResponseError for TasksError {
                                 .to_string(),
                         ),
                 },
-                TasksServiceError::UnknownError => HttpResponse::build(self.status_code())
+                TasksServiceError::InvalidTaskStatus(status) => {
+                    HttpResponse::build(self.status_code())
+                        .insert_header(ContentType::json())
+                        .body(
+                            json!({"error": format!("Task status {status} does not allow this action"), "status": "invalid_task_status"})
+                                .to_string(),
+                        )
+                }
+                TasksServiceError::StationsDBError(_)
+                | TasksServiceError::TaskChecksDBError(_)
+                | TasksServiceError::TaskPricesDBError(_)
+                | TasksServiceError::UsersDBError(_) => {
+                    HttpResponse::build(self.status_code())
+                        .insert_header(ContentType::json())
+                        .body(
+                            json!({"error": "Unknown error", "status": "unknown_error"})
+                                .to_string(),
+                        )
+                }
+                TasksServiceError::InvalidTaskFilters(_) => HttpResponse::build(self.status_code())
                     .insert_header(ContentType::json())
-                    .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
+                    .body(
+                        json!({"error": "Invalid input data", "status": "invalid_data"})
+                            .to_string(),
+                    ),
+                TasksServiceError::ActiveTasksLimitReached(limit) => {
+                    HttpResponse::build(self.status_code())
+                        .insert_header(ContentType::json())
+                        .body(
+                            json!({"error": format!("Active tasks limit of {limit} is reached"), "status": "tasks_limit_reached"})
+                                .to_string(),
+                        )
+                }
+                TasksServiceError::CheckIntervalTooShort(min_interval) => {
+                    HttpResponse::build(self.status_code())
+                        .insert_header(ContentType::json())
+                        .body(
+                            json!({"error": format!("Check interval must be at least {min_interval} seconds on your plan"), "status": "check_interval_not_allowed"})
+                                .to_string(),
+                        )
+                }
+                TasksServiceError::UnknownStationCodes(codes) => {
+                    HttpResponse::build(self.status_code())
+                        .insert_header(ContentType::json())
+                        .body(
+                            json!({"error": format!("Unknown station codes: {}", codes.join(", ")), "status": "unknown_station"})
+                                .to_string(),
+                        )
+                }
             },
         }
     }
 }
 
-pub struct ValidateCreateTaskDataContext(String);
+fn validate_create_task_data(data: &CreateTaskData) -> Result<(), ValidationError> {
+    validate_task_filters(&data.data, &data.filters)
+}
 
-fn validate_create_task_data(
-    data: &HashMap<String, String>,
-    context: &ValidateCreateTaskDataContext,
-) -> Result<(), ValidationError> {
-    match context.0.as_str() {
-        "day" => {
-            if data.keys().len() != 3 {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("from_point_code") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("to_point_code") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("date") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            Ok(())
-        }
-        "train" => {
-            if data.keys().len() != 5 {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("from_point_code") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("to_point_code") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("date") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("time") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            if !data.contains_key("tnum") {
-                return Err(ValidationError::new("bad_task_data"));
-            }
-            Ok(())
-        }
-        &_ => Err(ValidationError::new("unknown_task_type")),
+#[derive(Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "validate_create_task_data"))]
+pub struct CreateTaskData {
+    #[serde(flatten)]
+    #[validate(nested)]
+    data: TaskData,
+    #[serde(default)]
+    #[validate(nested)]
+    filters: TaskFilters,
+    #[validate(nested)]
+    price_alert: Option<PriceAlert>,
+    /// Seconds between checks, the shortest interval of the user's plan by default.
+    #[validate(range(min = 1))]
+    check_interval: Option<u64>,
+}
+
+/// Reads the optional flattened `type` and `data` of a patch, rejecting half or malformed ones
+/// that a plain flattened `Option` would silently drop.
+fn deserialize_task_data_patch<'de, D>(deserializer: D) -> Result<Option<TaskData>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct TaskDataPatch {
+        #[serde(rename = "type")]
+        task_type: Option<serde_json::Value>,
+        data: Option<serde_json::Value>,
+    }
+
+    let patch = TaskDataPatch::deserialize(deserializer)?;
+    match (patch.task_type, patch.data) {
+        (None, None) => Ok(None),
+        (task_type, data) => serde_json::from_value(json!({"type": task_type, "data": data}))
+            .map(Some)
+            .map_err(D::Error::custom),
     }
 }
 
+/// Fields to change, omitted ones keep their current value.
 #[derive(Deserialize, ToSchema, Validate)]
-#[validate(context = ValidateCreateTaskDataContext)]
-pub struct CreateTaskData {
-    task_type: String,
-    #[validate(custom(function = "validate_create_task_data", use_context))]
-    data: HashMap<String, String>,
+pub struct UpdateTaskData {
+    /// Replaces what the task watches and restarts its checks, same `type` and `data` as on create.
+    #[serde(flatten, deserialize_with = "deserialize_task_data_patch")]
+    #[validate(nested)]
+    task: Option<TaskData>,
+    #[validate(nested)]
+    filters: Option<TaskFilters>,
+    /// `null` removes the price alert.
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    #[schema(value_type = Option<PriceAlert>)]
+    #[validate(nested)]
+    price_alert: Option<Option<PriceAlert>>,
+    #[validate(range(min = 1))]
+    check_interval: Option<u64>,
 }
 
 #[derive(Deserialize, Clone)]
-struct DeleteTaskData {
+struct TaskPathData {
     task_id: Base64EncodedThing,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ResponseListTasksData {
+    #[schema(value_type = String)]
+    pub id: Base64EncodedThing,
     pub created_at: DateTime<Utc>,
-    #[serde(rename = "type")]
-    pub type_: String,
-    pub data: HashMap<String, String>,
+    #[serde(flatten)]
+    pub data: TaskData,
+    #[schema(value_type = String)]
     pub user: Base64EncodedThing,
+    pub filters: TaskFilters,
+    pub price_alert: Option<PriceAlert>,
+    pub check_interval: Option<u64>,
+    pub notifications: Option<TaskNotificationOverrides>,
+    pub status: TaskStatus,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub last_check: Option<TaskCheck>,
+    pub next_check_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Validate)]
+struct ListTasksData {
+    #[serde(rename = "type")]
+    task_type: Option<TaskType>,
+    status: Option<TaskStatus>,
+    date_from: Option<NaiveDate>,
+    date_to: Option<NaiveDate>,
+    #[validate(custom(function = "validate_station_code"))]
+    station_code: Option<String>,
+    order: Option<SortOrder>,
+    cursor: Option<EncodedCursor>,
+    #[validate(range(min = 1, max = 100))]
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, Validate)]
+struct ListTaskChecksData {
+    cursor: Option<EncodedCursor>,
+    #[validate(range(min = 1, max = 100))]
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseTaskCheckData {
+    pub checked_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub check: TaskCheck,
+    pub notified: bool,
+}
+
+impl From<TaskCheckRecord> for ResponseTaskCheckData {
+    fn from(value: TaskCheckRecord) -> Self {
+        Self {
+            checked_at: value.created_at.to_utc(),
+            check: value.check,
+            notified: value.notified,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListTaskPricesData {
+    since: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PricePoint {
+    pub checked_at: DateTime<Utc>,
+    pub min_price: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PriceSeries {
+    /// Travel date, not set for prices stored before they were tracked per date.
+    #[schema(value_type = Option<String>, format = Date)]
+    pub date: Option<NaiveDate>,
+    /// Set for round-trip tasks.
+    pub leg: Option<TripLeg>,
+    pub car_type: String,
+    pub points: Vec<PricePoint>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResponseTaskPricesData {
+    pub series: Vec<PriceSeries>,
+}
+
+impl From<Vec<TaskPrice>> for ResponseTaskPricesData {
+    fn from(value: Vec<TaskPrice>) -> Self {
+        let mut series: BTreeMap<(Option<NaiveDate>, Option<TripLeg>, String), Vec<PricePoint>> =
+            BTreeMap::new();
+        for price in value {
+            series
+                .entry((price.date, price.leg, price.car_type))
+                .or_default()
+                .push(PricePoint {
+                    checked_at: price.created_at.to_utc(),
+                    min_price: price.min_price,
+                });
+        }
+        Self {
+            series: series
+                .into_iter()
+                .map(|((date, leg, car_type), points)| PriceSeries {
+                    date,
+                    leg,
+                    car_type,
+                    points,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Task> for ResponseListTasksData {
+    fn from(task: Task) -> Self {
+        Self {
+            id: Base64EncodedThing(task.id),
+            created_at: task.created_at.to_utc(),
+            data: task.data,
+            user: Base64EncodedThing(task.user),
+            filters: task.filters,
+            price_alert: task.price_alert,
+            check_interval: task.check_interval,
+            notifications: task.notifications,
+            status: task.status,
+            last_checked_at: task.last_checked_at.map(|d| d.to_utc()),
+            last_check: task.last_check,
+            next_check_at: task.next_check_at.map(|d| d.to_utc()),
+        }
+    }
 }
 
 struct VecTask(Vec<Task>);
@@ -146,21 +361,27 @@ impl From<VecTask> for VecResponseListTasksData {
         let mut r: VecResponseListTasksData = VecResponseListTasksData(Vec::new());
 
         for task in value.0 {
-            r.0.push(ResponseListTasksData {
-                created_at: task.created_at.to_utc(),
-                type_: task.type_,
-                data: task.data,
-                user: Base64EncodedThing(task.user),
-            })
+            r.0.push(task.into())
         }
         r
     }
 }
 
 #[utoipa::path(
-    params(("X-API-AUTH-TOKEN" = Uuid, Header, description = "Auth token"),),
+    params(
+    ("type" = Option<TaskType>, Query, description = "Only tasks of this type"),
+    ("status" = Option<TaskStatus>, Query, description = "Only tasks with this status"),
+    ("date_from" = Option<NaiveDate>, Query, description = "Only tasks with travel dates on or after this day"),
+    ("date_to" = Option<NaiveDate>, Query, description = "Only tasks with travel dates on or before this day"),
+    ("station_code" = Option<String>, Query, description = "Only tasks departing from or arriving at this station"),
+    ("order" = Option<SortOrder>, Query, description = "Order by creation time, desc by default"),
+    ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+    ("limit" = Option<usize>, Query, description = "Max number of tasks, 20 by default"),
+    ("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),
+    ),
     responses(
     (status = OK, description = "OK", body = ResponseListTasks),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
     (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
     (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
     ),
@@ -170,29 +391,53 @@ tag = "tasks"
 pub async fn list_tasks(
     user: UserMiddleware,
     state: web::Data<AppState>,
+    data: web::Query<ListTasksData>,
 ) -> Result<web::Json<ResponseListTasks>, TasksError> {
     let user_id = user.user_id;
+    match data.validate() {
+        Ok(_) => {
+            let data = data.into_inner();
+            let r = state
+                .tasks_service
+                .list_tasks_for_user(
+                    user_id,
+                    TasksQuery {
+                        task_type: data.task_type,
+                        status: data.status,
+                        date_from: data.date_from,
+                        date_to: data.date_to,
+                        station_code: data.station_code,
+                        order: data.order.unwrap_or_default(),
+                        cursor: data.cursor.map(|cursor| cursor.0),
+                        limit: data.limit.unwrap_or(DEFAULT_PAGE_LIMIT),
+                    },
+                )
+                .await;
 
-    let r = state.tasks_service.list_tasks_for_user(user_id).await;
-
-    match r {
-        Ok(tasks) => Ok(web::Json(ResponseListTasks {
-            status: "success".to_string(),
-            data: {
-                let r: VecResponseListTasksData = VecTask(tasks).into();
-                r.0
-            },
-        })),
-        Err(err) => Err(TasksError::TasksServiceError(err)),
+            match r {
+                Ok((tasks, next_cursor)) => Ok(web::Json(ResponseListTasks {
+                    status: "success".to_string(),
+                    data: {
+                        let r: VecResponseListTasksData = VecTask(tasks).into();
+                        r.0
+                    },
+                    next_cursor,
+                })),
+                Err(err) => Err(TasksError::TasksServiceError(err)),
+            }
+        }
+        Err(err) => Err(TasksError::InvalidInputData(err)),
     }
 }
 
 #[utoipa::path(
-    params(("X-API-AUTH-TOKEN" = Uuid, Header, description = "Auth token"),),
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
     responses(
     (status = OK, description = "OK", body = ResponseCreateTask),
     (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
     (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = FORBIDDEN, description = "Check interval is shorter than the plan allows", body = ErrorResponse),
+    (status = TOO_MANY_REQUESTS, description = "Active tasks limit of the plan is reached", body = ErrorResponse),
     (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
     ),
 tag = "tasks"
@@ -204,17 +449,23 @@ pub async fn create_task(
     data: web::Json<CreateTaskData>,
 ) -> Result<web::Json<ResponseCreateTask>, TasksError> {
     let user_id = user.user_id;
-    match data.validate_with_args(&ValidateCreateTaskDataContext(data.task_type.clone())) {
+    match data.validate() {
         Ok(_) => {
             let r = state
                 .tasks_service
-                .create_task_for_user(user_id, data.task_type.clone(), data.data.clone())
+                .create_task_for_user(
+                    user_id,
+                    data.data.clone(),
+                    data.filters.clone(),
+                    data.price_alert.clone(),
+                    data.check_interval,
+                )
                 .await;
 
             match r {
                 Ok(task) => Ok(web::Json(ResponseCreateTask {
                     status: "success".to_string(),
-                    data: task,
+                    data: task.into(),
                 })),
                 Err(err) => Err(TasksError::TasksServiceError(err)),
             }
@@ -224,7 +475,138 @@ pub async fn create_task(
 }
 
 #[utoipa::path(
-    params(("task_id" = Uuid, Path, description = "Task id"),("X-API-AUTH-TOKEN" = Uuid, Header, description = "Auth token"),),
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "Server-Sent Events about checks, found seats and status changes of the user's tasks, the event name is the `event` field", body = TaskEvent, content_type = "text/event-stream"),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[get("/api/v1/rzd/tasks/stream")]
+pub async fn stream_task_events(user: UserMiddleware, state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        // Keeps the compression middleware from buffering events
+        .insert_header(ContentEncoding::Identity)
+        .streaming(state.task_events_service.subscribe(user.user_id))
+}
+
+#[utoipa::path(
+    params(("task_id" = String, Path, description = "Base64 encoded task id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseGetTask),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[get("/api/v1/rzd/tasks/{task_id}")]
+pub async fn get_task_by_id_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    data: web::Path<TaskPathData>,
+) -> Result<web::Json<ResponseGetTask>, TasksError> {
+    let user_id = user.user_id;
+
+    let r = state
+        .tasks_service
+        .get_task_for_user(user_id, data.task_id.0.clone())
+        .await;
+
+    match r {
+        Ok(task) => Ok(web::Json(ResponseGetTask {
+            status: "success".to_string(),
+            data: task.into(),
+        })),
+        Err(err) => Err(TasksError::TasksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("task_id" = String, Path, description = "Base64 encoded task id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseUpdateTask),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = FORBIDDEN, description = "Check interval is shorter than the plan allows", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = TOO_MANY_REQUESTS, description = "Active tasks limit of the plan is reached", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[patch("/api/v1/rzd/tasks/{task_id}")]
+pub async fn update_task_by_id_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    path: web::Path<TaskPathData>,
+    data: web::Json<UpdateTaskData>,
+) -> Result<web::Json<ResponseUpdateTask>, TasksError> {
+    let user_id = user.user_id;
+    match data.validate() {
+        Ok(_) => {
+            let r = state
+                .tasks_service
+                .update_task_for_user(
+                    user_id,
+                    path.task_id.0.clone(),
+                    data.task.clone(),
+                    data.filters.clone(),
+                    data.price_alert.clone(),
+                    data.check_interval,
+                )
+                .await;
+
+            match r {
+                Ok(task) => Ok(web::Json(ResponseUpdateTask {
+                    status: "success".to_string(),
+                    data: task.into(),
+                })),
+                Err(err) => Err(TasksError::TasksServiceError(err)),
+            }
+        }
+        Err(err) => Err(TasksError::InvalidInputData(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("task_id" = String, Path, description = "Base64 encoded task id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    request_body = TaskNotificationOverrides,
+    responses(
+    (status = OK, description = "OK", body = ResponseUpdateTask),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[put("/api/v1/rzd/tasks/{task_id}/notifications")]
+pub async fn set_task_notifications_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    path: web::Path<TaskPathData>,
+    data: web::Json<TaskNotificationOverrides>,
+) -> Result<web::Json<ResponseUpdateTask>, TasksError> {
+    let user_id = user.user_id;
+
+    let r = state
+        .tasks_service
+        .set_task_notifications_for_user(user_id, path.task_id.0.clone(), data.into_inner())
+        .await;
+
+    match r {
+        Ok(task) => Ok(web::Json(ResponseUpdateTask {
+            status: "success".to_string(),
+            data: task.into(),
+        })),
+        Err(err) => Err(TasksError::TasksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("task_id" = String, Path, description = "Base64 encoded task id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
     responses(
     (status = OK, description = "OK", body = ResponseDeleteTaskByIdForUser),
     (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
@@ -237,7 +619,7 @@ pub async fn create_task(
 pub async fn delete_task_by_id_for_user(
     user: UserMiddleware,
     state: web::Data<AppState>,
-    data: web::Path<DeleteTaskData>,
+    data: web::Path<TaskPathData>,
 ) -> Result<web::Json<ResponseDeleteTaskByIdForUser>, TasksError> {
     let user_id = user.user_id;
 
@@ -256,7 +638,7 @@ pub async fn delete_task_by_id_for_user(
 }
 
 #[utoipa::path(
-    params(("X-API-AUTH-TOKEN" = Uuid, Header, description = "Auth token"),),
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
     responses(
     (status = OK, description = "OK", body = ResponseDeleteAllTasksForUser),
     (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
@@ -281,3 +663,187 @@ pub async fn delete_all_tasks_for_user(
         Err(err) => Err(TasksError::TasksServiceError(err)),
     }
 }
+
+#[utoipa::path(
+    params(("task_id" = String, Path, description = "Base64 encoded task id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseChangeTaskStatusForUser),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = CONFLICT, description = "Task is not active", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[post("/api/v1/rzd/tasks/{task_id}/pause")]
+pub async fn pause_task_by_id_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    data: web::Path<TaskPathData>,
+) -> Result<web::Json<ResponseChangeTaskStatusForUser>, TasksError> {
+    let user_id = user.user_id;
+
+    let r = state
+        .tasks_service
+        .pause_task_for_user(user_id, data.task_id.0.clone())
+        .await;
+
+    match r {
+        Ok(_) => Ok(web::Json(ResponseChangeTaskStatusForUser {
+            status: "success".to_string(),
+            data: String::from("success"),
+        })),
+        Err(err) => Err(TasksError::TasksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("task_id" = String, Path, description = "Base64 encoded task id"),("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseChangeTaskStatusForUser),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = CONFLICT, description = "Task is not paused or failed", body = ErrorResponse),
+    (status = TOO_MANY_REQUESTS, description = "Active tasks limit of the plan is reached", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[post("/api/v1/rzd/tasks/{task_id}/resume")]
+pub async fn resume_task_by_id_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    data: web::Path<TaskPathData>,
+) -> Result<web::Json<ResponseChangeTaskStatusForUser>, TasksError> {
+    let user_id = user.user_id;
+
+    let r = state
+        .tasks_service
+        .resume_task_for_user(user_id, data.task_id.0.clone())
+        .await;
+
+    match r {
+        Ok(_) => Ok(web::Json(ResponseChangeTaskStatusForUser {
+            status: "success".to_string(),
+            data: String::from("success"),
+        })),
+        Err(err) => Err(TasksError::TasksServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(
+    ("task_id" = String, Path, description = "Base64 encoded task id"),
+    ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+    ("limit" = Option<usize>, Query, description = "Max number of checks, 20 by default"),
+    ("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),
+    ),
+    responses(
+    (status = OK, description = "OK", body = ResponseListTaskChecks),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[get("/api/v1/rzd/tasks/{task_id}/checks")]
+pub async fn list_task_checks_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    path: web::Path<TaskPathData>,
+    data: web::Query<ListTaskChecksData>,
+) -> Result<web::Json<ResponseListTaskChecks>, TasksError> {
+    let user_id = user.user_id;
+    match data.validate() {
+        Ok(_) => {
+            let r = state
+                .tasks_service
+                .list_task_checks_for_user(
+                    user_id,
+                    path.task_id.0.clone(),
+                    data.cursor.clone().map(|cursor| cursor.0),
+                    data.limit.unwrap_or(DEFAULT_PAGE_LIMIT),
+                )
+                .await;
+
+            match r {
+                Ok((checks, next_cursor)) => Ok(web::Json(ResponseListTaskChecks {
+                    status: "success".to_string(),
+                    data: checks
+                        .into_iter()
+                        .map(ResponseTaskCheckData::from)
+                        .collect(),
+                    next_cursor,
+                })),
+                Err(err) => Err(TasksError::TasksServiceError(err)),
+            }
+        }
+        Err(err) => Err(TasksError::InvalidInputData(err)),
+    }
+}
+
+#[utoipa::path(
+    params(
+    ("task_id" = String, Path, description = "Base64 encoded task id"),
+    ("since" = Option<DateTime<Utc>>, Query, description = "Only prices seen at or after this moment"),
+    ("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),
+    ),
+    responses(
+    (status = OK, description = "OK", body = ResponseTaskPrices),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = NOT_FOUND, description = "Task not found for user", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "INTERNAL_SERVER_ERROR", body = ErrorResponse)
+    ),
+    tag = "tasks"
+)]
+#[get("/api/v1/rzd/tasks/{task_id}/prices")]
+pub async fn list_task_prices_for_user(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    path: web::Path<TaskPathData>,
+    data: web::Query<ListTaskPricesData>,
+) -> Result<web::Json<ResponseTaskPrices>, TasksError> {
+    let user_id = user.user_id;
+
+    let r = state
+        .tasks_service
+        .list_task_prices_for_user(user_id, path.task_id.0.clone(), data.since)
+        .await;
+
+    match r {
+        Ok(prices) => Ok(web::Json(ResponseTaskPrices {
+            status: "success".to_string(),
+            data: prices.into(),
+        })),
+        Err(err) => Err(TasksError::TasksServiceError(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_task_data_reads_flattened_task() {
+        let data: UpdateTaskData = serde_json::from_value(json!({
+            "type": "day",
+            "data": {"from_point_code": "2004000", "to_point_code": "2000000", "date": "2024-08-01"},
+            "check_interval": 600,
+        }))
+        .unwrap();
+        assert!(matches!(data.task, Some(TaskData::Day(_))));
+        assert_eq!(data.check_interval, Some(600));
+
+        let data: UpdateTaskData = serde_json::from_value(json!({"check_interval": 600})).unwrap();
+        assert!(data.task.is_none());
+
+        for malformed in [
+            json!({"type": "day"}),
+            json!({"type": "day", "data": {"from_point_code": "2004000"}}),
+        ] {
+            assert!(serde_json::from_value::<UpdateTaskData>(malformed).is_err());
+        }
+    }
+}
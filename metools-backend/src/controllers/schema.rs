@@ -3,17 +3,33 @@ use utoipa::ToSchema;
 
 use crate::{
     controllers::{
-        rzd::tasks::ResponseListTasksData,
+        notifications::{
+            telegram::{ResponseTelegramChatData, ResponseTelegramLinkData},
+            webhooks::{
+                ResponseWebhookData, ResponseWebhookDeliveryData, ResponseWebhookSecretData,
+            },
+        },
+        rzd::tasks::{ResponseListTasksData, ResponseTaskCheckData, ResponseTaskPricesData},
         users::users::{ResponseMeData, ResponseSignupData},
     },
-    models::rzd::tasks::Task,
-    services::{tasks::TasksService, users::UsersService},
+    models::{notifications::NotificationSettings, rzd::stations::Station},
+    services::{
+        events::TaskEventsService, notifications::NotificationsService, rzd::guard::RzdGuard,
+        stations::StationsService, tasks::TasksService, telegram::bot::TelegramService,
+        users::UsersService, webhooks::WebhooksService,
+    },
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub users_service: UsersService,
     pub tasks_service: TasksService,
+    pub stations_service: StationsService,
+    pub telegram_service: Option<TelegramService>,
+    pub webhooks_service: WebhooksService,
+    pub notifications_service: NotificationsService,
+    pub task_events_service: TaskEventsService,
+    pub rzd_guard: RzdGuard,
     pub jwt_secret: String,
     pub jwt_maxage: usize,
 }
@@ -22,15 +38,38 @@ pub struct AppState {
 #[aliases(ResponseMe = Response<ResponseMeData>,
     ResponseLogin = Response<String>,
     ResponseSignup = Response<ResponseSignupData>,
-    ResponseListTasks = Response<Vec<ResponseListTasksData>>,
-    ResponseCreateTask = Response<Task>,
+    ResponseNotificationSettings = Response<NotificationSettings>,
+    ResponseCreateTask = Response<ResponseListTasksData>,
+    ResponseGetTask = Response<ResponseListTasksData>,
+    ResponseUpdateTask = Response<ResponseListTasksData>,
     ResponseDeleteTaskByIdForUser = Response<String>,
-    ResponseDeleteAllTasksForUser = Response<String>)]
+    ResponseDeleteAllTasksForUser = Response<String>,
+    ResponseChangeTaskStatusForUser = Response<String>,
+    ResponseSearchStations = Response<Vec<Station>>,
+    ResponseTaskPrices = Response<ResponseTaskPricesData>,
+    ResponseTelegramChat = Response<ResponseTelegramChatData>,
+    ResponseTelegramLink = Response<ResponseTelegramLinkData>,
+    ResponseUnlinkTelegram = Response<String>,
+    ResponseListWebhooks = Response<Vec<ResponseWebhookData>>,
+    ResponseCreateWebhook = Response<ResponseWebhookData>,
+    ResponseDeleteWebhook = Response<String>,
+    ResponseWebhookSecret = Response<ResponseWebhookSecretData>)]
 pub struct Response<T: Serialize> {
     pub status: String,
     pub data: T,
 }
 
+#[derive(Serialize, ToSchema)]
+#[aliases(ResponseListTasks = PaginatedResponse<Vec<ResponseListTasksData>>,
+    ResponseListTaskChecks = PaginatedResponse<Vec<ResponseTaskCheckData>>,
+    ResponseListWebhookDeliveries = PaginatedResponse<Vec<ResponseWebhookDeliveryData>>)]
+pub struct PaginatedResponse<T: Serialize> {
+    pub status: String,
+    pub data: T,
+    /// Pass as `cursor` to get the next page, absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ErrorResponse {
     status: String,
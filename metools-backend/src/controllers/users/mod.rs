@@ -1 +1,3 @@
+pub(crate) mod notifications;
+#[allow(clippy::module_inception)]
 pub(crate) mod users;
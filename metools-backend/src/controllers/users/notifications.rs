@@ -0,0 +1,109 @@
+use actix_web::{
+    body::BoxBody,
+    get,
+    http::{header::ContentType, StatusCode},
+    put, web, HttpResponse, ResponseError,
+};
+use derive_more::Display;
+use serde_json::json;
+use validator::{Validate, ValidationErrors};
+
+use crate::{
+    controllers::{
+        middlewares::UserMiddleware,
+        schema::{AppState, ResponseNotificationSettings},
+    },
+    models::notifications::NotificationSettings,
+    services::notifications::NotificationsServiceError,
+};
+
+#[derive(Debug, Display)]
+enum NotificationsError {
+    InvalidInputData(ValidationErrors),
+    NotificationsServiceError(NotificationsServiceError),
+}
+
+impl ResponseError for NotificationsError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidInputData(_) => StatusCode::BAD_REQUEST,
+            Self::NotificationsServiceError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        match self {
+            Self::InvalidInputData(_errors) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Invalid input data", "status": "invalid_data"}).to_string()),
+            Self::NotificationsServiceError(_) => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::json())
+                .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
+        }
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    responses(
+    (status = OK, description = "OK", body = ResponseNotificationSettings),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "users"
+)]
+#[get("/api/v1/users/me/notifications")]
+pub async fn get_notification_settings(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+) -> Result<web::Json<ResponseNotificationSettings>, NotificationsError> {
+    let user_id = user.user_id;
+    let r = state
+        .notifications_service
+        .get_settings_for_user(user_id)
+        .await;
+
+    match r {
+        Ok(settings) => Ok(web::Json(ResponseNotificationSettings {
+            status: "success".to_string(),
+            data: settings,
+        })),
+        Err(err) => Err(NotificationsError::NotificationsServiceError(err)),
+    }
+}
+
+#[utoipa::path(
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
+    request_body = NotificationSettings,
+    responses(
+    (status = OK, description = "OK", body = ResponseNotificationSettings),
+    (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse),
+    (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
+    (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "users"
+)]
+#[put("/api/v1/users/me/notifications")]
+pub async fn update_notification_settings(
+    user: UserMiddleware,
+    state: web::Data<AppState>,
+    data: web::Json<NotificationSettings>,
+) -> Result<web::Json<ResponseNotificationSettings>, NotificationsError> {
+    let user_id = user.user_id;
+    match data.validate() {
+        Ok(_) => {
+            let r = state
+                .notifications_service
+                .update_settings_for_user(user_id, data.into_inner())
+                .await;
+
+            match r {
+                Ok(settings) => Ok(web::Json(ResponseNotificationSettings {
+                    status: "success".to_string(),
+                    data: settings,
+                })),
+                Err(err) => Err(NotificationsError::NotificationsServiceError(err)),
+            }
+        }
+        Err(err) => Err(NotificationsError::InvalidInputData(err)),
+    }
+}
@@ -58,7 +58,6 @@ pub struct TokenClaims {
 enum UsersError {
     InvalidInputData(ValidationErrors),
     UsersServiceError(UsersServiceError),
-    UnknownError,
 }
 
 impl ResponseError for UsersError {
@@ -72,14 +71,12 @@ impl ResponseError for UsersError {
                     VerifyTokensDBError::VerifyTokenNotFound => StatusCode::NOT_FOUND,
                     _ => StatusCode::INTERNAL_SERVER_ERROR,
                 },
-                UsersServiceError::CommitError(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 UsersServiceError::UnknownError => StatusCode::INTERNAL_SERVER_ERROR,
             },
-            Self::UnknownError => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
     fn error_response(&self) -> HttpResponse<BoxBody> {
-        return match self {
+        match self {
             Self::InvalidInputData(_errors) => HttpResponse::build(self.status_code())
                 .insert_header(ContentType::json())
                 .body(json!({"error": "Invalid input data", "status": "invalid_data"}).to_string()),
@@ -109,21 +106,15 @@ impl ResponseError for UsersError {
                         json!({"error": "Invalid credentials", "status": "invalid_credentials"})
                             .to_string(),
                     ),
-                UsersServiceError::CommitError(_) => HttpResponse::build(self.status_code())
-                    .insert_header(ContentType::json())
-                    .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
                 UsersServiceError::UnknownError => HttpResponse::build(self.status_code())
                     .insert_header(ContentType::json())
                     .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
             },
-            Self::UnknownError => HttpResponse::build(self.status_code())
-                .insert_header(ContentType::json())
-                .body(json!({"error": "Unknown error", "status": "unknown_error"}).to_string()),
-        };
+        }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ResponseMeData {
     pub created_at: DateTime<Utc>,
     pub username: String,
@@ -141,7 +132,7 @@ impl From<UserReturn> for ResponseMeData {
 }
 
 #[utoipa::path(
-    params(("X-API-AUTH-TOKEN" = Uuid, Header, description = "Auth token"),),
+    params(("X-API-AUTH-TOKEN" = String, Header, description = "JWT auth token"),),
     responses(
     (status = OK, description = "OK", body = ResponseMe),
     (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorResponse),
@@ -166,7 +157,7 @@ pub async fn me(
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ResponseSignupData {
     pub created_at: DateTime<Utc>,
     pub username: String,
@@ -185,7 +176,7 @@ impl From<UserReturn> for ResponseSignupData {
 
 #[utoipa::path(
     responses(
-    (status = OK, description = "OK", body = ResponseSignUp),
+    (status = OK, description = "OK", body = ResponseSignup),
     (status = BAD_REQUEST, description = "Data is not valid", body = ErrorResponse)
     ),
 tag = "users"
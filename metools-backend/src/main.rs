@@ -4,7 +4,7 @@ mod models;
 mod services;
 mod utils;
 
-use std::{env, fs::File, io::Write, path::Path, time::Duration};
+use std::{env, fs::File, io::Write, path::Path, sync::Arc, time::Duration};
 
 use actix_cors::Cors;
 use actix_web::{
@@ -13,21 +13,68 @@ use actix_web::{
     web, App, HttpResponse, HttpServer,
 };
 use actix_web_prometheus::PrometheusMetricsBuilder;
-use controllers::rzd::tasks::{
-    create_task, delete_all_tasks_for_user, delete_task_by_id_for_user, list_tasks,
+use controllers::{
+    notifications::{
+        telegram::{create_telegram_link, get_telegram_chat, unlink_telegram_chat},
+        webhooks::{
+            create_webhook, delete_webhook, get_webhook_secret, list_webhook_deliveries,
+            list_webhooks, rotate_webhook_secret,
+        },
+    },
+    rzd::{
+        monitoring::monitor_tasks,
+        stations::search_stations,
+        tasks::{
+            create_task, delete_all_tasks_for_user, delete_task_by_id_for_user,
+            get_task_by_id_for_user, list_task_checks_for_user, list_task_prices_for_user,
+            list_tasks, pause_task_by_id_for_user, resume_task_by_id_for_user,
+            set_task_notifications_for_user, stream_task_events, update_task_by_id_for_user,
+        },
+    },
 };
 use lettre::{transport::smtp::authentication::Credentials, SmtpTransport};
-use models::verify_tokens::delete_expired_verify_tokens;
-use services::{mailer::MailerService, tasks::TasksService};
+use models::{
+    outbox::delete_old_outbox_messages,
+    rzd::{
+        task_checks::delete_old_task_checks, task_prices::delete_old_task_prices,
+        tasks::expire_outdated_tasks,
+    },
+    telegram::delete_expired_telegram_link_codes,
+    verify_tokens::delete_expired_verify_tokens,
+    webhooks::delete_old_webhook_deliveries,
+};
+use serde_json::json;
+use services::{
+    dispatcher::{DispatcherMetrics, NotificationsDispatcher},
+    events::{TaskEventKind, TaskEventsService},
+    mailer::MailerService,
+    notifications::NotificationsService,
+    rzd::{
+        client::{HttpRzdClient, RzdClient},
+        fake::FakeRzdClient,
+        guard::{CircuitState, GuardedRzdClient, RzdGuard},
+        poller::RzdPoller,
+    },
+    stations::StationsService,
+    tasks::TasksService,
+    telegram::{
+        bot::TelegramService,
+        client::{HttpTelegramClient, TelegramClient},
+    },
+    webhooks::WebhooksService,
+};
 use surrealdb_migrations::MigrationRunner;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
-    config::Config,
+    config::{Config, DBConfig},
     controllers::{
         schema::AppState,
-        users::users::{login, me, signup, verify_user},
+        users::{
+            notifications::{get_notification_settings, update_notification_settings},
+            users::{login, me, signup, verify_user},
+        },
     },
     services::users::UsersService,
 };
@@ -40,29 +87,118 @@ use crate::{
         controllers::users::users::login,
         controllers::users::users::signup,
         controllers::users::users::verify_user,
+        controllers::users::notifications::get_notification_settings,
+        controllers::users::notifications::update_notification_settings,
         controllers::rzd::tasks::list_tasks,
         controllers::rzd::tasks::create_task,
         controllers::rzd::tasks::delete_task_by_id_for_user,
-        controllers::rzd::tasks::delete_all_tasks_for_user
+        controllers::rzd::tasks::delete_all_tasks_for_user,
+        controllers::rzd::tasks::pause_task_by_id_for_user,
+        controllers::rzd::tasks::resume_task_by_id_for_user,
+        controllers::rzd::tasks::list_task_checks_for_user,
+        controllers::rzd::tasks::list_task_prices_for_user,
+        controllers::rzd::tasks::stream_task_events,
+        controllers::rzd::monitoring::monitor_tasks,
+        controllers::rzd::tasks::get_task_by_id_for_user,
+        controllers::rzd::tasks::update_task_by_id_for_user,
+        controllers::rzd::tasks::set_task_notifications_for_user,
+        controllers::rzd::stations::search_stations,
+        controllers::notifications::telegram::get_telegram_chat,
+        controllers::notifications::telegram::create_telegram_link,
+        controllers::notifications::telegram::unlink_telegram_chat,
+        controllers::notifications::webhooks::list_webhooks,
+        controllers::notifications::webhooks::create_webhook,
+        controllers::notifications::webhooks::delete_webhook,
+        controllers::notifications::webhooks::get_webhook_secret,
+        controllers::notifications::webhooks::rotate_webhook_secret,
+        controllers::notifications::webhooks::list_webhook_deliveries
     ),
     components(schemas(
         crate::controllers::users::users::LoginData,
         crate::controllers::users::users::SignUpData,
         crate::controllers::rzd::tasks::CreateTaskData,
+        crate::controllers::rzd::tasks::UpdateTaskData,
         crate::controllers::schema::ErrorResponse,
         crate::controllers::schema::ResponseMe,
+        crate::controllers::schema::ResponseLogin,
+        crate::controllers::schema::ResponseSignup,
+        crate::controllers::users::users::ResponseMeData,
+        crate::controllers::users::users::ResponseSignupData,
+        crate::controllers::schema::ResponseNotificationSettings,
+        crate::models::notifications::NotificationSettings,
+        crate::models::notifications::NotificationChannels,
+        crate::models::notifications::QuietHours,
+        crate::models::notifications::DeliveryMode,
+        crate::models::notifications::TaskNotificationOverrides,
         crate::controllers::schema::ResponseListTasks,
         crate::controllers::schema::ResponseCreateTask,
+        crate::controllers::schema::ResponseGetTask,
+        crate::controllers::schema::ResponseUpdateTask,
+        crate::services::events::TaskEvent,
+        crate::services::events::TaskEventKind,
+        crate::controllers::rzd::monitoring::ClientMessage,
+        crate::controllers::rzd::monitoring::ServerMessage,
         crate::controllers::schema::ResponseDeleteTaskByIdForUser,
         crate::controllers::schema::ResponseDeleteAllTasksForUser,
-        crate::models::rzd::tasks::Task,
-        crate::models::users::UserReturn
+        crate::controllers::schema::ResponseChangeTaskStatusForUser,
+        crate::controllers::schema::ResponseSearchStations,
+        crate::controllers::schema::ResponseListTaskChecks,
+        crate::controllers::rzd::tasks::ResponseTaskCheckData,
+        crate::controllers::schema::ResponseTaskPrices,
+        crate::controllers::schema::ResponseTelegramChat,
+        crate::controllers::schema::ResponseTelegramLink,
+        crate::controllers::schema::ResponseUnlinkTelegram,
+        crate::controllers::notifications::telegram::ResponseTelegramChatData,
+        crate::controllers::notifications::telegram::ResponseTelegramLinkData,
+        crate::controllers::notifications::webhooks::CreateWebhookData,
+        crate::controllers::schema::ResponseListWebhooks,
+        crate::controllers::schema::ResponseCreateWebhook,
+        crate::controllers::schema::ResponseDeleteWebhook,
+        crate::controllers::schema::ResponseWebhookSecret,
+        crate::controllers::schema::ResponseListWebhookDeliveries,
+        crate::controllers::notifications::webhooks::ResponseWebhookData,
+        crate::controllers::notifications::webhooks::ResponseWebhookSecretData,
+        crate::controllers::notifications::webhooks::ResponseWebhookDeliveryData,
+        crate::models::webhooks::WebhookDeliveryStatus,
+        crate::controllers::rzd::tasks::ResponseTaskPricesData,
+        crate::controllers::rzd::tasks::PriceSeries,
+        crate::controllers::rzd::tasks::PricePoint,
+        crate::controllers::rzd::tasks::ResponseListTasksData,
+        crate::models::rzd::tasks::TaskData,
+        crate::models::rzd::tasks::TaskType,
+        crate::utils::pagination::SortOrder,
+        crate::models::rzd::tasks::TaskFilters,
+        crate::models::rzd::tasks::SeatType,
+        crate::models::rzd::tasks::PriceAlert,
+        crate::models::rzd::tasks::TrackedPrice,
+        crate::models::rzd::tasks::PriceKey,
+        crate::models::rzd::tasks::ClassPrice,
+        crate::models::rzd::tasks::DayTask,
+        crate::models::rzd::tasks::TrainTask,
+        crate::models::rzd::tasks::DatesTask,
+        crate::models::rzd::tasks::DateRangeTask,
+        crate::models::rzd::tasks::RoundTripTask,
+        crate::models::rzd::tasks::TripLeg,
+        crate::models::rzd::tasks::LegCheck,
+        crate::models::rzd::tasks::TaskStatus,
+        crate::models::rzd::tasks::TaskCheck,
+        crate::models::rzd::tasks::TaskCheckStatus,
+        crate::models::rzd::tasks::AvailableTrain,
+        crate::models::rzd::tasks::AvailableCarClass,
+        crate::models::rzd::stations::Station
     ))
 )]
 struct OpenAPI;
 
-async fn health() -> HttpResponse {
-    HttpResponse::Ok().finish()
+/// Reports `degraded` while RZD requests are paused, the service itself keeps serving.
+async fn health(state: web::Data<AppState>) -> HttpResponse {
+    let rzd_circuit = state.rzd_guard.circuit_state();
+    let status = match rzd_circuit {
+        CircuitState::Open => "degraded",
+        CircuitState::Closed | CircuitState::HalfOpen => "ok",
+    };
+
+    HttpResponse::Ok().json(json!({"status": status, "rzd": {"circuit": rzd_circuit}}))
 }
 
 async fn run_migrations(config: &Config) {
@@ -86,6 +222,18 @@ async fn main() -> std::io::Result<()> {
                 file.write_all(&yaml_string.try_into_bytes().unwrap())
                     .unwrap_or_else(|_| panic!("Cant write to file with path {}", path.clone()))
             }
+            "import-stations" => {
+                let path = env::args().nth(2).expect("Need path parameter");
+                if Path::new(".env").exists() {
+                    dotenv::dotenv().ok();
+                }
+                let stations_service = StationsService::init(DBConfig::init());
+                let count = stations_service
+                    .import_stations(Path::new(path.as_str()))
+                    .await
+                    .unwrap_or_else(|err| panic!("Cant import stations from {path}: {err}"));
+                println!("Imported {count} stations");
+            }
             &_ => {
                 panic!("Unknown CLI command");
             }
@@ -105,6 +253,7 @@ async fn main() -> std::io::Result<()> {
     // Open a remote connection to gmail
     let smtp_transport = SmtpTransport::relay(config.smtp_hostname.as_str())
         .unwrap()
+        .port(config.smtp_port)
         .credentials(creds)
         .build();
 
@@ -122,9 +271,199 @@ async fn main() -> std::io::Result<()> {
                 Ok(c) => log::info!("Deleted {c} verify tokens"),
                 Err(err) => log::error!("Error on loop delete_expired_verify_tokens: {err}"),
             }
+            let connection = clonned_db_config.clone().get_connection().await;
+            let r = delete_expired_telegram_link_codes(connection).await;
+            match r {
+                Ok(c) => log::info!("Deleted {c} telegram link codes"),
+                Err(err) => {
+                    log::error!("Error on loop delete_expired_telegram_link_codes: {err}")
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        }
+    });
+    let task_events_service = TaskEventsService::init();
+    let clonned_db_config = config.db.clone();
+    let clonned_task_events_service = task_events_service.clone();
+    tokio::spawn(async move {
+        loop {
+            let connection = clonned_db_config.clone().get_connection().await;
+            let r = expire_outdated_tasks(connection).await;
+            match r {
+                Ok(changes) => {
+                    log::info!("Expired {} rzd tasks", changes.len());
+                    for change in changes {
+                        clonned_task_events_service.publish(
+                            change.user,
+                            change.id,
+                            TaskEventKind::StatusChanged {
+                                status: change.status,
+                            },
+                        );
+                    }
+                }
+                Err(err) => log::error!("Error on loop expire_outdated_tasks: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        }
+    });
+    let clonned_db_config = config.db.clone();
+    let rzd_checks_retention_days = config.rzd_checks_retention_days;
+    let rzd_prices_retention_days = config.rzd_prices_retention_days;
+    let webhook_deliveries_retention_days = config.webhook_deliveries_retention_days;
+    let notifications_outbox_retention_days = config.notifications_outbox_retention_days;
+    tokio::spawn(async move {
+        loop {
+            let connection = clonned_db_config.clone().get_connection().await;
+            let r = delete_old_task_checks(connection, rzd_checks_retention_days).await;
+            match r {
+                Ok(c) => log::info!("Deleted {c} rzd task checks"),
+                Err(err) => log::error!("Error on loop delete_old_task_checks: {err}"),
+            }
+            let connection = clonned_db_config.clone().get_connection().await;
+            let r = delete_old_task_prices(connection, rzd_prices_retention_days).await;
+            match r {
+                Ok(c) => log::info!("Deleted {c} rzd task prices"),
+                Err(err) => log::error!("Error on loop delete_old_task_prices: {err}"),
+            }
+            let connection = clonned_db_config.clone().get_connection().await;
+            let r =
+                delete_old_webhook_deliveries(connection, webhook_deliveries_retention_days).await;
+            match r {
+                Ok(c) => log::info!("Deleted {c} webhook deliveries"),
+                Err(err) => log::error!("Error on loop delete_old_webhook_deliveries: {err}"),
+            }
+            let connection = clonned_db_config.clone().get_connection().await;
+            let r =
+                delete_old_outbox_messages(connection, notifications_outbox_retention_days).await;
+            match r {
+                Ok(c) => log::info!("Deleted {c} outbox messages"),
+                Err(err) => log::error!("Error on loop delete_old_outbox_messages: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        }
+    });
+    // Built once, so that the metrics of services live in the registry every worker exports
+    let prometheus = PrometheusMetricsBuilder::new("api")
+        .endpoint("/metrics")
+        .build()
+        .unwrap();
+    let rzd_guard = RzdGuard::init(config.rzd_limits.clone(), &prometheus.registry);
+    let rzd_client: Arc<dyn RzdClient> = match config.rzd_fixtures_path.clone() {
+        Some(fixtures_path) => {
+            log::info!("Using RZD fixtures from {fixtures_path}");
+            Arc::new(FakeRzdClient::init(fixtures_path.into()))
+        }
+        None => Arc::new(HttpRzdClient::init(
+            config.rzd_http.clone(),
+            rzd_guard.clone(),
+        )),
+    };
+    let rzd_client: Arc<dyn RzdClient> =
+        Arc::new(GuardedRzdClient::init(rzd_client, rzd_guard.clone()));
+    let telegram_service = config.telegram.clone().map(|telegram| {
+        let telegram_client: Arc<dyn TelegramClient> = Arc::new(HttpTelegramClient::init(
+            telegram.api_url,
+            telegram.bot_token,
+        ));
+        TelegramService::init(config.db.clone(), telegram_client, telegram.bot_username)
+    });
+    if let Some(telegram_service) = telegram_service.clone() {
+        tokio::spawn(async move {
+            let mut offset = None;
+            loop {
+                let r = telegram_service.process_updates(offset).await;
+                match r {
+                    Ok(next_offset) => offset = next_offset,
+                    Err(err) => {
+                        log::error!("Error on loop process_updates: {err}");
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+    }
+    let webhooks_service = WebhooksService::init(
+        config.db.clone(),
+        config.webhook_max_attempts,
+        config.webhook_retry_delay,
+    );
+    let clonned_webhooks_service = webhooks_service.clone();
+    tokio::spawn(async move {
+        loop {
+            let r = clonned_webhooks_service.deliver_due().await;
+            match r {
+                Ok(c) if c > 0 => log::info!("Sent {c} webhook deliveries"),
+                Ok(_) => {}
+                Err(err) => log::error!("Error on loop deliver_due: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+    });
+    let mailer_service = MailerService::init(
+        smtp_transport.clone(),
+        config.smtp_from.clone(),
+        config.service_url.clone(),
+    );
+    let notifications_dispatcher = NotificationsDispatcher::init(
+        config.db.clone(),
+        mailer_service.clone(),
+        telegram_service.clone(),
+        config.notifications_max_attempts,
+        config.notifications_retry_delay,
+        DispatcherMetrics::init(&prometheus.registry),
+    );
+    let clonned_notifications_dispatcher = notifications_dispatcher.clone();
+    tokio::spawn(async move {
+        loop {
+            let r = clonned_notifications_dispatcher.dispatch_due().await;
+            match r {
+                Ok(c) if c > 0 => log::info!("Dispatched {c} outbox messages"),
+                Ok(_) => {}
+                Err(err) => log::error!("Error on loop dispatch_due: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+    });
+    let notifications_service = NotificationsService::init(
+        config.db.clone(),
+        notifications_dispatcher.clone(),
+        webhooks_service.clone(),
+        config.notifications_digest_interval,
+    );
+    let clonned_notifications_service = notifications_service.clone();
+    tokio::spawn(async move {
+        loop {
+            let r = clonned_notifications_service.send_due_digests().await;
+            match r {
+                Ok(c) if c > 0 => log::info!("Sent notification digests to {c} users"),
+                Ok(_) => {}
+                Err(err) => log::error!("Error on loop send_due_digests: {err}"),
+            }
             tokio::time::sleep(Duration::from_secs(60)).await;
         }
     });
+    let rzd_poller = RzdPoller::init(
+        config.db.clone(),
+        rzd_client,
+        notifications_service.clone(),
+        task_events_service.clone(),
+        config.rzd_max_failed_checks,
+        config.rzd_poll_interval,
+        config.rzd_max_checks_per_run,
+    );
+    let rzd_scheduler_tick = config.rzd_scheduler_tick;
+    tokio::spawn(async move {
+        loop {
+            let r = rzd_poller.check_due_tasks().await;
+            match r {
+                Ok(c) if c > 0 => log::info!("Checked {c} rzd tasks"),
+                Ok(_) => {}
+                Err(err) => log::error!("Error on loop check_due_tasks: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(rzd_scheduler_tick)).await;
+        }
+    });
     HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
@@ -132,37 +471,61 @@ async fn main() -> std::io::Result<()> {
             .allow_any_header()
             .expose_any_header()
             .max_age(3600);
-        let prometheus = PrometheusMetricsBuilder::new("api")
-            .endpoint("/metrics")
-            .build()
-            .unwrap();
         App::new()
             .service(me)
             .service(login)
             .service(signup)
             .service(verify_user)
+            .service(get_notification_settings)
+            .service(update_notification_settings)
             .service(list_tasks)
             .service(create_task)
             .service(delete_task_by_id_for_user)
             .service(delete_all_tasks_for_user)
+            .service(pause_task_by_id_for_user)
+            .service(resume_task_by_id_for_user)
+            .service(list_task_checks_for_user)
+            .service(list_task_prices_for_user)
+            .service(stream_task_events)
+            .service(monitor_tasks)
+            .service(get_task_by_id_for_user)
+            .service(update_task_by_id_for_user)
+            .service(set_task_notifications_for_user)
+            .service(search_stations)
+            .service(get_telegram_chat)
+            .service(create_telegram_link)
+            .service(unlink_telegram_chat)
+            .service(list_webhooks)
+            .service(create_webhook)
+            .service(get_webhook_secret)
+            .service(rotate_webhook_secret)
+            .service(delete_webhook)
+            .service(list_webhook_deliveries)
             .service(
                 SwaggerUi::new("/swagger/{_:.*}").url("/openapi.json", OpenAPI::openapi().clone()),
             )
             .service(web::resource("/healthz").to(health))
             .wrap(Logger::default())
             .wrap(Compress::default())
-            .wrap(prometheus)
+            .wrap(prometheus.clone())
             .wrap(cors)
             .app_data(web::Data::new(AppState {
                 users_service: UsersService::init(
                     config.db.clone(),
-                    MailerService::init(
-                        smtp_transport.clone(),
-                        config.smtp_from.clone(),
-                        config.service_url.clone(),
-                    ),
+                    mailer_service.clone(),
+                    notifications_dispatcher.clone(),
+                ),
+                tasks_service: TasksService::init(
+                    config.db.clone(),
+                    config.rzd_quotas.clone(),
+                    task_events_service.clone(),
                 ),
-                tasks_service: TasksService::init(config.db.clone()),
+                stations_service: StationsService::init(config.db.clone()),
+                telegram_service: telegram_service.clone(),
+                webhooks_service: webhooks_service.clone(),
+                notifications_service: notifications_service.clone(),
+                task_events_service: task_events_service.clone(),
+                rzd_guard: rzd_guard.clone(),
                 jwt_secret: config.jwt_secret.clone(),
                 jwt_maxage: config.jwt_maxage,
             }))
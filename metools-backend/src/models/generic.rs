@@ -2,6 +2,7 @@ use serde::Deserialize;
 use surrealdb::sql::Thing;
 
 #[derive(Deserialize, Clone)]
+#[allow(dead_code)]
 pub struct Record {
     pub id: Thing,
 }
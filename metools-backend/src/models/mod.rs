@@ -1,4 +1,8 @@
 pub mod generic;
+pub mod notifications;
+pub mod outbox;
 pub mod rzd;
+pub mod telegram;
 pub mod users;
 pub mod verify_tokens;
+pub mod webhooks;
@@ -0,0 +1,350 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surrealdb::{
+    sql::{Datetime, Thing},
+    Connection, Error, Response, Surreal,
+};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::models::rzd::tasks::{AvailableTrain, PriceDrop};
+
+const SETTINGS_TABLE_NAME: &str = "notification_settings";
+const DIGEST_ITEMS_TABLE_NAME: &str = "notification_digest_items";
+
+#[derive(Debug, Display)]
+pub enum NotificationsDBError {
+    UnknownError(Error),
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Where notifications are sent, every channel is on by default.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq)]
+pub struct NotificationChannels {
+    #[serde(default = "enabled")]
+    pub email: bool,
+    #[serde(default = "enabled")]
+    pub telegram: bool,
+    #[serde(default = "enabled")]
+    pub webhook: bool,
+}
+
+impl Default for NotificationChannels {
+    fn default() -> Self {
+        Self {
+            email: true,
+            telegram: true,
+            webhook: true,
+        }
+    }
+}
+
+/// Local time window without messages, it wraps midnight when `start` is after `end`.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq)]
+pub struct QuietHours {
+    #[schema(value_type = String, example = "23:00")]
+    pub start: NaiveTime,
+    #[schema(value_type = String, example = "08:00")]
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMode {
+    /// Every notification is sent right away.
+    #[default]
+    Instant,
+    /// Notifications are collected and sent together once per digest interval.
+    Digest,
+}
+
+pub fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    if Tz::from_str(timezone).is_err() {
+        return Err(ValidationError::new("unknown_timezone"));
+    }
+    Ok(())
+}
+
+fn default_timezone() -> String {
+    String::from("UTC")
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+pub struct NotificationSettings {
+    #[serde(default)]
+    pub channels: NotificationChannels,
+    pub quiet_hours: Option<QuietHours>,
+    /// IANA time zone the quiet hours are in, e.g. `Europe/Moscow`.
+    #[serde(default = "default_timezone")]
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: String,
+    #[serde(default)]
+    pub delivery: DeliveryMode,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            channels: NotificationChannels::default(),
+            quiet_hours: None,
+            timezone: default_timezone(),
+            delivery: DeliveryMode::default(),
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn tz(&self) -> Tz {
+        Tz::from_str(self.timezone.as_str()).unwrap_or(Tz::UTC)
+    }
+}
+
+/// Task level changes to the user's settings, omitted fields follow the user's settings.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, Default)]
+pub struct TaskNotificationOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<NotificationChannels>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<DeliveryMode>,
+    /// `false` lets notifications of the task through during quiet hours.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<bool>,
+}
+
+/// What a queued notification is about.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotificationContent {
+    Availability { trains: Vec<AvailableTrain> },
+    PriceDrops { drops: Vec<PriceDrop> },
+}
+
+#[derive(Serialize)]
+pub struct NewDigestItem {
+    pub user: Thing,
+    pub task: Thing,
+    pub channels: NotificationChannels,
+    pub content: NotificationContent,
+    pub send_after: Datetime,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DigestItem {
+    pub id: Thing,
+    #[allow(dead_code)]
+    pub created_at: Datetime,
+    pub user: Thing,
+    pub channels: NotificationChannels,
+    pub content: NotificationContent,
+}
+
+#[derive(Deserialize)]
+struct StoredSettings {
+    #[serde(flatten)]
+    settings: NotificationSettings,
+}
+
+pub async fn get_notification_settings_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+) -> Result<Option<NotificationSettings>, NotificationsDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT channels, quiet_hours, timezone, delivery FROM type::table($table) WHERE user = <record>$user_id")
+        .bind(json!(
+            {
+                "table": SETTINGS_TABLE_NAME,
+                "user_id": user_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut settings) => match settings.take::<Option<StoredSettings>>(0) {
+            Ok(settings) => Ok(settings.map(|stored| stored.settings)),
+            Err(err) => Err(NotificationsDBError::UnknownError(err)),
+        },
+        Err(err) => Err(NotificationsDBError::UnknownError(err)),
+    }
+}
+
+/// Stores the settings of the user, replacing the previous ones.
+pub async fn set_notification_settings_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    settings: NotificationSettings,
+) -> Result<(), NotificationsDBError> {
+    let r = conn
+        .query(
+            "BEGIN TRANSACTION;
+            DELETE type::table($table) WHERE user = <record>$user_id;
+            CREATE type::table($table) SET user = <record>$user_id, channels = $channels,
+                quiet_hours = IF $quiet_hours THEN $quiet_hours ELSE NONE END,
+                timezone = $timezone, delivery = $delivery;
+            COMMIT TRANSACTION;",
+        )
+        .bind(json!(
+            {
+                "table": SETTINGS_TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "channels": settings.channels,
+                "quiet_hours": settings.quiet_hours,
+                "timezone": settings.timezone,
+                "delivery": settings.delivery
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(NotificationsDBError::UnknownError(err)),
+    }
+}
+
+pub async fn insert_digest_item<T: Connection>(
+    conn: &Surreal<T>,
+    item: NewDigestItem,
+) -> Result<(), NotificationsDBError> {
+    let r: Result<Vec<DigestItem>, Error> =
+        conn.create(DIGEST_ITEMS_TABLE_NAME).content(item).await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(NotificationsDBError::UnknownError(err)),
+    }
+}
+
+/// Lists queued items that are due, oldest first, so that they can be grouped by user.
+pub async fn list_due_digest_items<T: Connection>(
+    conn: &Surreal<T>,
+    now: DateTime<Utc>,
+) -> Result<Vec<DigestItem>, NotificationsDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT * FROM type::table($table) WHERE send_after <= <datetime>$now ORDER BY created_at")
+        .bind(json!(
+            {
+                "table": DIGEST_ITEMS_TABLE_NAME,
+                "now": now
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut items) => match items.take::<Vec<DigestItem>>(0) {
+            Ok(items) => Ok(items),
+            Err(err) => Err(NotificationsDBError::UnknownError(err)),
+        },
+        Err(err) => Err(NotificationsDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_digest_items<T: Connection>(
+    conn: &Surreal<T>,
+    item_ids: Vec<Thing>,
+) -> Result<(), NotificationsDBError> {
+    let r = conn
+        .query("DELETE type::table($table) WHERE id INSIDE $item_ids")
+        .bind(json!(
+            {
+                "table": DIGEST_ITEMS_TABLE_NAME
+            }
+        ))
+        // Bound as record ids, strings would never equal an id
+        .bind(("item_ids", item_ids))
+        .await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(NotificationsDBError::UnknownError(err)),
+    }
+}
+
+/// Narrows queued items to the given channels, e.g. to the ones their digest failed on.
+pub async fn set_digest_items_channels<T: Connection>(
+    conn: &Surreal<T>,
+    item_ids: Vec<Thing>,
+    channels: NotificationChannels,
+) -> Result<(), NotificationsDBError> {
+    let r = conn
+        .query("UPDATE type::table($table) SET channels = $channels WHERE id INSIDE $item_ids")
+        .bind(json!(
+            {
+                "table": DIGEST_ITEMS_TABLE_NAME,
+                "channels": channels
+            }
+        ))
+        .bind(("item_ids", item_ids))
+        .await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(NotificationsDBError::UnknownError(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use surrealdb::engine::any::Any;
+
+    use super::*;
+    use crate::utils::test_db::test_db_config;
+
+    async fn insert_items(conn: &Surreal<Any>, count: usize) -> Vec<Thing> {
+        for _ in 0..count {
+            let item = NewDigestItem {
+                user: Thing::from(("users", "user")),
+                task: Thing::from(("rzd_tasks", "task")),
+                channels: NotificationChannels::default(),
+                content: NotificationContent::Availability { trains: Vec::new() },
+                send_after: Utc::now().into(),
+            };
+            insert_digest_item(conn, item).await.unwrap();
+        }
+        list_due_digest_items(conn, Utc::now())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn digest_items_are_updated_and_deleted_by_id() {
+        let conn = test_db_config().await.get_connection().await;
+        let ids = insert_items(&conn, 3).await;
+        let email_only = NotificationChannels {
+            email: true,
+            telegram: false,
+            webhook: false,
+        };
+
+        set_digest_items_channels(&conn, vec![ids[0].clone()], email_only.clone())
+            .await
+            .unwrap();
+        delete_digest_items(&conn, vec![ids[1].clone(), ids[2].clone()])
+            .await
+            .unwrap();
+
+        let items = list_due_digest_items(&conn, Utc::now()).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, ids[0]);
+        assert_eq!(items[0].channels, email_only);
+    }
+}
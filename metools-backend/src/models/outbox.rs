@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surrealdb::{sql::Thing, Connection, Error, Response, Surreal};
+
+const TABLE_NAME: &str = "notifications_outbox";
+
+#[derive(Debug, Display)]
+pub enum OutboxDBError {
+    UnknownError(Error),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxChannel {
+    #[display(fmt = "email")]
+    Email,
+    #[display(fmt = "telegram")]
+    Telegram,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Sent,
+    /// Gave up after the last attempt, kept for inspection.
+    Dead,
+}
+
+/// Message to send, emails go to `recipient` and Telegram messages to the chat of `user`.
+#[derive(Serialize)]
+pub struct NewOutboxMessage {
+    pub user: Option<Thing>,
+    pub channel: OutboxChannel,
+    pub recipient: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct OutboxMessage {
+    pub id: Thing,
+    pub user: Option<Thing>,
+    pub channel: OutboxChannel,
+    pub recipient: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+    pub attempts: u32,
+}
+
+/// Outcome of one sending attempt.
+pub struct OutboxAttempt {
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+pub async fn insert_outbox_message<T: Connection>(
+    conn: &Surreal<T>,
+    message: NewOutboxMessage,
+) -> Result<(), OutboxDBError> {
+    let r: Result<Vec<OutboxMessage>, Error> = conn.create(TABLE_NAME).content(message).await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(OutboxDBError::UnknownError(err)),
+    }
+}
+
+pub async fn list_due_outbox_messages<T: Connection>(
+    conn: &Surreal<T>,
+    limit: usize,
+) -> Result<Vec<OutboxMessage>, OutboxDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT * FROM type::table($table) WHERE status = 'pending' AND next_attempt_at <= time::now() ORDER BY next_attempt_at LIMIT $limit")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "limit": limit
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut messages) => match messages.take::<Vec<OutboxMessage>>(0) {
+            Ok(messages) => Ok(messages),
+            Err(err) => Err(OutboxDBError::UnknownError(err)),
+        },
+        Err(err) => Err(OutboxDBError::UnknownError(err)),
+    }
+}
+
+pub async fn set_outbox_attempt<T: Connection>(
+    conn: &Surreal<T>,
+    message_id: Thing,
+    attempt: OutboxAttempt,
+) -> Result<(), OutboxDBError> {
+    let r = conn
+        .query(
+            "UPDATE type::table($table) SET status = $status, attempts = $attempts, last_attempt_at = time::now(),
+            next_attempt_at = IF $next_attempt_at THEN <datetime>$next_attempt_at ELSE next_attempt_at END,
+            error = IF $error THEN $error ELSE NONE END
+            WHERE id = <record>$message_id",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "message_id": message_id.to_string(),
+                "status": attempt.status,
+                "attempts": attempt.attempts,
+                "next_attempt_at": attempt.next_attempt_at,
+                "error": attempt.error
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(OutboxDBError::UnknownError(err)),
+    }
+}
+
+pub async fn count_pending_outbox_messages<T: Connection>(
+    conn: &Surreal<T>,
+) -> Result<usize, OutboxDBError> {
+    let r: Result<Response, Error> = conn
+        .query("count(SELECT id FROM type::table($table) WHERE status = 'pending')")
+        .bind(json!(
+            {
+                "table": TABLE_NAME
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut count) => match count.take::<Option<usize>>(0) {
+            Ok(count) => Ok(count.unwrap_or_default()),
+            Err(err) => Err(OutboxDBError::UnknownError(err)),
+        },
+        Err(err) => Err(OutboxDBError::UnknownError(err)),
+    }
+}
+
+/// Deletes sent and dead messages older than the retention, pending ones are kept.
+pub async fn delete_old_outbox_messages<T: Connection>(
+    conn: Surreal<T>,
+    retention_days: u32,
+) -> Result<usize, OutboxDBError> {
+    let r = conn
+        .query("count(DELETE type::table($table) WHERE created_at < time::now() - <duration>$retention AND status != 'pending' RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "retention": format!("{retention_days}d")
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut r) => {
+            let surreal_response = r.take::<Vec<usize>>(0).unwrap()[0];
+            Ok(surreal_response)
+        }
+        Err(err) => Err(OutboxDBError::UnknownError(err)),
+    }
+}
@@ -1 +1,4 @@
+pub mod stations;
+pub mod task_checks;
+pub mod task_prices;
 pub mod tasks;
@@ -0,0 +1,210 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surrealdb::{Connection, Error, Response, Surreal};
+use utoipa::ToSchema;
+
+const TABLE_NAME: &str = "rzd_stations";
+
+#[derive(Debug, Display)]
+pub enum StationsDBError {
+    UnknownError(Error),
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct Station {
+    /// RZD express code, e.g. 2000000 for Moscow
+    pub code: String,
+    pub name: String,
+    pub name_latin: String,
+}
+
+#[derive(Serialize)]
+struct NewStation {
+    /// Bare record key, the station code, so that INSERT builds `rzd_stations:⟨code⟩`
+    id: String,
+    #[serde(flatten)]
+    station: Station,
+}
+
+/// Inserts stations or updates their names when the code is already known.
+pub async fn upsert_stations<T: Connection>(
+    conn: Surreal<T>,
+    stations: Vec<Station>,
+) -> Result<usize, StationsDBError> {
+    let stations: Vec<NewStation> = stations
+        .into_iter()
+        .map(|station| NewStation {
+            id: station.code.clone(),
+            station,
+        })
+        .collect();
+    // INSERT does not accept type::table, so the table is spelled out
+    let r: Result<Response, Error> = conn
+        .query("count(INSERT INTO rzd_stations $stations ON DUPLICATE KEY UPDATE name = $input.name, name_latin = $input.name_latin)")
+        .bind(json!(
+            {
+                "stations": stations
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut r) => match r.take::<Option<usize>>(0) {
+            Ok(count) => Ok(count.unwrap_or_default()),
+            Err(err) => Err(StationsDBError::UnknownError(err)),
+        },
+        Err(err) => Err(StationsDBError::UnknownError(err)),
+    }
+}
+
+/// Finds stations by Russian or Latin name, prefix matches first, then fuzzy ones.
+pub async fn search_stations<T: Connection>(
+    conn: Surreal<T>,
+    query: String,
+    limit: usize,
+) -> Result<Vec<Station>, StationsDBError> {
+    let r: Result<Response, Error> = conn
+        .query(
+            "SELECT code, name, name_latin,
+            string::startsWith(string::lowercase(name), $query) OR string::startsWith(string::lowercase(name_latin), $query) AS prefix,
+            math::max([string::similarity::fuzzy(string::lowercase(name), $query), string::similarity::fuzzy(string::lowercase(name_latin), $query)]) AS score
+            FROM type::table($table)
+            WHERE string::contains(string::lowercase(name), $query) OR string::contains(string::lowercase(name_latin), $query)
+            OR string::lowercase(name) ~ $query OR string::lowercase(name_latin) ~ $query
+            ORDER BY prefix DESC, score DESC, name LIMIT $limit",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "query": query.to_lowercase(),
+                "limit": limit
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut stations) => match stations.take::<Vec<Station>>(0) {
+            Ok(stations) => Ok(stations),
+            Err(err) => Err(StationsDBError::UnknownError(err)),
+        },
+        Err(err) => Err(StationsDBError::UnknownError(err)),
+    }
+}
+
+/// Whether any station was imported yet.
+pub async fn has_stations<T: Connection>(conn: Surreal<T>) -> Result<bool, StationsDBError> {
+    let r: Result<Response, Error> = conn
+        .query("count(SELECT id FROM type::table($table) LIMIT 1)")
+        .bind(json!(
+            {
+                "table": TABLE_NAME
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut count) => match count.take::<Option<usize>>(0) {
+            Ok(count) => Ok(count.unwrap_or_default() > 0),
+            Err(err) => Err(StationsDBError::UnknownError(err)),
+        },
+        Err(err) => Err(StationsDBError::UnknownError(err)),
+    }
+}
+
+/// Returns the codes that are missing from the station directory.
+pub async fn find_unknown_station_codes<T: Connection>(
+    conn: Surreal<T>,
+    codes: Vec<String>,
+) -> Result<Vec<String>, StationsDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT VALUE code FROM type::table($table) WHERE code IN $codes")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "codes": codes
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut known_codes) => match known_codes.take::<Vec<String>>(0) {
+            Ok(known_codes) => Ok(codes
+                .into_iter()
+                .filter(|code| !known_codes.contains(code))
+                .collect()),
+            Err(err) => Err(StationsDBError::UnknownError(err)),
+        },
+        Err(err) => Err(StationsDBError::UnknownError(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use surrealdb::engine::any::Any;
+
+    use super::*;
+    use crate::utils::test_db::test_db_config;
+
+    fn station(code: &str, name: &str, name_latin: &str) -> Station {
+        Station {
+            code: String::from(code),
+            name: String::from(name),
+            name_latin: String::from(name_latin),
+        }
+    }
+
+    async fn names(conn: Surreal<Any>, query: &str) -> Vec<String> {
+        search_stations(conn, String::from(query), 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|station| station.name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search_puts_prefix_matches_first() {
+        let conn = test_db_config().await.get_connection().await;
+        let stations = vec![
+            station("2000000", "Москва", "Moskva"),
+            station("2004000", "Санкт-Петербург", "Sankt-Peterburg"),
+            station("2030080", "Новомосковск", "Novomoskovsk"),
+        ];
+        assert_eq!(upsert_stations(conn.clone(), stations).await.unwrap(), 3);
+
+        assert_eq!(
+            names(conn.clone(), "МОСК").await,
+            vec!["Москва", "Новомосковск"]
+        );
+        assert_eq!(names(conn.clone(), "sankt").await, vec!["Санкт-Петербург"]);
+        assert!(names(conn, "Казань").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn upsert_renames_known_codes_and_unknown_codes_are_reported() {
+        let conn = test_db_config().await.get_connection().await;
+        upsert_stations(conn.clone(), vec![station("2000000", "Москва", "Moskva")])
+            .await
+            .unwrap();
+        upsert_stations(
+            conn.clone(),
+            vec![station("2000000", "Москва Все вокзалы", "Moskva")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            names(conn.clone(), "москва").await,
+            vec!["Москва Все вокзалы"]
+        );
+        let codes = vec![String::from("2000000"), String::from("2004000")];
+        assert_eq!(
+            find_unknown_station_codes(conn.clone(), codes)
+                .await
+                .unwrap(),
+            vec!["2004000"]
+        );
+        assert!(has_stations(conn).await.unwrap());
+    }
+}
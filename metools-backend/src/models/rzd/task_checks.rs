@@ -0,0 +1,106 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surrealdb::{
+    sql::{Datetime, Thing},
+    Connection, Error, Response, Surreal,
+};
+
+use crate::{models::rzd::tasks::TaskCheck, utils::pagination::Cursor};
+
+const TABLE_NAME: &str = "rzd_task_checks";
+
+#[derive(Debug, Display)]
+pub enum TaskChecksDBError {
+    UnknownError(Error),
+}
+
+#[derive(Serialize)]
+struct NewTaskCheckRecord {
+    task: Thing,
+    check: TaskCheck,
+    notified: bool,
+}
+
+/// A single poll of a task as it was seen at `created_at`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TaskCheckRecord {
+    pub id: Thing,
+    pub created_at: Datetime,
+    pub check: TaskCheck,
+    pub notified: bool,
+}
+
+pub async fn insert_task_check<T: Connection>(
+    conn: &Surreal<T>,
+    task_id: Thing,
+    check: TaskCheck,
+    notified: bool,
+) -> Result<(), TaskChecksDBError> {
+    let new_check = NewTaskCheckRecord {
+        task: task_id,
+        check,
+        notified,
+    };
+
+    let r: Result<Vec<TaskCheckRecord>, Error> = conn.create(TABLE_NAME).content(new_check).await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(TaskChecksDBError::UnknownError(err)),
+    }
+}
+
+/// Lists checks of a task newest first, starting after `cursor` when it is set.
+pub async fn list_task_checks<T: Connection>(
+    conn: Surreal<T>,
+    task_id: Thing,
+    cursor: Option<Cursor>,
+    limit: usize,
+) -> Result<Vec<TaskCheckRecord>, TaskChecksDBError> {
+    let query = match cursor {
+        Some(_) => "SELECT id, created_at, check, notified FROM type::table($table) WHERE task = <record>$task_id AND (created_at < <datetime>$cursor.created_at OR (created_at = <datetime>$cursor.created_at AND id < <record>$cursor.id)) ORDER BY created_at DESC, id DESC LIMIT $limit",
+        None => "SELECT id, created_at, check, notified FROM type::table($table) WHERE task = <record>$task_id ORDER BY created_at DESC, id DESC LIMIT $limit",
+    };
+    let r: Result<Response, Error> = conn
+        .query(query)
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "task_id": task_id.to_string(),
+                "cursor": cursor,
+                "limit": limit
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut checks) => match checks.take::<Vec<TaskCheckRecord>>(0) {
+            Ok(checks) => Ok(checks),
+            Err(err) => Err(TaskChecksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TaskChecksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_old_task_checks<T: Connection>(
+    conn: Surreal<T>,
+    retention_days: u32,
+) -> Result<usize, TaskChecksDBError> {
+    let r = conn
+        .query("count(DELETE type::table($table) WHERE created_at < time::now() - <duration>$retention RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "retention": format!("{retention_days}d")
+            }
+        ))
+        .await;
+    match r {
+        Ok(mut r) => {
+            let surreal_response = r.take::<Vec<usize>>(0).unwrap()[0];
+            Ok(surreal_response)
+        }
+        Err(err) => Err(TaskChecksDBError::UnknownError(err)),
+    }
+}
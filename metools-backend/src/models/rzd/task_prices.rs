@@ -0,0 +1,111 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surrealdb::{
+    sql::{Datetime, Thing},
+    Connection, Error, Response, Surreal,
+};
+
+use crate::models::rzd::tasks::{ClassPrice, TripLeg};
+
+const TABLE_NAME: &str = "rzd_task_prices";
+
+#[derive(Debug, Display)]
+pub enum TaskPricesDBError {
+    UnknownError(Error),
+}
+
+#[derive(Serialize)]
+struct NewTaskPrice {
+    task: Thing,
+    #[serde(flatten)]
+    price: ClassPrice,
+}
+
+/// Minimum price of a car class seen by one check.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TaskPrice {
+    pub created_at: Datetime,
+    /// Not set for prices stored before they were tracked per travel date.
+    pub date: Option<NaiveDate>,
+    pub leg: Option<TripLeg>,
+    pub car_type: String,
+    pub min_price: u32,
+}
+
+pub async fn insert_task_prices<T: Connection>(
+    conn: &Surreal<T>,
+    task_id: Thing,
+    prices: Vec<ClassPrice>,
+) -> Result<(), TaskPricesDBError> {
+    let prices: Vec<NewTaskPrice> = prices
+        .into_iter()
+        .map(|price| NewTaskPrice {
+            task: task_id.clone(),
+            price,
+        })
+        .collect();
+    if prices.is_empty() {
+        return Ok(());
+    }
+
+    let r: Result<Vec<TaskPrice>, Error> = conn.insert(TABLE_NAME).content(prices).await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(TaskPricesDBError::UnknownError(err)),
+    }
+}
+
+/// Lists the price series of a task oldest first, optionally only since `since`.
+pub async fn list_task_prices<T: Connection>(
+    conn: Surreal<T>,
+    task_id: Thing,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<TaskPrice>, TaskPricesDBError> {
+    let query = match since {
+        Some(_) => "SELECT created_at, date, leg, car_type, min_price FROM type::table($table) WHERE task = <record>$task_id AND created_at >= <datetime>$since ORDER BY created_at",
+        None => "SELECT created_at, date, leg, car_type, min_price FROM type::table($table) WHERE task = <record>$task_id ORDER BY created_at",
+    };
+    let r: Result<Response, Error> = conn
+        .query(query)
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "task_id": task_id.to_string(),
+                "since": since
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut prices) => match prices.take::<Vec<TaskPrice>>(0) {
+            Ok(prices) => Ok(prices),
+            Err(err) => Err(TaskPricesDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TaskPricesDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_old_task_prices<T: Connection>(
+    conn: Surreal<T>,
+    retention_days: u32,
+) -> Result<usize, TaskPricesDBError> {
+    let r = conn
+        .query("count(DELETE type::table($table) WHERE created_at < time::now() - <duration>$retention RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "retention": format!("{retention_days}d")
+            }
+        ))
+        .await;
+    match r {
+        Ok(mut r) => {
+            let surreal_response = r.take::<Vec<usize>>(0).unwrap()[0];
+            Ok(surreal_response)
+        }
+        Err(err) => Err(TaskPricesDBError::UnknownError(err)),
+    }
+}
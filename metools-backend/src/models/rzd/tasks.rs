@@ -1,84 +1,870 @@
-use std::collections::HashMap;
-
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use serde_with::{serde_as, DefaultOnNull};
 use surrealdb::{
     sql::{Datetime, Thing},
     Connection, Error, Response, Surreal,
 };
 use utoipa::ToSchema;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::{
+    models::notifications::TaskNotificationOverrides,
+    utils::pagination::{Cursor, SortOrder},
+};
 
 #[derive(Debug, Display)]
 pub enum TasksDBError {
     NoDeletedTask,
+    TaskNotFound,
+    /// The write would make more tasks of the user active than the given limit.
+    ActiveTasksLimitReached(usize),
     UnknownError(Error),
 }
 
+pub fn validate_station_code(code: &str) -> Result<(), ValidationError> {
+    // RZD express codes are 7 digits, e.g. 2000000 for Moscow
+    if code.len() != 7 || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ValidationError::new("bad_station_code"));
+    }
+    Ok(())
+}
+
+fn validate_date_not_in_past(date: &NaiveDate) -> Result<(), ValidationError> {
+    if *date < Utc::now().date_naive() {
+        return Err(ValidationError::new("date_in_past"));
+    }
+    Ok(())
+}
+
+const MAX_TASK_DATES: usize = 31;
+
+fn validate_task_dates(dates: &[NaiveDate]) -> Result<(), ValidationError> {
+    if dates.is_empty() || dates.len() > MAX_TASK_DATES {
+        return Err(ValidationError::new("bad_dates_count"));
+    }
+    dates.iter().try_for_each(validate_date_not_in_past)
+}
+
+fn validate_date_range(task: &DateRangeTask) -> Result<(), ValidationError> {
+    if task.date_to < task.date_from
+        || (task.date_to - task.date_from).num_days() >= MAX_TASK_DATES as i64
+    {
+        return Err(ValidationError::new("bad_date_range"));
+    }
+    Ok(())
+}
+
+fn validate_round_trip(task: &RoundTripTask) -> Result<(), ValidationError> {
+    if task.return_leg.date < task.outbound.date {
+        return Err(ValidationError::new("return_before_outbound"));
+    }
+    Ok(())
+}
+
+fn validate_train_number(tnum: &str) -> Result<(), ValidationError> {
+    // Train numbers are 3 digits followed by a letter, e.g. 016А
+    if !(4..=5).contains(&tnum.chars().count())
+        || !tnum.chars().take(3).all(|c| c.is_ascii_digit())
+        || !tnum.chars().skip(3).all(char::is_alphabetic)
+    {
+        return Err(ValidationError::new("bad_train_number"));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+pub struct DayTask {
+    #[validate(custom(function = "validate_station_code"))]
+    pub from_point_code: String,
+    #[validate(custom(function = "validate_station_code"))]
+    pub to_point_code: String,
+    #[validate(custom(function = "validate_date_not_in_past"))]
+    #[schema(value_type = String, format = Date)]
+    pub date: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+pub struct TrainTask {
+    #[validate(custom(function = "validate_station_code"))]
+    pub from_point_code: String,
+    #[validate(custom(function = "validate_station_code"))]
+    pub to_point_code: String,
+    #[validate(custom(function = "validate_date_not_in_past"))]
+    #[schema(value_type = String, format = Date)]
+    pub date: NaiveDate,
+    #[schema(value_type = String, example = "23:55:00")]
+    pub time: NaiveTime,
+    #[validate(custom(function = "validate_train_number"))]
+    pub tnum: String,
+}
+
+/// Watches several arbitrary days at once.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+pub struct DatesTask {
+    #[validate(custom(function = "validate_station_code"))]
+    pub from_point_code: String,
+    #[validate(custom(function = "validate_station_code"))]
+    pub to_point_code: String,
+    #[validate(custom(function = "validate_task_dates"))]
+    #[schema(value_type = Vec<String>)]
+    pub dates: Vec<NaiveDate>,
+}
+
+/// Watches every day from `date_from` to `date_to` inclusive.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+#[validate(schema(function = "validate_date_range"))]
+pub struct DateRangeTask {
+    #[validate(custom(function = "validate_station_code"))]
+    pub from_point_code: String,
+    #[validate(custom(function = "validate_station_code"))]
+    pub to_point_code: String,
+    #[validate(custom(function = "validate_date_not_in_past"))]
+    #[schema(value_type = String, format = Date)]
+    pub date_from: NaiveDate,
+    #[schema(value_type = String, format = Date)]
+    pub date_to: NaiveDate,
+}
+
+/// Watches an outbound and a return journey together.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+#[validate(schema(function = "validate_round_trip"))]
+pub struct RoundTripTask {
+    #[validate(nested)]
+    pub outbound: DayTask,
+    #[validate(nested)]
+    pub return_leg: DayTask,
+    /// Notify as soon as any leg has seats instead of waiting for both.
+    #[serde(default)]
+    pub alert_per_leg: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskType {
+    Day,
+    Train,
+    Dates,
+    DateRange,
+    RoundTrip,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum TaskData {
+    Day(DayTask),
+    Train(TrainTask),
+    Dates(DatesTask),
+    DateRange(DateRangeTask),
+    RoundTrip(RoundTripTask),
+}
+
+impl Validate for TaskData {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            Self::Day(task) => task.validate(),
+            Self::Train(task) => task.validate(),
+            Self::Dates(task) => task.validate(),
+            Self::DateRange(task) => task.validate(),
+            Self::RoundTrip(task) => task.validate(),
+        }
+    }
+}
+
+impl TaskData {
+    /// Station codes of every leg, without duplicates.
+    pub fn station_codes(&self) -> Vec<String> {
+        let mut codes = match self {
+            Self::Day(task) => vec![&task.from_point_code, &task.to_point_code],
+            Self::Train(task) => vec![&task.from_point_code, &task.to_point_code],
+            Self::Dates(task) => vec![&task.from_point_code, &task.to_point_code],
+            Self::DateRange(task) => vec![&task.from_point_code, &task.to_point_code],
+            Self::RoundTrip(task) => vec![
+                &task.outbound.from_point_code,
+                &task.outbound.to_point_code,
+                &task.return_leg.from_point_code,
+                &task.return_leg.to_point_code,
+            ],
+        }
+        .into_iter()
+        .cloned()
+        .collect::<Vec<String>>();
+        codes.sort();
+        codes.dedup();
+        codes
+    }
+
+    /// Travel dates watched by the task.
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        match self {
+            Self::Day(task) => vec![task.date],
+            Self::Train(task) => vec![task.date],
+            Self::Dates(task) => task.dates.clone(),
+            Self::DateRange(task) => task
+                .date_from
+                .iter_days()
+                .take_while(|date| *date <= task.date_to)
+                .collect(),
+            Self::RoundTrip(task) => vec![task.outbound.date, task.return_leg.date],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatType {
+    Lower,
+    Upper,
+    SideLower,
+    SideUpper,
+}
+
+/// Optional criteria a car class has to meet before the user is notified about it.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug, Default)]
+pub struct TaskFilters {
+    /// Car classes as named by RZD, e.g. `Купе`. Any class matches when empty.
+    #[serde(default)]
+    pub car_types: Vec<String>,
+    /// Seat types are only known for train tasks. Any seat matches when empty.
+    #[serde(default)]
+    pub seat_types: Vec<SeatType>,
+    #[validate(range(min = 1))]
+    pub max_price: Option<u32>,
+    #[validate(range(min = 1))]
+    pub min_seats: Option<u32>,
+}
+
+fn validate_price_alert(alert: &PriceAlert) -> Result<(), ValidationError> {
+    if alert.below_price.is_none() && alert.drop_percent.is_none() {
+        return Err(ValidationError::new("empty_price_alert"));
+    }
+    Ok(())
+}
+
+/// Alerts about prices of car classes that already have seats.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Validate, Debug)]
+#[validate(schema(function = "validate_price_alert"))]
+pub struct PriceAlert {
+    /// Alert once the minimum price of a car class falls to this value or below.
+    #[validate(range(min = 1))]
+    pub below_price: Option<u32>,
+    /// Alert once the minimum price of a car class drops by this percentage from its baseline.
+    #[validate(range(min = 1, max = 99))]
+    pub drop_percent: Option<u32>,
+}
+
+/// A car class on one travel date and trip leg, its prices are tracked separately.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PriceKey {
+    #[schema(value_type = String, format = Date)]
+    pub date: NaiveDate,
+    /// Set for round-trip tasks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leg: Option<TripLeg>,
+    pub car_type: String,
+}
+
+impl PriceKey {
+    /// Travel date as shown in notifications, with the leg for round-trip tasks.
+    pub fn date_label(&self) -> String {
+        let date = self.date.format("%d.%m.%Y");
+        match self.leg {
+            Some(TripLeg::Outbound) => format!("{date}, outbound"),
+            Some(TripLeg::Return) => format!("{date}, return"),
+            None => date.to_string(),
+        }
+    }
+}
+
+/// Minimum price of a car class on one travel date and trip leg.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq)]
+pub struct ClassPrice {
+    #[serde(flatten)]
+    pub key: PriceKey,
+    pub min_price: u32,
+}
+
+/// Price state of a car class used to detect threshold crossings and drops.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct TrackedPrice {
+    #[serde(flatten)]
+    pub key: PriceKey,
+    pub last_price: u32,
+    /// Price drops are measured from here: the highest price since the last alert.
+    pub baseline_price: u32,
+}
+
+/// A car class whose minimum price triggered a price alert.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PriceDrop {
+    #[serde(flatten)]
+    pub key: PriceKey,
+    pub price: u32,
+    pub baseline_price: u32,
+}
+
+pub fn validate_task_filters(
+    data: &TaskData,
+    filters: &TaskFilters,
+) -> Result<(), ValidationError> {
+    if !filters.seat_types.is_empty() && !matches!(data, TaskData::Train(_)) {
+        return Err(ValidationError::new("seat_types_require_train_task"));
+    }
+    Ok(())
+}
+
+/// What a user can tune on a task besides the trip itself.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct TaskSettings {
+    pub filters: TaskFilters,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_alert: Option<PriceAlert>,
+    /// Seconds between checks, every poller run when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_interval: Option<u64>,
+}
+
 #[derive(Serialize)]
 struct NewTask {
     user: Thing,
-    #[serde(rename = "type")]
-    type_: String,
-    data: HashMap<String, String>,
+    #[serde(flatten)]
+    data: TaskData,
+    #[serde(flatten)]
+    settings: TaskSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, Display, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    #[default]
+    Active,
+    Paused,
+    Fulfilled,
+    Expired,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskCheckStatus {
+    SeatsFound,
+    NoSeats,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TripLeg {
+    Outbound,
+    Return,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct AvailableCarClass {
+    pub car_type: String,
+    pub free_seats: u32,
+    pub min_price: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct AvailableTrain {
+    pub number: String,
+    pub departure_date: String,
+    pub departure_time: String,
+    pub arrival_date: String,
+    pub arrival_time: String,
+    pub car_classes: Vec<AvailableCarClass>,
+    /// Set for trains found by round-trip tasks.
+    #[serde(default)]
+    pub leg: Option<TripLeg>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct LegCheck {
+    pub leg: TripLeg,
+    pub status: TaskCheckStatus,
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+pub struct TaskCheck {
+    pub status: TaskCheckStatus,
+    pub trains: Vec<AvailableTrain>,
+    /// Travel dates on which seats were found.
+    #[serde(default)]
+    #[schema(value_type = Vec<String>)]
+    pub available_dates: Vec<NaiveDate>,
+    /// Per-leg outcome of round-trip tasks.
+    #[serde(default)]
+    pub legs: Vec<LegCheck>,
+    pub error: Option<String>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Task {
     pub id: Thing,
     pub created_at: Datetime,
-    #[serde(rename = "type")]
-    pub type_: String,
-    pub data: HashMap<String, String>,
+    #[serde(flatten)]
+    pub data: TaskData,
+    pub user: Thing,
+    #[serde(default)]
+    pub filters: TaskFilters,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub failed_checks: u32,
+    pub last_checked_at: Option<Datetime>,
+    pub last_check: Option<TaskCheck>,
+    pub notified_availability: Option<Vec<String>>,
+    pub last_notified_at: Option<Datetime>,
+    pub price_alert: Option<PriceAlert>,
+    /// Selecting the field by name gives null until prices are tracked.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub tracked_prices: Vec<TrackedPrice>,
+    pub check_interval: Option<u64>,
+    pub notifications: Option<TaskNotificationOverrides>,
+    pub next_check_at: Option<Datetime>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TaskStatusChange {
+    pub id: Thing,
     pub user: Thing,
+    pub status: TaskStatus,
 }
 
 const TABLE_NAME: &str = "rzd_tasks";
 
+/// Result of a write that is refused when it would exceed the active tasks limit of the user.
+#[derive(Deserialize)]
+struct LimitedTaskWrite {
+    limited: bool,
+    tasks: Vec<Task>,
+}
+
+/// Creates an active task unless the user already has `max_active_tasks` of them,
+/// the count and the insert run in one transaction.
 pub async fn insert_new_task<T: Connection>(
     conn: Surreal<T>,
     user_id: Thing,
-    type_: String,
-    task_data: HashMap<String, String>,
+    task_data: TaskData,
+    settings: TaskSettings,
+    max_active_tasks: usize,
 ) -> Result<Task, TasksDBError> {
     let new_task = NewTask {
-        user: user_id,
-        type_,
+        user: user_id.clone(),
         data: task_data,
+        settings,
     };
 
-    let r: Result<Vec<Task>, Error> = conn.create(TABLE_NAME).content(new_task).await;
+    let r: Result<Response, Error> = conn
+        .query(
+            "BEGIN TRANSACTION;
+            LET $limited = count(SELECT id FROM type::table($table) WHERE user = <record>$user_id AND status = 'active') >= $max_active_tasks;
+            LET $tasks = IF $limited THEN [] ELSE (CREATE type::table($table) CONTENT $task) END;
+            RETURN { limited: $limited, tasks: $tasks };
+            COMMIT TRANSACTION;",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "max_active_tasks": max_active_tasks
+            }
+        ))
+        .bind(("task", new_task))
+        .await;
 
     match r {
-        Ok(tasks) => Ok(tasks[0].clone()),
+        Ok(mut r) => match r.take::<Option<LimitedTaskWrite>>(0) {
+            Ok(Some(LimitedTaskWrite { limited: true, .. })) => {
+                Err(TasksDBError::ActiveTasksLimitReached(max_active_tasks))
+            }
+            Ok(Some(LimitedTaskWrite { tasks, .. })) => match tasks.into_iter().next() {
+                Some(task) => Ok(task),
+                None => Err(TasksDBError::TaskNotFound),
+            },
+            Ok(None) => Err(TasksDBError::TaskNotFound),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
         Err(err) => Err(TasksDBError::UnknownError(err)),
     }
 }
 
-pub async fn list_all_tasks<T: Connection>(conn: Surreal<T>) -> Result<Vec<Task>, TasksDBError> {
-    let r: Result<Vec<Task>, Error> = conn.select(TABLE_NAME).await;
+/// Active tasks that were never checked or whose next check time comes within `lookahead` seconds.
+pub async fn list_tasks_due_for_check<T: Connection>(
+    conn: Surreal<T>,
+    lookahead: u64,
+) -> Result<Vec<Task>, TasksDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT * FROM type::table($table) WHERE status = 'active' AND (next_check_at IS NONE OR next_check_at <= time::now() + <duration>$lookahead)")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "lookahead": format!("{lookahead}s")
+            }
+        ))
+        .await;
 
     match r {
-        Ok(tasks) => Ok(tasks),
+        Ok(mut tasks) => match tasks.take::<Vec<Task>>(0) {
+            Ok(tasks) => Ok(tasks),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
         Err(err) => Err(TasksDBError::UnknownError(err)),
     }
 }
 
-pub async fn list_all_users_tasks<T: Connection>(
+/// Optional conditions and the page to read when listing a user's tasks.
+#[derive(Clone, Debug, Default)]
+pub struct TasksQuery {
+    pub task_type: Option<TaskType>,
+    pub status: Option<TaskStatus>,
+    /// Tasks with travel dates on or after this day.
+    pub date_from: Option<NaiveDate>,
+    /// Tasks with travel dates on or before this day.
+    pub date_to: Option<NaiveDate>,
+    pub station_code: Option<String>,
+    pub order: SortOrder,
+    pub cursor: Option<Cursor>,
+    pub limit: usize,
+}
+
+pub async fn list_users_tasks<T: Connection>(
     conn: Surreal<T>,
     user_id: Thing,
+    query: TasksQuery,
 ) -> Result<Vec<Task>, TasksDBError> {
-    let r: Result<Response, Error> = conn.query("SELECT id, created_at, type, data, user FROM type::table($table) WHERE user = <record>$user_id").bind(
+    let mut conditions = vec!["user = <record>$user_id"];
+    if query.task_type.is_some() {
+        conditions.push("type = $task_type");
+    }
+    if query.status.is_some() {
+        conditions.push("status = $status");
+    }
+    if query.date_from.is_some() {
+        conditions.push(
+            "((type IN ['day', 'train'] AND data.date >= $date_from)
+            OR (type = 'dates' AND array::max(data.dates) >= $date_from)
+            OR (type = 'date_range' AND data.date_to >= $date_from)
+            OR (type = 'round_trip' AND data.return_leg.date >= $date_from))",
+        );
+    }
+    if query.date_to.is_some() {
+        conditions.push(
+            "((type IN ['day', 'train'] AND data.date <= $date_to)
+            OR (type = 'dates' AND array::min(data.dates) <= $date_to)
+            OR (type = 'date_range' AND data.date_from <= $date_to)
+            OR (type = 'round_trip' AND data.outbound.date <= $date_to))",
+        );
+    }
+    if query.station_code.is_some() {
+        conditions.push(
+            "$station_code IN [data.from_point_code, data.to_point_code,
+            data.outbound.from_point_code, data.outbound.to_point_code,
+            data.return_leg.from_point_code, data.return_leg.to_point_code]",
+        );
+    }
+    let order = match query.order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    if query.cursor.is_some() {
+        conditions.push(match query.order {
+            SortOrder::Asc => "(created_at > <datetime>$cursor.created_at OR (created_at = <datetime>$cursor.created_at AND id > <record>$cursor.id))",
+            SortOrder::Desc => "(created_at < <datetime>$cursor.created_at OR (created_at = <datetime>$cursor.created_at AND id < <record>$cursor.id))",
+        });
+    }
+
+    let r: Result<Response, Error> = conn
+        .query(format!(
+            "SELECT id, created_at, type, data, user, filters, status, failed_checks, last_checked_at, last_check, notified_availability, last_notified_at, price_alert, tracked_prices, check_interval, notifications, next_check_at FROM type::table($table) WHERE {} ORDER BY created_at {order}, id {order} LIMIT $limit",
+            conditions.join(" AND ")
+        ))
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "task_type": query.task_type,
+                "status": query.status,
+                "date_from": query.date_from,
+                "date_to": query.date_to,
+                "station_code": query.station_code,
+                "cursor": query.cursor,
+                "limit": query.limit
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut tasks) => match tasks.take::<Vec<Task>>(0) {
+            Ok(tasks) => Ok(tasks),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn get_task_by_id_for_user<T: Connection>(
+    conn: Surreal<T>,
+    user_id: Thing,
+    task_id: Thing,
+) -> Result<Task, TasksDBError> {
+    let r: Result<Response, Error> = conn.query("SELECT id, created_at, type, data, user, filters, status, failed_checks, last_checked_at, last_check, notified_availability, last_notified_at, price_alert, tracked_prices, check_interval, notifications, next_check_at FROM type::table($table) WHERE user = <record>$user_id AND id = <record>$task_id").bind(
         json!(
             {
                 "table": TABLE_NAME,
-                "user_id":  user_id.to_string()
+                "user_id": user_id.to_string(),
+                "task_id": task_id.to_string()
             }
         )
     ).await;
 
     match r {
-        Ok(mut tasks) => Ok(tasks.take::<Vec<Task>>(0).unwrap().clone()),
+        Ok(mut tasks) => match tasks.take::<Option<Task>>(0) {
+            Ok(Some(task)) => Ok(task),
+            Ok(None) => Err(TasksDBError::TaskNotFound),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+/// Replaces what the task watches. With `reset_checks` the monitoring state starts over
+/// and a finished task becomes active again.
+/// Restarting checks brings finished tasks back to active, which is refused when the user
+/// already has `max_active_tasks` active tasks.
+pub async fn update_task_for_user<T: Connection>(
+    conn: Surreal<T>,
+    user_id: Thing,
+    task_id: Thing,
+    task_data: TaskData,
+    settings: TaskSettings,
+    reset_checks: bool,
+    max_active_tasks: usize,
+) -> Result<Task, TasksDBError> {
+    let r: Result<Response, Error> = conn
+        .query(
+            "BEGIN TRANSACTION;
+            LET $activates = $reset_checks AND (SELECT VALUE status FROM type::table($table) WHERE user = <record>$user_id AND id = <record>$task_id)[0] IN ['expired', 'fulfilled', 'failed'];
+            LET $limited = $activates AND count(SELECT id FROM type::table($table) WHERE user = <record>$user_id AND status = 'active') >= $max_active_tasks;
+            LET $tasks = IF $limited THEN [] ELSE (UPDATE type::table($table) SET type = $task.type, data = $task.data, filters = $filters,
+            price_alert = IF $price_alert THEN $price_alert ELSE NONE END,
+            check_interval = IF $check_interval THEN $check_interval ELSE NONE END,
+            notified_availability = IF $reset_checks THEN NONE ELSE notified_availability END,
+            last_notified_at = IF $reset_checks THEN NONE ELSE last_notified_at END,
+            last_check = IF $reset_checks THEN NONE ELSE last_check END,
+            last_checked_at = IF $reset_checks THEN NONE ELSE last_checked_at END,
+            tracked_prices = IF $reset_checks THEN NONE ELSE tracked_prices END,
+            failed_checks = IF $reset_checks THEN 0 ELSE failed_checks END,
+            status = IF $reset_checks AND status IN ['expired', 'fulfilled', 'failed'] THEN 'active' ELSE status END
+            WHERE user = <record>$user_id AND id = <record>$task_id RETURN AFTER) END;
+            RETURN { limited: $limited = true, tasks: $tasks };
+            COMMIT TRANSACTION;",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "task_id": task_id.to_string(),
+                "task": task_data,
+                "filters": settings.filters,
+                "price_alert": settings.price_alert,
+                "check_interval": settings.check_interval,
+                "reset_checks": reset_checks,
+                "max_active_tasks": max_active_tasks
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut r) => match r.take::<Option<LimitedTaskWrite>>(0) {
+            Ok(Some(LimitedTaskWrite { limited: true, .. })) => {
+                Err(TasksDBError::ActiveTasksLimitReached(max_active_tasks))
+            }
+            Ok(Some(LimitedTaskWrite { tasks, .. })) => match tasks.into_iter().next() {
+                Some(task) => Ok(task),
+                None => Err(TasksDBError::TaskNotFound),
+            },
+            Ok(None) => Err(TasksDBError::TaskNotFound),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn set_task_notifications_for_user<T: Connection>(
+    conn: Surreal<T>,
+    user_id: Thing,
+    task_id: Thing,
+    notifications: TaskNotificationOverrides,
+) -> Result<Task, TasksDBError> {
+    let r: Result<Response, Error> = conn
+        .query("UPDATE type::table($table) SET notifications = $notifications WHERE user = <record>$user_id AND id = <record>$task_id RETURN AFTER")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "task_id": task_id.to_string(),
+                "notifications": notifications
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut tasks) => match tasks.take::<Option<Task>>(0) {
+            Ok(Some(task)) => Ok(task),
+            Ok(None) => Err(TasksDBError::TaskNotFound),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+/// Makes a task of the user active again unless they already have `max_active_tasks` active
+/// tasks, the count and the update run in one transaction.
+pub async fn activate_task_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    task_id: Thing,
+    max_active_tasks: usize,
+) -> Result<(), TasksDBError> {
+    let r: Result<Response, Error> = conn
+        .query(
+            "BEGIN TRANSACTION;
+            LET $limited = count(SELECT id FROM type::table($table) WHERE user = <record>$user_id AND status = 'active') >= $max_active_tasks;
+            LET $tasks = IF $limited THEN [] ELSE (UPDATE type::table($table) SET status = 'active', failed_checks = 0 WHERE user = <record>$user_id AND id = <record>$task_id RETURN AFTER) END;
+            RETURN { limited: $limited, tasks: $tasks };
+            COMMIT TRANSACTION;",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "task_id": task_id.to_string(),
+                "max_active_tasks": max_active_tasks
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut r) => match r.take::<Option<LimitedTaskWrite>>(0) {
+            Ok(Some(LimitedTaskWrite { limited: true, .. })) => {
+                Err(TasksDBError::ActiveTasksLimitReached(max_active_tasks))
+            }
+            Ok(Some(LimitedTaskWrite { tasks, .. })) if !tasks.is_empty() => Ok(()),
+            Ok(_) => Err(TasksDBError::TaskNotFound),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn set_task_status<T: Connection>(
+    conn: &Surreal<T>,
+    task_id: Thing,
+    status: TaskStatus,
+) -> Result<(), TasksDBError> {
+    let r = conn
+        .query("UPDATE type::table($table) SET status = $status, failed_checks = 0 WHERE id = <record>$task_id")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "task_id": task_id.to_string(),
+                "status": status
+            }
+        ))
+        .await;
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+/// Saves the check outcome and marks the task failed after `max_failed_checks` errors in a row,
+/// returns the status of the task after the check.
+///
+/// Errors with `count_failure` unset, e.g. RZD being down, neither count nor reset the streak.
+pub async fn set_task_check<T: Connection>(
+    conn: &Surreal<T>,
+    task_id: Thing,
+    check: TaskCheck,
+    count_failure: bool,
+    max_failed_checks: u32,
+    next_check_at: DateTime<Utc>,
+) -> Result<TaskStatus, TasksDBError> {
+    let r = conn
+        .query(
+            "UPDATE type::table($table) SET last_checked_at = time::now(), last_check = $check, next_check_at = <datetime>$next_check_at,
+            failed_checks = IF $check.status != 'error' THEN 0 ELSE IF $count_failure THEN failed_checks + 1 ELSE failed_checks END,
+            status = IF failed_checks >= $max_failed_checks THEN 'failed' ELSE status END WHERE id = <record>$task_id RETURN VALUE status",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "task_id": task_id.to_string(),
+                "check": check,
+                "count_failure": count_failure,
+                "max_failed_checks": max_failed_checks,
+                "next_check_at": next_check_at
+            }
+        ))
+        .await;
+    match r {
+        Ok(mut status) => match status.take::<Option<TaskStatus>>(0) {
+            Ok(Some(status)) => Ok(status),
+            Ok(None) => Err(TasksDBError::TaskNotFound),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn set_task_notified_availability<T: Connection>(
+    conn: &Surreal<T>,
+    task_id: Thing,
+    notified_availability: Option<Vec<String>>,
+) -> Result<(), TasksDBError> {
+    let query = match notified_availability {
+        Some(_) => "UPDATE type::table($table) SET notified_availability = $notified_availability, last_notified_at = time::now() WHERE id = <record>$task_id",
+        None => "UPDATE type::table($table) SET notified_availability = NONE WHERE id = <record>$task_id",
+    };
+    let r = conn
+        .query(query)
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "task_id": task_id.to_string(),
+                "notified_availability": notified_availability
+            }
+        ))
+        .await;
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn set_task_tracked_prices<T: Connection>(
+    conn: &Surreal<T>,
+    task_id: Thing,
+    tracked_prices: Vec<TrackedPrice>,
+) -> Result<(), TasksDBError> {
+    let r = conn
+        .query("UPDATE type::table($table) SET tracked_prices = $tracked_prices WHERE id = <record>$task_id")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "task_id": task_id.to_string(),
+                "tracked_prices": tracked_prices
+            }
+        ))
+        .await;
+    match r {
+        Ok(_) => Ok(()),
         Err(err) => Err(TasksDBError::UnknownError(err)),
     }
 }
@@ -130,3 +916,333 @@ pub async fn delete_all_tasks_for_user<T: Connection>(
         Err(err) => Err(TasksDBError::UnknownError(err)),
     }
 }
+
+/// Moves tasks whose last travel date has passed to `fulfilled` if the user was ever notified,
+/// otherwise to `expired`.
+pub async fn expire_outdated_tasks<T: Connection>(
+    conn: Surreal<T>,
+) -> Result<Vec<TaskStatusChange>, TasksDBError> {
+    let r: Result<Response, Error> = conn
+        .query("UPDATE type::table($table) SET status = IF last_notified_at THEN 'fulfilled' ELSE 'expired' END WHERE status IN ['active', 'paused', 'failed'] AND (
+            (type IN ['day', 'train'] AND data.date < $today)
+            OR (type = 'dates' AND array::max(data.dates) < $today)
+            OR (type = 'date_range' AND data.date_to < $today)
+            OR (type = 'round_trip' AND data.return_leg.date < $today)
+        ) RETURN id, user, status")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "today": chrono::Utc::now().date_naive()
+            }
+        ))
+        .await;
+    match r {
+        Ok(mut changes) => match changes.take::<Vec<TaskStatusChange>>(0) {
+            Ok(changes) => Ok(changes),
+            Err(err) => Err(TasksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TasksDBError::UnknownError(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Days, TimeDelta};
+    use surrealdb::engine::any::Any;
+
+    use super::*;
+    use crate::utils::test_db::test_db_config;
+
+    fn user() -> Thing {
+        Thing::from(("users", "user"))
+    }
+
+    fn day_task() -> TaskData {
+        TaskData::Day(DayTask {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from("2000000"),
+            date: Utc::now().date_naive() + Days::new(1),
+        })
+    }
+
+    #[test]
+    fn station_codes_are_seven_digits() {
+        assert!(validate_station_code("2000000").is_ok());
+        for code in ["200000", "20000000", "200000A", "", "２０００００"] {
+            assert!(validate_station_code(code).is_err(), "{code}");
+        }
+        let task = DayTask {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from("moscow"),
+            date: Utc::now().date_naive() + Days::new(1),
+        };
+        assert!(task.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn task_reads_return_next_check_at() {
+        let conn = test_db_config().await.get_connection().await;
+        let task = insert_new_task(
+            conn.clone(),
+            user(),
+            day_task(),
+            TaskSettings::default(),
+            10,
+        )
+        .await
+        .unwrap();
+        let check = TaskCheck {
+            status: TaskCheckStatus::NoSeats,
+            trains: Vec::new(),
+            available_dates: Vec::new(),
+            legs: Vec::new(),
+            error: None,
+        };
+        let next_check_at = DateTime::from_timestamp(Utc::now().timestamp() + 600, 0).unwrap();
+        set_task_check(&conn, task.id.clone(), check, false, 3, next_check_at)
+            .await
+            .unwrap();
+
+        let task = get_task_by_id_for_user(conn.clone(), user(), task.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            task.next_check_at.map(|at| at.to_utc()),
+            Some(next_check_at)
+        );
+
+        let query = TasksQuery {
+            limit: 10,
+            ..TasksQuery::default()
+        };
+        let tasks = list_users_tasks(conn, user(), query).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(
+            tasks[0].next_check_at.clone().map(|at| at.to_utc()),
+            Some(next_check_at)
+        );
+        assert!(next_check_at - Utc::now() > TimeDelta::minutes(5));
+    }
+
+    #[tokio::test]
+    async fn writes_respect_active_tasks_limit() {
+        let conn = test_db_config().await.get_connection().await;
+        let insert =
+            || insert_new_task(conn.clone(), user(), day_task(), TaskSettings::default(), 1);
+        let first = insert().await.unwrap();
+        assert!(matches!(
+            insert().await,
+            Err(TasksDBError::ActiveTasksLimitReached(1))
+        ));
+
+        // Only active tasks count
+        set_task_status(&conn, first.id.clone(), TaskStatus::Paused)
+            .await
+            .unwrap();
+        let second = insert().await.unwrap();
+        assert!(matches!(
+            activate_task_for_user(&conn, user(), first.id.clone(), 1).await,
+            Err(TasksDBError::ActiveTasksLimitReached(1))
+        ));
+        set_task_status(&conn, second.id, TaskStatus::Expired)
+            .await
+            .unwrap();
+        activate_task_for_user(&conn, user(), first.id.clone(), 1)
+            .await
+            .unwrap();
+
+        // Concurrent creates still leave a single active task
+        set_task_status(&conn, first.id, TaskStatus::Paused)
+            .await
+            .unwrap();
+        let inserts: Vec<_> = (0..5)
+            .map(|_| {
+                tokio::spawn(insert_new_task(
+                    conn.clone(),
+                    user(),
+                    day_task(),
+                    TaskSettings::default(),
+                    1,
+                ))
+            })
+            .collect();
+        let mut created = 0;
+        for insert in inserts {
+            if insert.await.unwrap().is_ok() {
+                created += 1;
+            }
+        }
+        assert_eq!(created, 1);
+    }
+
+    fn in_days(days: u64) -> NaiveDate {
+        Utc::now().date_naive() + Days::new(days)
+    }
+
+    fn day(from_point_code: &str, to_point_code: &str, days: u64) -> DayTask {
+        DayTask {
+            from_point_code: String::from(from_point_code),
+            to_point_code: String::from(to_point_code),
+            date: in_days(days),
+        }
+    }
+
+    fn task_type(data: &TaskData) -> TaskType {
+        match data {
+            TaskData::Day(_) => TaskType::Day,
+            TaskData::Train(_) => TaskType::Train,
+            TaskData::Dates(_) => TaskType::Dates,
+            TaskData::DateRange(_) => TaskType::DateRange,
+            TaskData::RoundTrip(_) => TaskType::RoundTrip,
+        }
+    }
+
+    /// Lists the user's tasks and returns their types in list order.
+    async fn listed_types(conn: &Surreal<Any>, query: TasksQuery) -> Vec<TaskType> {
+        let query = TasksQuery { limit: 10, ..query };
+        list_users_tasks(conn.clone(), user(), query)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|task| task_type(&task.data))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_type_status_dates_and_station() {
+        let conn = test_db_config().await.get_connection().await;
+        let tasks = [
+            TaskData::Day(day("2004000", "2000000", 1)),
+            TaskData::Dates(DatesTask {
+                from_point_code: String::from("2000000"),
+                to_point_code: String::from("2010000"),
+                dates: vec![in_days(3), in_days(5)],
+            }),
+            TaskData::DateRange(DateRangeTask {
+                from_point_code: String::from("2010000"),
+                to_point_code: String::from("2060001"),
+                date_from: in_days(10),
+                date_to: in_days(12),
+            }),
+            TaskData::RoundTrip(RoundTripTask {
+                outbound: day("2004000", "2060001", 2),
+                return_leg: day("2060001", "2004000", 7),
+                alert_per_leg: false,
+            }),
+        ];
+        let mut ids = Vec::new();
+        for data in tasks {
+            let task = insert_new_task(conn.clone(), user(), data, TaskSettings::default(), 10)
+                .await
+                .unwrap();
+            ids.push(task.id);
+        }
+        set_task_status(&conn, ids[1].clone(), TaskStatus::Paused)
+            .await
+            .unwrap();
+        insert_new_task(
+            conn.clone(),
+            Thing::from(("users", "other")),
+            TaskData::Day(day("2004000", "2000000", 1)),
+            TaskSettings::default(),
+            10,
+        )
+        .await
+        .unwrap();
+
+        let all = listed_types(&conn, TasksQuery::default()).await;
+        assert_eq!(
+            all,
+            vec![
+                TaskType::RoundTrip,
+                TaskType::DateRange,
+                TaskType::Dates,
+                TaskType::Day
+            ]
+        );
+        let oldest_first = TasksQuery {
+            order: SortOrder::Asc,
+            ..TasksQuery::default()
+        };
+        assert_eq!(listed_types(&conn, oldest_first).await[0], TaskType::Day);
+
+        let by_type = TasksQuery {
+            task_type: Some(TaskType::Dates),
+            ..TasksQuery::default()
+        };
+        assert_eq!(listed_types(&conn, by_type).await, vec![TaskType::Dates]);
+        let by_status = TasksQuery {
+            status: Some(TaskStatus::Active),
+            ..TasksQuery::default()
+        };
+        assert_eq!(listed_types(&conn, by_status).await.len(), 3);
+
+        // Tasks match when any of their travel dates falls into the range
+        let by_dates = TasksQuery {
+            date_from: Some(in_days(4)),
+            date_to: Some(in_days(8)),
+            ..TasksQuery::default()
+        };
+        assert_eq!(
+            listed_types(&conn, by_dates).await,
+            vec![TaskType::RoundTrip, TaskType::Dates]
+        );
+        let from_day_11 = TasksQuery {
+            date_from: Some(in_days(11)),
+            ..TasksQuery::default()
+        };
+        assert_eq!(
+            listed_types(&conn, from_day_11).await,
+            vec![TaskType::DateRange]
+        );
+
+        // Both ends of a route and both legs of a round trip count
+        let by_station = TasksQuery {
+            station_code: Some(String::from("2060001")),
+            ..TasksQuery::default()
+        };
+        assert_eq!(
+            listed_types(&conn, by_station).await,
+            vec![TaskType::RoundTrip, TaskType::DateRange]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_ignores_status_of_other_users_tasks() {
+        let conn = test_db_config().await.get_connection().await;
+        insert_new_task(conn.clone(), user(), day_task(), TaskSettings::default(), 1)
+            .await
+            .unwrap();
+        let other_user = Thing::from(("users", "other"));
+        let other_task = insert_new_task(
+            conn.clone(),
+            other_user.clone(),
+            day_task(),
+            TaskSettings::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        set_task_status(&conn, other_task.id.clone(), TaskStatus::Expired)
+            .await
+            .unwrap();
+
+        // Reading the other task's status would report the user's quota instead
+        let r = update_task_for_user(
+            conn.clone(),
+            user(),
+            other_task.id.clone(),
+            day_task(),
+            TaskSettings::default(),
+            true,
+            1,
+        )
+        .await;
+        assert!(matches!(r, Err(TasksDBError::TaskNotFound)));
+
+        let other_task = get_task_by_id_for_user(conn, other_user, other_task.id)
+            .await
+            .unwrap();
+        assert_eq!(other_task.status, TaskStatus::Expired);
+    }
+}
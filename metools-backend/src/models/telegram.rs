@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surrealdb::{
+    sql::{Datetime, Thing},
+    Connection, Error, Response, Surreal,
+};
+
+const LINK_CODES_TABLE_NAME: &str = "telegram_link_codes";
+const CHATS_TABLE_NAME: &str = "telegram_chats";
+
+#[derive(Debug, Display)]
+pub enum TelegramDBError {
+    LinkCodeNotFound,
+    ChatNotFound,
+    UnknownError(Error),
+}
+
+#[derive(Serialize)]
+struct NewTelegramLinkCode {
+    code: String,
+    user: Thing,
+    valid_until: Datetime,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[allow(dead_code)]
+pub struct TelegramLinkCode {
+    pub id: Thing,
+    pub created_at: Datetime,
+    pub code: String,
+    pub user: Thing,
+    pub valid_until: Datetime,
+}
+
+/// Telegram chat the bot sends a user's alerts to.
+#[derive(Deserialize, Clone, Debug)]
+#[allow(dead_code)]
+pub struct TelegramChat {
+    pub id: Thing,
+    pub created_at: Datetime,
+    pub user: Thing,
+    pub chat_id: i64,
+    pub username: Option<String>,
+}
+
+pub async fn create_telegram_link_code<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    code: String,
+    valid_until: DateTime<Utc>,
+) -> Result<TelegramLinkCode, TelegramDBError> {
+    let link_code = NewTelegramLinkCode {
+        code,
+        user: user_id,
+        valid_until: Datetime::from(valid_until),
+    };
+    let r: Result<Vec<TelegramLinkCode>, Error> =
+        conn.create(LINK_CODES_TABLE_NAME).content(link_code).await;
+
+    match r {
+        Ok(link_codes) => Ok(link_codes[0].clone()),
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
+
+/// Deletes a valid link code so that it can be used only once and returns its user.
+pub async fn take_telegram_link_code<T: Connection>(
+    conn: &Surreal<T>,
+    code: String,
+) -> Result<Thing, TelegramDBError> {
+    let r: Result<Response, Error> = conn
+        .query("DELETE type::table($table) WHERE code = $code AND valid_until > time::now() RETURN BEFORE")
+        .bind(json!(
+            {
+                "table": LINK_CODES_TABLE_NAME,
+                "code": code
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut link_codes) => match link_codes.take::<Vec<TelegramLinkCode>>(0) {
+            Ok(link_codes) => match link_codes.into_iter().next() {
+                Some(link_code) => Ok(link_code.user),
+                None => Err(TelegramDBError::LinkCodeNotFound),
+            },
+            Err(err) => Err(TelegramDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_expired_telegram_link_codes<T: Connection>(
+    conn: Surreal<T>,
+) -> Result<usize, TelegramDBError> {
+    let r: Result<Response, Error> = conn
+        .query("count(DELETE type::table($table) WHERE valid_until <= time::now() RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": LINK_CODES_TABLE_NAME
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut count) => match count.take::<Option<usize>>(0) {
+            Ok(count) => Ok(count.unwrap_or_default()),
+            Err(err) => Err(TelegramDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
+
+/// Links the chat to the user, replacing a chat linked before on either side.
+pub async fn link_telegram_chat<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    chat_id: i64,
+    username: Option<String>,
+) -> Result<TelegramChat, TelegramDBError> {
+    let r: Result<Response, Error> = conn
+        .query(
+            "BEGIN TRANSACTION;
+            DELETE type::table($table) WHERE user = <record>$user_id OR chat_id = $chat_id;
+            CREATE type::table($table) SET user = <record>$user_id, chat_id = $chat_id,
+                username = IF $username THEN $username ELSE NONE END;
+            COMMIT TRANSACTION;",
+        )
+        .bind(json!(
+            {
+                "table": CHATS_TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "chat_id": chat_id,
+                "username": username
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut chats) => match chats.take::<Option<TelegramChat>>(1) {
+            Ok(Some(chat)) => Ok(chat),
+            Ok(None) => Err(TelegramDBError::ChatNotFound),
+            Err(err) => Err(TelegramDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
+
+pub async fn get_telegram_chat_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+) -> Result<TelegramChat, TelegramDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT * FROM type::table($table) WHERE user = <record>$user_id")
+        .bind(json!(
+            {
+                "table": CHATS_TABLE_NAME,
+                "user_id": user_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut chats) => match chats.take::<Option<TelegramChat>>(0) {
+            Ok(Some(chat)) => Ok(chat),
+            Ok(None) => Err(TelegramDBError::ChatNotFound),
+            Err(err) => Err(TelegramDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_telegram_chat_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+) -> Result<(), TelegramDBError> {
+    let r: Result<Response, Error> = conn
+        .query("count(DELETE type::table($table) WHERE user = <record>$user_id RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": CHATS_TABLE_NAME,
+                "user_id": user_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut count) => match count.take::<Option<usize>>(0) {
+            Ok(Some(count)) if count > 0 => Ok(()),
+            Ok(_) => Err(TelegramDBError::ChatNotFound),
+            Err(err) => Err(TelegramDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_telegram_chat_by_chat_id<T: Connection>(
+    conn: &Surreal<T>,
+    chat_id: i64,
+) -> Result<(), TelegramDBError> {
+    let r: Result<Response, Error> = conn
+        .query("count(DELETE type::table($table) WHERE chat_id = $chat_id RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": CHATS_TABLE_NAME,
+                "chat_id": chat_id
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut count) => match count.take::<Option<usize>>(0) {
+            Ok(Some(count)) if count > 0 => Ok(()),
+            Ok(_) => Err(TelegramDBError::ChatNotFound),
+            Err(err) => Err(TelegramDBError::UnknownError(err)),
+        },
+        Err(err) => Err(TelegramDBError::UnknownError(err)),
+    }
+}
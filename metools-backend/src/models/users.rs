@@ -6,7 +6,6 @@ use surrealdb::{
     sql::{Datetime, Thing},
     Connection, Error, Response, Surreal,
 };
-use utoipa::ToSchema;
 
 const TABLE_NAME: &str = "users";
 
@@ -16,7 +15,7 @@ pub enum UsersDBError {
     UnknownError(Error),
 }
 
-#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct UserReturn {
     pub id: Thing,
     pub created_at: Datetime,
@@ -26,6 +26,7 @@ pub struct NewVerifyToken {
 }
 
 #[derive(Deserialize, Clone, Debug)]
+#[allow(dead_code)]
 pub struct VerifyTokenReturn {
     pub id: Thing,
     pub created_at: Datetime,
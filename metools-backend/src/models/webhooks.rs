@@ -0,0 +1,362 @@
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use surrealdb::{
+    sql::{Datetime, Thing},
+    Connection, Error, Response, Surreal,
+};
+use utoipa::ToSchema;
+
+use crate::utils::pagination::Cursor;
+
+const TABLE_NAME: &str = "webhooks";
+const SECRETS_TABLE_NAME: &str = "webhook_secrets";
+const DELIVERIES_TABLE_NAME: &str = "webhook_deliveries";
+
+#[derive(Debug, Display)]
+pub enum WebhooksDBError {
+    WebhookNotFound,
+    SecretNotFound,
+    UnknownError(Error),
+}
+
+#[derive(Serialize)]
+struct NewWebhook {
+    user: Thing,
+    url: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Webhook {
+    pub id: Thing,
+    pub created_at: Datetime,
+    #[allow(dead_code)]
+    pub user: Thing,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Serialize)]
+pub struct NewWebhookDelivery {
+    pub webhook: Thing,
+    pub user: Thing,
+    pub event: String,
+    pub payload: Value,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WebhookDelivery {
+    pub id: Thing,
+    pub created_at: Datetime,
+    pub event: String,
+    pub payload: Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Datetime,
+    pub last_attempt_at: Option<Datetime>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Delivery due to be sent with what is needed to send it.
+#[derive(Deserialize, Clone, Debug)]
+pub struct DueWebhookDelivery {
+    pub id: Thing,
+    pub event: String,
+    pub payload: Value,
+    pub attempts: u32,
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+/// Outcome of one delivery attempt.
+pub struct WebhookDeliveryAttempt {
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+pub async fn create_webhook<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    url: String,
+) -> Result<Webhook, WebhooksDBError> {
+    let new_webhook = NewWebhook { user: user_id, url };
+    let r: Result<Vec<Webhook>, Error> = conn.create(TABLE_NAME).content(new_webhook).await;
+
+    match r {
+        Ok(webhooks) => Ok(webhooks[0].clone()),
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn list_webhooks_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+) -> Result<Vec<Webhook>, WebhooksDBError> {
+    let r: Result<Response, Error> = conn
+        .query(
+            "SELECT * FROM type::table($table) WHERE user = <record>$user_id ORDER BY created_at",
+        )
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut webhooks) => match webhooks.take::<Vec<Webhook>>(0) {
+            Ok(webhooks) => Ok(webhooks),
+            Err(err) => Err(WebhooksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn get_webhook_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    webhook_id: Thing,
+) -> Result<Webhook, WebhooksDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT * FROM type::table($table) WHERE user = <record>$user_id AND id = <record>$webhook_id")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "webhook_id": webhook_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut webhooks) => match webhooks.take::<Option<Webhook>>(0) {
+            Ok(Some(webhook)) => Ok(webhook),
+            Ok(None) => Err(WebhooksDBError::WebhookNotFound),
+            Err(err) => Err(WebhooksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_webhook_for_user<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    webhook_id: Thing,
+) -> Result<(), WebhooksDBError> {
+    let r: Result<Response, Error> = conn
+        .query("count(DELETE type::table($table) WHERE user = <record>$user_id AND id = <record>$webhook_id RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "webhook_id": webhook_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut count) => match count.take::<Option<usize>>(0) {
+            Ok(Some(count)) if count > 0 => Ok(()),
+            Ok(_) => Err(WebhooksDBError::WebhookNotFound),
+            Err(err) => Err(WebhooksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn get_webhook_secret<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+) -> Result<String, WebhooksDBError> {
+    let r: Result<Response, Error> = conn
+        .query("SELECT VALUE secret FROM type::table($table) WHERE user = <record>$user_id")
+        .bind(json!(
+            {
+                "table": SECRETS_TABLE_NAME,
+                "user_id": user_id.to_string()
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut secrets) => match secrets.take::<Option<String>>(0) {
+            Ok(Some(secret)) => Ok(secret),
+            Ok(None) => Err(WebhooksDBError::SecretNotFound),
+            Err(err) => Err(WebhooksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+/// Stores the signing secret of the user, replacing the previous one.
+pub async fn set_webhook_secret<T: Connection>(
+    conn: &Surreal<T>,
+    user_id: Thing,
+    secret: String,
+) -> Result<(), WebhooksDBError> {
+    let r = conn
+        .query(
+            "BEGIN TRANSACTION;
+            DELETE type::table($table) WHERE user = <record>$user_id;
+            CREATE type::table($table) SET user = <record>$user_id, secret = $secret;
+            COMMIT TRANSACTION;",
+        )
+        .bind(json!(
+            {
+                "table": SECRETS_TABLE_NAME,
+                "user_id": user_id.to_string(),
+                "secret": secret
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn insert_webhook_deliveries<T: Connection>(
+    conn: &Surreal<T>,
+    deliveries: Vec<NewWebhookDelivery>,
+) -> Result<(), WebhooksDBError> {
+    if deliveries.is_empty() {
+        return Ok(());
+    }
+    let r: Result<Vec<WebhookDelivery>, Error> =
+        conn.insert(DELIVERIES_TABLE_NAME).content(deliveries).await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn list_due_webhook_deliveries<T: Connection>(
+    conn: &Surreal<T>,
+    limit: usize,
+) -> Result<Vec<DueWebhookDelivery>, WebhooksDBError> {
+    let r: Result<Response, Error> = conn
+        .query(
+            "SELECT id, event, payload, attempts, next_attempt_at, webhook.url AS url,
+                (SELECT VALUE secret FROM type::table($secrets_table) WHERE user = $parent.user)[0] AS secret
+            FROM type::table($table) WHERE status = 'pending' AND next_attempt_at <= time::now()
+            ORDER BY next_attempt_at LIMIT $limit",
+        )
+        .bind(json!(
+            {
+                "table": DELIVERIES_TABLE_NAME,
+                "secrets_table": SECRETS_TABLE_NAME,
+                "limit": limit
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut deliveries) => match deliveries.take::<Vec<DueWebhookDelivery>>(0) {
+            Ok(deliveries) => Ok(deliveries),
+            Err(err) => Err(WebhooksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn set_webhook_delivery_attempt<T: Connection>(
+    conn: &Surreal<T>,
+    delivery_id: Thing,
+    attempt: WebhookDeliveryAttempt,
+) -> Result<(), WebhooksDBError> {
+    let r = conn
+        .query(
+            "UPDATE type::table($table) SET status = $status, attempts = $attempts, last_attempt_at = time::now(),
+            next_attempt_at = IF $next_attempt_at THEN <datetime>$next_attempt_at ELSE next_attempt_at END,
+            response_status = IF $response_status THEN $response_status ELSE NONE END,
+            error = IF $error THEN $error ELSE NONE END
+            WHERE id = <record>$delivery_id",
+        )
+        .bind(json!(
+            {
+                "table": DELIVERIES_TABLE_NAME,
+                "delivery_id": delivery_id.to_string(),
+                "status": attempt.status,
+                "attempts": attempt.attempts,
+                "next_attempt_at": attempt.next_attempt_at,
+                "response_status": attempt.response_status,
+                "error": attempt.error
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(_) => Ok(()),
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+/// Lists deliveries of a webhook newest first, starting after `cursor` when it is set.
+pub async fn list_webhook_deliveries<T: Connection>(
+    conn: &Surreal<T>,
+    webhook_id: Thing,
+    cursor: Option<Cursor>,
+    limit: usize,
+) -> Result<Vec<WebhookDelivery>, WebhooksDBError> {
+    let query = match cursor {
+        Some(_) => "SELECT * FROM type::table($table) WHERE webhook = <record>$webhook_id AND (created_at < <datetime>$cursor.created_at OR (created_at = <datetime>$cursor.created_at AND id < <record>$cursor.id)) ORDER BY created_at DESC, id DESC LIMIT $limit",
+        None => "SELECT * FROM type::table($table) WHERE webhook = <record>$webhook_id ORDER BY created_at DESC, id DESC LIMIT $limit",
+    };
+    let r: Result<Response, Error> = conn
+        .query(query)
+        .bind(json!(
+            {
+                "table": DELIVERIES_TABLE_NAME,
+                "webhook_id": webhook_id.to_string(),
+                "cursor": cursor,
+                "limit": limit
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut deliveries) => match deliveries.take::<Vec<WebhookDelivery>>(0) {
+            Ok(deliveries) => Ok(deliveries),
+            Err(err) => Err(WebhooksDBError::UnknownError(err)),
+        },
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
+
+pub async fn delete_old_webhook_deliveries<T: Connection>(
+    conn: Surreal<T>,
+    retention_days: u32,
+) -> Result<usize, WebhooksDBError> {
+    let r = conn
+        .query("count(DELETE type::table($table) WHERE created_at < time::now() - <duration>$retention AND status != 'pending' RETURN BEFORE)")
+        .bind(json!(
+            {
+                "table": DELIVERIES_TABLE_NAME,
+                "retention": format!("{retention_days}d")
+            }
+        ))
+        .await;
+
+    match r {
+        Ok(mut r) => {
+            let surreal_response = r.take::<Vec<usize>>(0).unwrap()[0];
+            Ok(surreal_response)
+        }
+        Err(err) => Err(WebhooksDBError::UnknownError(err)),
+    }
+}
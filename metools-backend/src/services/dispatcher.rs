@@ -0,0 +1,483 @@
+use chrono::{TimeDelta, Utc};
+use derive_more::Display;
+use lettre::message::header::ContentType;
+use prometheus::{IntCounterVec, IntGauge, Opts, Registry};
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
+
+use crate::{
+    config::DBConfig,
+    models::{
+        outbox::{
+            count_pending_outbox_messages, insert_outbox_message, list_due_outbox_messages,
+            set_outbox_attempt, NewOutboxMessage, OutboxAttempt, OutboxChannel, OutboxDBError,
+            OutboxMessage, OutboxStatus,
+        },
+        telegram::{get_telegram_chat_for_user, TelegramDBError},
+    },
+    services::{
+        mailer::{Mail, MailerService},
+        telegram::bot::TelegramService,
+    },
+};
+
+const DUE_MESSAGES_BATCH: usize = 100;
+
+#[derive(Debug, Display)]
+pub enum DispatcherError {
+    OutboxDBError(OutboxDBError),
+    TelegramDBError(TelegramDBError),
+}
+
+/// Why a message was not sent.
+enum SendError {
+    /// Worth another attempt later.
+    Failed(String),
+    /// Nobody to send to anymore, e.g. the user unlinked their telegram chat.
+    NoRecipient(String),
+}
+
+/// Outbox counters by channel, exported on `/metrics`.
+#[derive(Clone)]
+pub struct DispatcherMetrics {
+    queued: IntCounterVec,
+    sent: IntCounterVec,
+    failed_attempts: IntCounterVec,
+    dead: IntCounterVec,
+    pending: IntGauge,
+}
+
+impl DispatcherMetrics {
+    pub fn init(registry: &Registry) -> Self {
+        let counter = |name: &str, help: &str| {
+            let counter = IntCounterVec::new(Opts::new(name, help), &["channel"]).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+        let pending = IntGauge::new(
+            "notifications_outbox_pending",
+            "Messages waiting in the outbox",
+        )
+        .unwrap();
+        registry.register(Box::new(pending.clone())).unwrap();
+
+        Self {
+            queued: counter(
+                "notifications_outbox_queued_total",
+                "Messages put in the outbox",
+            ),
+            sent: counter("notifications_outbox_sent_total", "Messages sent"),
+            failed_attempts: counter(
+                "notifications_outbox_failed_attempts_total",
+                "Sending attempts that failed",
+            ),
+            dead: counter(
+                "notifications_outbox_dead_total",
+                "Messages given up after the last attempt",
+            ),
+            pending,
+        }
+    }
+}
+
+/// Sends messages from the outbox, failed ones are retried with exponential backoff.
+#[derive(Clone)]
+pub struct NotificationsDispatcher {
+    db: DBConfig,
+    mailer: MailerService,
+    telegram: Option<TelegramService>,
+    max_attempts: u32,
+    retry_delay: u64,
+    metrics: DispatcherMetrics,
+}
+
+impl NotificationsDispatcher {
+    pub fn init(
+        db: DBConfig,
+        mailer: MailerService,
+        telegram: Option<TelegramService>,
+        max_attempts: u32,
+        retry_delay: u64,
+        metrics: DispatcherMetrics,
+    ) -> Self {
+        Self {
+            db,
+            mailer,
+            telegram,
+            max_attempts,
+            retry_delay,
+            metrics,
+        }
+    }
+
+    async fn enqueue(
+        &self,
+        conn: &Surreal<Any>,
+        message: NewOutboxMessage,
+    ) -> Result<(), DispatcherError> {
+        let channel = message.channel.to_string();
+
+        match insert_outbox_message(conn, message).await {
+            Ok(()) => {
+                self.metrics.queued.with_label_values(&[&channel]).inc();
+                Ok(())
+            }
+            Err(err) => Err(DispatcherError::OutboxDBError(err)),
+        }
+    }
+
+    pub async fn enqueue_email(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Option<Thing>,
+        to_mail: String,
+        mail: Mail,
+    ) -> Result<(), DispatcherError> {
+        self.enqueue(
+            conn,
+            NewOutboxMessage {
+                user: user_id,
+                channel: OutboxChannel::Email,
+                recipient: Some(to_mail),
+                subject: Some(mail.subject),
+                body: mail.body,
+            },
+        )
+        .await
+    }
+
+    /// Queues a Telegram message, returns false when Telegram is off or the user has no chat.
+    pub async fn enqueue_telegram(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+        text: String,
+    ) -> Result<bool, DispatcherError> {
+        if self.telegram.is_none() {
+            return Ok(false);
+        }
+        match get_telegram_chat_for_user(conn, user_id.clone()).await {
+            Ok(_) => {}
+            Err(TelegramDBError::ChatNotFound) => return Ok(false),
+            Err(err) => return Err(DispatcherError::TelegramDBError(err)),
+        }
+
+        self.enqueue(
+            conn,
+            NewOutboxMessage {
+                user: Some(user_id),
+                channel: OutboxChannel::Telegram,
+                recipient: None,
+                subject: None,
+                body: text,
+            },
+        )
+        .await
+        .map(|_| true)
+    }
+
+    async fn send(&self, conn: &Surreal<Any>, message: &OutboxMessage) -> Result<(), SendError> {
+        match message.channel {
+            OutboxChannel::Email => {
+                let recipient = match &message.recipient {
+                    Some(recipient) => recipient.clone(),
+                    None => {
+                        return Err(SendError::NoRecipient(String::from(
+                            "Email without recipient",
+                        )))
+                    }
+                };
+                // SMTP sends block, so they are moved off the async workers
+                let mailer = self.mailer.clone();
+                let subject = message.subject.clone().unwrap_or_default();
+                let body = message.body.clone();
+                let r = tokio::task::spawn_blocking(move || {
+                    mailer.send(recipient, subject, ContentType::TEXT_HTML, body)
+                })
+                .await;
+
+                match r {
+                    Ok(Ok(_)) => Ok(()),
+                    Ok(Err(err)) => Err(SendError::Failed(err.to_string())),
+                    Err(err) => Err(SendError::Failed(err.to_string())),
+                }
+            }
+            OutboxChannel::Telegram => {
+                let (telegram, user_id) = match (&self.telegram, &message.user) {
+                    (Some(telegram), Some(user_id)) => (telegram, user_id.clone()),
+                    (None, _) => {
+                        return Err(SendError::Failed(String::from(
+                            "Telegram is not configured",
+                        )))
+                    }
+                    (_, None) => {
+                        return Err(SendError::NoRecipient(String::from(
+                            "Telegram message without user",
+                        )))
+                    }
+                };
+
+                match telegram
+                    .send_to_user(conn, user_id, message.body.as_str())
+                    .await
+                {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(SendError::NoRecipient(String::from(
+                        "Telegram chat is not linked",
+                    ))),
+                    Err(err) => Err(SendError::Failed(err.to_string())),
+                }
+            }
+        }
+    }
+
+    /// Sends messages that are due, returns how many were attempted.
+    pub async fn dispatch_due(&self) -> Result<usize, DispatcherError> {
+        let conn = self.db.get_connection().await;
+        let messages = match list_due_outbox_messages(&conn, DUE_MESSAGES_BATCH).await {
+            Ok(messages) => messages,
+            Err(err) => return Err(DispatcherError::OutboxDBError(err)),
+        };
+
+        for message in messages.iter() {
+            let channel = message.channel.to_string();
+            let attempts = message.attempts + 1;
+            let attempt = match self.send(&conn, message).await {
+                Ok(()) => {
+                    self.metrics.sent.with_label_values(&[&channel]).inc();
+                    OutboxAttempt {
+                        status: OutboxStatus::Sent,
+                        attempts,
+                        next_attempt_at: None,
+                        error: None,
+                    }
+                }
+                // Retries cannot bring the recipient back
+                Err(SendError::NoRecipient(error)) => {
+                    log::info!("Dropping outbox message {}: {error}", message.id);
+                    OutboxAttempt {
+                        status: OutboxStatus::Dead,
+                        attempts,
+                        next_attempt_at: None,
+                        error: Some(error),
+                    }
+                }
+                Err(SendError::Failed(error)) if attempts >= self.max_attempts => {
+                    log::error!(
+                        "Giving up on outbox message {} after {attempts} attempts: {error}",
+                        message.id
+                    );
+                    self.metrics
+                        .failed_attempts
+                        .with_label_values(&[&channel])
+                        .inc();
+                    self.metrics.dead.with_label_values(&[&channel]).inc();
+                    OutboxAttempt {
+                        status: OutboxStatus::Dead,
+                        attempts,
+                        next_attempt_at: None,
+                        error: Some(error),
+                    }
+                }
+                Err(SendError::Failed(error)) => {
+                    log::warn!("Error on sending outbox message {}: {error}", message.id);
+                    self.metrics
+                        .failed_attempts
+                        .with_label_values(&[&channel])
+                        .inc();
+                    OutboxAttempt {
+                        status: OutboxStatus::Pending,
+                        attempts,
+                        next_attempt_at: Some(
+                            Utc::now()
+                                + TimeDelta::seconds(
+                                    (self.retry_delay << (attempts - 1).min(16)) as i64,
+                                ),
+                        ),
+                        error: Some(error),
+                    }
+                }
+            };
+            if let Err(err) = set_outbox_attempt(&conn, message.id.clone(), attempt).await {
+                log::error!("Error on saving outbox message {}: {err}", message.id);
+            }
+        }
+
+        match count_pending_outbox_messages(&conn).await {
+            Ok(pending) => self.metrics.pending.set(pending as i64),
+            Err(err) => log::error!("Error on counting pending outbox messages: {err}"),
+        }
+        Ok(messages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use lettre::SmtpTransport;
+    use serde::Deserialize;
+    use surrealdb::sql::Datetime;
+
+    use super::*;
+    use crate::{
+        models::telegram::link_telegram_chat,
+        services::telegram::client::HttpTelegramClient,
+        utils::{
+            test_db::test_db_config,
+            test_server::{ScriptedResponse, TestServer},
+        },
+    };
+
+    #[derive(Deserialize)]
+    struct StoredMessage {
+        status: OutboxStatus,
+        attempts: u32,
+        next_attempt_at: Datetime,
+        error: Option<String>,
+    }
+
+    fn user() -> Thing {
+        Thing::from(("users", "user"))
+    }
+
+    /// Dispatcher whose bot talks to `telegram_url`.
+    async fn dispatcher(telegram_url: &str) -> NotificationsDispatcher {
+        let db = test_db_config().await;
+        let mailer = MailerService::init(
+            SmtpTransport::unencrypted_localhost(),
+            String::from("metools@example.com"),
+            String::from("http://localhost"),
+        );
+        let client = HttpTelegramClient::init(String::from(telegram_url), String::from("token"));
+        let telegram = TelegramService::init(db.clone(), Arc::new(client), String::from("bot"));
+        NotificationsDispatcher::init(
+            db,
+            mailer,
+            Some(telegram),
+            3,
+            60,
+            DispatcherMetrics::init(&Registry::new()),
+        )
+    }
+
+    async fn stored_messages(conn: &Surreal<Any>) -> Vec<StoredMessage> {
+        conn.query("SELECT status, attempts, next_attempt_at, error FROM notifications_outbox")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap()
+    }
+
+    async fn queue_telegram(dispatcher: &NotificationsDispatcher) {
+        let conn = dispatcher.db.get_connection().await;
+        let message = NewOutboxMessage {
+            user: Some(user()),
+            channel: OutboxChannel::Telegram,
+            recipient: None,
+            subject: None,
+            body: String::from("Seats found"),
+        };
+        insert_outbox_message(&conn, message).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn dispatch_due_sends_telegram_messages() {
+        let server = TestServer::start(vec![ScriptedResponse::json(
+            r#"{"ok": true, "result": {}}"#,
+        )])
+        .await;
+        let dispatcher = dispatcher(server.url.as_str()).await;
+        let conn = dispatcher.db.get_connection().await;
+        link_telegram_chat(&conn, user(), 42, None).await.unwrap();
+        queue_telegram(&dispatcher).await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+
+        let messages = stored_messages(&conn).await;
+        assert_eq!(messages[0].status, OutboxStatus::Sent);
+        assert_eq!(messages[0].attempts, 1);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].target, "/bottoken/sendMessage");
+        let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["chat_id"], 42);
+        assert_eq!(body["text"], "Seats found");
+        let metrics = &dispatcher.metrics;
+        assert_eq!(metrics.sent.with_label_values(&["telegram"]).get(), 1);
+        assert_eq!(metrics.pending.get(), 0);
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_due_backs_off_then_gives_up() {
+        let failure =
+            || ScriptedResponse::json(r#"{"ok": false, "description": "Too Many Requests"}"#);
+        let server = TestServer::start(vec![failure(), failure(), failure()]).await;
+        let dispatcher = dispatcher(server.url.as_str()).await;
+        let conn = dispatcher.db.get_connection().await;
+        link_telegram_chat(&conn, user(), 42, None).await.unwrap();
+        queue_telegram(&dispatcher).await;
+
+        for (attempts, delay) in [(1, 60), (2, 120)] {
+            let sent_at = Utc::now();
+            assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+            let message = &stored_messages(&conn).await[0];
+            assert_eq!(message.status, OutboxStatus::Pending);
+            assert_eq!(message.attempts, attempts);
+            let wait = message.next_attempt_at.to_utc() - sent_at;
+            assert!(wait >= TimeDelta::seconds(delay), "{wait}");
+            assert!(wait < TimeDelta::seconds(delay + 5), "{wait}");
+
+            assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+            conn.query("UPDATE notifications_outbox SET next_attempt_at = time::now()")
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        let message = &stored_messages(&conn).await[0];
+        assert_eq!(message.status, OutboxStatus::Dead);
+        assert_eq!(message.attempts, 3);
+        assert!(message.error.is_some());
+        let metrics = &dispatcher.metrics;
+        assert_eq!(
+            metrics
+                .failed_attempts
+                .with_label_values(&["telegram"])
+                .get(),
+            3
+        );
+        assert_eq!(metrics.dead.with_label_values(&["telegram"]).get(), 1);
+        assert_eq!(metrics.pending.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_due_drops_messages_to_unlinked_chats() {
+        // Nothing listens there, the bot is not reached without a chat
+        let dispatcher = dispatcher("http://127.0.0.1:9").await;
+        let conn = dispatcher.db.get_connection().await;
+        queue_telegram(&dispatcher).await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+
+        let messages = stored_messages(&conn).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].status, OutboxStatus::Dead);
+        assert_eq!(messages[0].attempts, 1);
+        assert_eq!(
+            messages[0].error.as_deref(),
+            Some("Telegram chat is not linked")
+        );
+        let metrics = &dispatcher.metrics;
+        assert_eq!(metrics.dead.with_label_values(&["telegram"]).get(), 0);
+        assert_eq!(
+            metrics
+                .failed_attempts
+                .with_label_values(&["telegram"])
+                .get(),
+            0
+        );
+        assert_eq!(metrics.pending.get(), 0);
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+}
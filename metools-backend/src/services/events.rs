@@ -0,0 +1,203 @@
+use std::{convert::Infallible, time::Duration};
+
+use actix_web::web::Bytes;
+use serde::Serialize;
+use serde_json::json;
+use surrealdb::sql::Thing;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, IntervalStream},
+    Stream, StreamExt,
+};
+use utoipa::ToSchema;
+
+use crate::{
+    models::rzd::tasks::{AvailableTrain, ClassPrice, TaskCheck, TaskStatus},
+    utils::string::encode_thing_to_base64_string,
+};
+
+/// Events kept for slow streams before they lag.
+const CHANNEL_CAPACITY: usize = 1024;
+/// Seconds between comments that keep idle streams open behind proxies.
+const KEEP_ALIVE_INTERVAL: u64 = 15;
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TaskEventKind {
+    Checked {
+        check: TaskCheck,
+    },
+    SeatsFound {
+        trains: Vec<AvailableTrain>,
+    },
+    /// Minimal prices of car classes by travel date and leg after they changed since the previous check.
+    PricesChanged {
+        prices: Vec<ClassPrice>,
+    },
+    StatusChanged {
+        status: TaskStatus,
+    },
+}
+
+impl TaskEventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Checked { .. } => "checked",
+            Self::SeatsFound { .. } => "seats_found",
+            Self::PricesChanged { .. } => "prices_changed",
+            Self::StatusChanged { .. } => "status_changed",
+        }
+    }
+}
+
+/// Change of a task pushed to the streams of its owner.
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct TaskEvent {
+    /// Base64 encoded task id.
+    pub task_id: String,
+    #[serde(flatten)]
+    pub kind: TaskEventKind,
+}
+
+impl TaskEvent {
+    fn to_sse(&self) -> Bytes {
+        Bytes::from(format!(
+            "event: {}\ndata: {}\n\n",
+            self.kind.name(),
+            serde_json::to_string(self).unwrap_or_default()
+        ))
+    }
+}
+
+/// Fans task events out to the open streams, events of users without streams are dropped.
+#[derive(Clone)]
+pub struct TaskEventsService {
+    sender: broadcast::Sender<(Thing, TaskEvent)>,
+}
+
+impl TaskEventsService {
+    pub fn init() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, user_id: Thing, task_id: Thing, kind: TaskEventKind) {
+        let event = TaskEvent {
+            task_id: encode_thing_to_base64_string(task_id),
+            kind,
+        };
+        // Fails only when nobody listens
+        let _ = self.sender.send((user_id, event));
+    }
+
+    /// Events of all users, receivers filter them by user themselves.
+    pub fn receiver(&self) -> broadcast::Receiver<(Thing, TaskEvent)> {
+        self.sender.subscribe()
+    }
+
+    /// Server-Sent Events of the user's tasks.
+    pub fn subscribe(&self, user_id: Thing) -> impl Stream<Item = Result<Bytes, Infallible>> {
+        let events = BroadcastStream::new(self.sender.subscribe()).filter_map(move |r| match r {
+            Ok((event_user_id, event)) if event_user_id == user_id => Some(event.to_sse()),
+            Ok(_) => None,
+            // The client missed events and should reload its tasks
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => Some(Bytes::from(format!(
+                "event: lagged\ndata: {}\n\n",
+                json!({"skipped": skipped})
+            ))),
+        });
+        let keep_alive = IntervalStream::new(tokio::time::interval(Duration::from_secs(
+            KEEP_ALIVE_INTERVAL,
+        )))
+        .map(|_| Bytes::from_static(b": keep-alive\n\n"));
+
+        events.merge(keep_alive).map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    /// Next event of the stream as text, keep-alive comments are skipped.
+    async fn next_event(
+        stream: &mut (impl Stream<Item = Result<Bytes, Infallible>> + Unpin),
+    ) -> String {
+        loop {
+            let item = tokio::time::timeout(Duration::from_secs(1), stream.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let text = String::from_utf8(item.to_vec()).unwrap();
+            if !text.starts_with(':') {
+                return text;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribe_streams_only_the_users_events() {
+        let events = TaskEventsService::init();
+        let user = Thing::from(("users", "user"));
+        let task = Thing::from(("rzd_tasks", "task"));
+        let mut stream = Box::pin(events.subscribe(user.clone()));
+
+        events.publish(
+            Thing::from(("users", "other")),
+            Thing::from(("rzd_tasks", "other")),
+            TaskEventKind::StatusChanged {
+                status: TaskStatus::Paused,
+            },
+        );
+        events.publish(
+            user,
+            task.clone(),
+            TaskEventKind::StatusChanged {
+                status: TaskStatus::Expired,
+            },
+        );
+
+        let event = next_event(&mut stream).await;
+        let (name, data) = event.split_once('\n').unwrap();
+        assert_eq!(name, "event: status_changed");
+        let data: serde_json::Value =
+            serde_json::from_str(data.strip_prefix("data: ").unwrap().trim_end()).unwrap();
+        assert_eq!(
+            data,
+            json!({
+                "task_id": encode_thing_to_base64_string(task),
+                "event": "status_changed",
+                "status": "expired"
+            })
+        );
+        assert!(event.ends_with("\n\n"));
+    }
+
+    #[tokio::test]
+    async fn subscribe_reports_lagging_streams() {
+        let events = TaskEventsService::init();
+        let user = Thing::from(("users", "user"));
+        let mut stream = Box::pin(events.subscribe(user.clone()));
+
+        for _ in 0..CHANNEL_CAPACITY + 5 {
+            events.publish(
+                user.clone(),
+                Thing::from(("rzd_tasks", "task")),
+                TaskEventKind::StatusChanged {
+                    status: TaskStatus::Active,
+                },
+            );
+        }
+
+        assert_eq!(
+            next_event(&mut stream).await,
+            "event: lagged\ndata: {\"skipped\":5}\n\n"
+        );
+        assert!(next_event(&mut stream)
+            .await
+            .starts_with("event: status_changed\n"));
+    }
+}
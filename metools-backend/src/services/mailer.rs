@@ -5,6 +5,80 @@ use lettre::{
 };
 use uuid::Uuid;
 
+use crate::{
+    models::{
+        notifications::NotificationContent,
+        rzd::tasks::{AvailableTrain, PriceDrop, TripLeg},
+    },
+    utils::string::escape_html,
+};
+
+fn task_availability_body(trains: &[AvailableTrain]) -> String {
+    let mut body = String::from("<p>Seats are available for your task:</p>");
+    let mut current_leg: Option<TripLeg> = None;
+    for train in trains {
+        if train.leg != current_leg {
+            match train.leg {
+                Some(TripLeg::Outbound) => body.push_str("<h2>Outbound</h2>"),
+                Some(TripLeg::Return) => body.push_str("<h2>Return</h2>"),
+                None => {}
+            }
+            current_leg = train.leg.clone();
+        }
+        body.push_str(
+            format!(
+                "<h3>Train {}, departure {} {}, arrival {} {}</h3>",
+                escape_html(&train.number),
+                escape_html(&train.departure_date),
+                escape_html(&train.departure_time),
+                escape_html(&train.arrival_date),
+                escape_html(&train.arrival_time)
+            )
+            .as_str(),
+        );
+        body.push_str("<table><tr><th>Car class</th><th>Free seats</th><th>Price from</th></tr>");
+        for car_class in train.car_classes.iter() {
+            body.push_str(
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{} RUB</td></tr>",
+                    escape_html(&car_class.car_type),
+                    car_class.free_seats,
+                    car_class.min_price
+                )
+                .as_str(),
+            );
+        }
+        body.push_str("</table>");
+    }
+    body
+}
+
+fn price_drops_body(drops: &[PriceDrop]) -> String {
+    let mut body = String::from(
+        "<p>Prices dropped for your task:</p><table><tr><th>Date</th><th>Car class</th><th>Price from</th><th>Was</th></tr>",
+    );
+    for drop in drops {
+        body.push_str(
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{} RUB</td><td>{} RUB</td></tr>",
+                drop.key.date_label(),
+                escape_html(&drop.key.car_type),
+                drop.price,
+                drop.baseline_price
+            )
+            .as_str(),
+        );
+    }
+    body.push_str("</table>");
+    body
+}
+
+/// HTML mail ready to be queued for sending.
+pub struct Mail {
+    pub subject: String,
+    pub body: String,
+}
+
 #[derive(Clone)]
 pub struct MailerService {
     smtp_transport: SmtpTransport,
@@ -39,19 +113,43 @@ impl MailerService {
         self.smtp_transport.send(&email)
     }
 
-    pub fn send_verification_mail(
-        &self,
-        to_mail: String,
-        verify_key: Uuid,
-    ) -> Result<Response, Error> {
-        self.send(
-            to_mail,
-            String::from("Verification"),
-            ContentType::TEXT_HTML,
-            format!(
+    pub fn verification_mail(&self, verify_key: Uuid) -> Mail {
+        Mail {
+            subject: String::from("Verification"),
+            body: format!(
                 "Your verification link: {}/api/v1/users/verify?verify_key={}&redirect={}",
                 self.service_url, verify_key, self.service_url
             ),
-        )
+        }
+    }
+}
+
+pub fn task_availability_mail(trains: &[AvailableTrain]) -> Mail {
+    Mail {
+        subject: String::from("Seats are available"),
+        body: task_availability_body(trains),
+    }
+}
+
+pub fn price_alert_mail(drops: &[PriceDrop]) -> Mail {
+    Mail {
+        subject: String::from("Prices dropped"),
+        body: price_drops_body(drops),
+    }
+}
+
+/// Puts queued notifications in one mail.
+pub fn digest_mail(contents: &[NotificationContent]) -> Mail {
+    let body: Vec<String> = contents
+        .iter()
+        .map(|content| match content {
+            NotificationContent::Availability { trains } => task_availability_body(trains),
+            NotificationContent::PriceDrops { drops } => price_drops_body(drops),
+        })
+        .collect();
+
+    Mail {
+        subject: String::from("Updates on your tasks"),
+        body: body.join("<hr>"),
     }
 }
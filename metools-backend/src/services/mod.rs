@@ -1,3 +1,10 @@
+pub(crate) mod dispatcher;
+pub(crate) mod events;
 pub(crate) mod mailer;
+pub(crate) mod notifications;
+pub(crate) mod rzd;
+pub(crate) mod stations;
 pub(crate) mod tasks;
+pub(crate) mod telegram;
 pub(crate) mod users;
+pub(crate) mod webhooks;
@@ -0,0 +1,700 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use derive_more::Display;
+use serde_json::json;
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
+
+use crate::{
+    config::DBConfig,
+    models::{
+        notifications::{
+            delete_digest_items, get_notification_settings_for_user, insert_digest_item,
+            list_due_digest_items, set_digest_items_channels, set_notification_settings_for_user,
+            DeliveryMode, DigestItem, NewDigestItem, NotificationChannels, NotificationContent,
+            NotificationSettings, NotificationsDBError,
+        },
+        rzd::tasks::{AvailableTrain, PriceDrop, Task, TaskCheckStatus},
+        users::get_user_by_id,
+    },
+    services::{
+        dispatcher::NotificationsDispatcher,
+        mailer::{digest_mail, price_alert_mail, task_availability_mail},
+        telegram::bot::{format_digest, format_price_drops, format_task_availability},
+        webhooks::WebhooksService,
+    },
+    utils::string::encode_thing_to_base64_string,
+};
+
+#[derive(Debug, Display)]
+pub enum NotificationsServiceError {
+    NotificationsDBError(NotificationsDBError),
+}
+
+/// How a notification leaves according to the user's settings and the task's overrides.
+enum Dispatch {
+    Skip,
+    Now(NotificationChannels),
+    Later(NotificationChannels, DateTime<Utc>),
+}
+
+/// What became of a notification handed to one channel.
+#[derive(PartialEq)]
+enum ChannelOutcome {
+    Queued,
+    /// The channel has nowhere to deliver to, e.g. no linked telegram chat.
+    NoRecipient,
+    Failed,
+}
+
+/// Start of the next digest window, windows are aligned so that items of a user go together.
+fn next_digest_at(now: DateTime<Utc>, digest_interval: u64) -> DateTime<Utc> {
+    let interval = digest_interval.max(1) as i64;
+    let next = (now.timestamp() / interval + 1) * interval;
+    DateTime::from_timestamp(next, 0).unwrap_or(now)
+}
+
+/// First moment after `at` when the quiet hours end in the given time zone.
+fn quiet_hours_end_after(at: DateTime<Utc>, end: NaiveTime, tz: Tz) -> DateTime<Utc> {
+    let local = at.with_timezone(&tz);
+    let mut date = local.date_naive();
+    if local.time() >= end {
+        date = date.succ_opt().unwrap_or(date);
+    }
+    match tz.from_local_datetime(&date.and_time(end)).earliest() {
+        Some(end_at) => end_at.with_timezone(&Utc),
+        // The end falls into a DST gap
+        None => at + TimeDelta::hours(1),
+    }
+}
+
+fn plan_dispatch(
+    settings: &NotificationSettings,
+    task: &Task,
+    now: DateTime<Utc>,
+    digest_interval: u64,
+) -> Dispatch {
+    let overrides = task.notifications.clone().unwrap_or_default();
+    let channels = overrides.channels.unwrap_or(settings.channels.clone());
+    if !channels.email && !channels.telegram {
+        return Dispatch::Skip;
+    }
+
+    let mut send_at = match overrides.delivery.unwrap_or(settings.delivery.clone()) {
+        DeliveryMode::Instant => now,
+        DeliveryMode::Digest => next_digest_at(now, digest_interval),
+    };
+    if let (Some(quiet_hours), true) =
+        (&settings.quiet_hours, overrides.quiet_hours.unwrap_or(true))
+    {
+        let tz = settings.tz();
+        if quiet_hours.contains(send_at.with_timezone(&tz).time()) {
+            send_at = quiet_hours_end_after(send_at, quiet_hours.end, tz);
+        }
+    }
+
+    if send_at <= now {
+        Dispatch::Now(channels)
+    } else {
+        Dispatch::Later(channels, send_at)
+    }
+}
+
+#[derive(Clone)]
+pub struct NotificationsService {
+    db: DBConfig,
+    dispatcher: NotificationsDispatcher,
+    webhooks: WebhooksService,
+    /// Seconds between digests.
+    digest_interval: u64,
+}
+
+impl NotificationsService {
+    pub fn init(
+        db: DBConfig,
+        dispatcher: NotificationsDispatcher,
+        webhooks: WebhooksService,
+        digest_interval: u64,
+    ) -> Self {
+        Self {
+            db,
+            dispatcher,
+            webhooks,
+            digest_interval,
+        }
+    }
+
+    pub async fn get_settings_for_user(
+        &self,
+        user_id: Thing,
+    ) -> Result<NotificationSettings, NotificationsServiceError> {
+        let r = get_notification_settings_for_user(&self.db.get_connection().await, user_id).await;
+
+        match r {
+            Ok(settings) => Ok(settings.unwrap_or_default()),
+            Err(err) => Err(NotificationsServiceError::NotificationsDBError(err)),
+        }
+    }
+
+    pub async fn update_settings_for_user(
+        &self,
+        user_id: Thing,
+        settings: NotificationSettings,
+    ) -> Result<NotificationSettings, NotificationsServiceError> {
+        let r = set_notification_settings_for_user(
+            &self.db.get_connection().await,
+            user_id,
+            settings.clone(),
+        )
+        .await;
+
+        match r {
+            Ok(()) => Ok(settings),
+            Err(err) => Err(NotificationsServiceError::NotificationsDBError(err)),
+        }
+    }
+
+    async fn load_settings(&self, conn: &Surreal<Any>, task: &Task) -> NotificationSettings {
+        match get_notification_settings_for_user(conn, task.user.clone()).await {
+            Ok(settings) => settings.unwrap_or_default(),
+            Err(err) => {
+                log::error!(
+                    "Error on getting notification settings of task {}: {err}",
+                    task.id
+                );
+                NotificationSettings::default()
+            }
+        }
+    }
+
+    async fn queue_email(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+        contents: &[NotificationContent],
+    ) -> ChannelOutcome {
+        let user = match get_user_by_id(conn.clone(), user_id.clone()).await {
+            Ok(user) => user,
+            Err(err) => {
+                log::error!("Error on getting user {user_id}: {err}");
+                return ChannelOutcome::Failed;
+            }
+        };
+        let mail = match contents {
+            [NotificationContent::Availability { trains }] => task_availability_mail(trains),
+            [NotificationContent::PriceDrops { drops }] => price_alert_mail(drops),
+            _ => digest_mail(contents),
+        };
+        let r = self
+            .dispatcher
+            .enqueue_email(conn, Some(user_id.clone()), user.email, mail)
+            .await;
+        match r {
+            Ok(()) => ChannelOutcome::Queued,
+            Err(err) => {
+                log::error!("Error on queueing email to {user_id}: {err}");
+                ChannelOutcome::Failed
+            }
+        }
+    }
+
+    async fn queue_telegram(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+        contents: &[NotificationContent],
+    ) -> ChannelOutcome {
+        let text = match contents {
+            [NotificationContent::Availability { trains }] => format_task_availability(trains),
+            [NotificationContent::PriceDrops { drops }] => format_price_drops(drops),
+            _ => format_digest(contents),
+        };
+        let r = self
+            .dispatcher
+            .enqueue_telegram(conn, user_id.clone(), text)
+            .await;
+        match r {
+            Ok(true) => ChannelOutcome::Queued,
+            Ok(false) => ChannelOutcome::NoRecipient,
+            Err(err) => {
+                log::error!("Error on queueing telegram message to {user_id}: {err}");
+                ChannelOutcome::Failed
+            }
+        }
+    }
+
+    /// Puts the contents in the outbox of each channel, returns the channels that failed to take them.
+    ///
+    /// A channel without a recipient, e.g. no linked telegram chat, counts as done.
+    async fn send_now(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+        channels: &NotificationChannels,
+        contents: &[NotificationContent],
+    ) -> NotificationChannels {
+        let mut failed = NotificationChannels {
+            email: false,
+            telegram: false,
+            webhook: false,
+        };
+        if channels.email {
+            failed.email =
+                self.queue_email(conn, user_id.clone(), contents).await == ChannelOutcome::Failed;
+        }
+        if channels.telegram {
+            failed.telegram = self.queue_telegram(conn, user_id.clone(), contents).await
+                == ChannelOutcome::Failed;
+        }
+        failed
+    }
+
+    /// Queues the notification until the next digest run at or after `send_after`.
+    async fn send_later(
+        &self,
+        conn: &Surreal<Any>,
+        task: &Task,
+        channels: NotificationChannels,
+        content: NotificationContent,
+        send_after: DateTime<Utc>,
+    ) -> bool {
+        let item = NewDigestItem {
+            user: task.user.clone(),
+            task: task.id.clone(),
+            channels,
+            content,
+            send_after: send_after.into(),
+        };
+        match insert_digest_item(conn, item).await {
+            Ok(()) => true,
+            Err(err) => {
+                log::error!("Error on queueing notification of task {}: {err}", task.id);
+                false
+            }
+        }
+    }
+
+    /// Sends or queues a notification about the task, returns false when it should be retried.
+    async fn notify(&self, conn: &Surreal<Any>, task: &Task, content: NotificationContent) -> bool {
+        let settings = self.load_settings(conn, task).await;
+
+        match plan_dispatch(&settings, task, Utc::now(), self.digest_interval) {
+            Dispatch::Skip => true,
+            Dispatch::Now(channels) => {
+                let failed = self
+                    .send_now(
+                        conn,
+                        task.user.clone(),
+                        &channels,
+                        std::slice::from_ref(&content),
+                    )
+                    .await;
+                if !failed.email && !failed.telegram {
+                    return true;
+                }
+                // Only the failed channels are retried, with the next digest run
+                self.send_later(conn, task, failed, content, Utc::now())
+                    .await
+            }
+            Dispatch::Later(channels, send_after) => {
+                self.send_later(conn, task, channels, content, send_after)
+                    .await
+            }
+        }
+    }
+
+    pub async fn notify_task_availability(
+        &self,
+        conn: &Surreal<Any>,
+        task: &Task,
+        trains: &[AvailableTrain],
+    ) -> bool {
+        self.notify(
+            conn,
+            task,
+            NotificationContent::Availability {
+                trains: trains.to_vec(),
+            },
+        )
+        .await
+    }
+
+    pub async fn notify_price_drops(
+        &self,
+        conn: &Surreal<Any>,
+        task: &Task,
+        drops: &[PriceDrop],
+    ) -> bool {
+        self.notify(
+            conn,
+            task,
+            NotificationContent::PriceDrops {
+                drops: drops.to_vec(),
+            },
+        )
+        .await
+    }
+
+    /// Queues the webhook event about changed availability unless webhooks are turned off.
+    pub async fn notify_availability_changed(
+        &self,
+        conn: &Surreal<Any>,
+        task: &Task,
+        status: &TaskCheckStatus,
+        trains: &[AvailableTrain],
+    ) {
+        let settings = self.load_settings(conn, task).await;
+        let channels = task
+            .notifications
+            .as_ref()
+            .and_then(|overrides| overrides.channels.clone())
+            .unwrap_or(settings.channels);
+        if !channels.webhook {
+            return;
+        }
+
+        if let Err(err) = self
+            .webhooks
+            .enqueue_event(
+                conn,
+                task.user.clone(),
+                "task.availability_changed",
+                json!({
+                    "task_id": encode_thing_to_base64_string(task.id.clone()),
+                    "status": status,
+                    "trains": trains
+                }),
+            )
+            .await
+        {
+            log::error!("Error on queueing webhooks for task {}: {err}", task.id);
+        }
+    }
+
+    /// Sends queued notifications that are due, one message per user and channel.
+    pub async fn send_due_digests(&self) -> Result<usize, NotificationsServiceError> {
+        let conn = self.db.get_connection().await;
+        let items = match list_due_digest_items(&conn, Utc::now()).await {
+            Ok(items) => items,
+            Err(err) => return Err(NotificationsServiceError::NotificationsDBError(err)),
+        };
+
+        let mut items_by_user: BTreeMap<String, Vec<DigestItem>> = BTreeMap::new();
+        for item in items {
+            items_by_user
+                .entry(item.user.to_string())
+                .or_default()
+                .push(item);
+        }
+
+        let users = items_by_user.len();
+        for items in items_by_user.into_values() {
+            let user_id = items[0].user.clone();
+            let by_email: Vec<NotificationContent> = items
+                .iter()
+                .filter(|item| item.channels.email)
+                .map(|item| item.content.clone())
+                .collect();
+            let by_telegram: Vec<NotificationContent> = items
+                .iter()
+                .filter(|item| item.channels.telegram)
+                .map(|item| item.content.clone())
+                .collect();
+            let email_failed = !by_email.is_empty()
+                && self.queue_email(&conn, user_id.clone(), &by_email).await
+                    == ChannelOutcome::Failed;
+            let telegram_failed = !by_telegram.is_empty()
+                && self
+                    .queue_telegram(&conn, user_id.clone(), &by_telegram)
+                    .await
+                    == ChannelOutcome::Failed;
+
+            // Items stay queued for the channels that failed and go to the next digest
+            let mut sent_ids: Vec<Thing> = Vec::new();
+            let mut email_left_ids: Vec<Thing> = Vec::new();
+            let mut telegram_left_ids: Vec<Thing> = Vec::new();
+            for item in items {
+                match (
+                    item.channels.email && email_failed,
+                    item.channels.telegram && telegram_failed,
+                ) {
+                    (false, false) => sent_ids.push(item.id),
+                    (true, false) => email_left_ids.push(item.id),
+                    (false, true) => telegram_left_ids.push(item.id),
+                    (true, true) => {}
+                }
+            }
+            if let Err(err) = delete_digest_items(&conn, sent_ids).await {
+                log::error!("Error on deleting sent digest items of {user_id}: {err}");
+            }
+            for (item_ids, channels) in [
+                (
+                    email_left_ids,
+                    NotificationChannels {
+                        email: true,
+                        telegram: false,
+                        webhook: false,
+                    },
+                ),
+                (
+                    telegram_left_ids,
+                    NotificationChannels {
+                        email: false,
+                        telegram: true,
+                        webhook: false,
+                    },
+                ),
+            ] {
+                if item_ids.is_empty() {
+                    continue;
+                }
+                if let Err(err) = set_digest_items_channels(&conn, item_ids, channels).await {
+                    log::error!("Error on updating unsent digest items of {user_id}: {err}");
+                }
+            }
+        }
+        Ok(users)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Days;
+    use lettre::SmtpTransport;
+    use prometheus::Registry;
+
+    use super::*;
+    use crate::{
+        models::{
+            notifications::{QuietHours, TaskNotificationOverrides},
+            outbox::{count_pending_outbox_messages, list_due_outbox_messages},
+            rzd::tasks::{insert_new_task, DayTask, TaskData, TaskSettings},
+            users::insert_new_user,
+        },
+        services::{dispatcher::DispatcherMetrics, mailer::MailerService},
+        utils::test_db::test_db_config,
+    };
+
+    /// Notifications without a telegram bot, so telegram never has a recipient.
+    async fn service() -> NotificationsService {
+        let db = test_db_config().await;
+        let mailer = MailerService::init(
+            SmtpTransport::unencrypted_localhost(),
+            String::from("metools@example.com"),
+            String::from("http://localhost"),
+        );
+        let dispatcher = NotificationsDispatcher::init(
+            db.clone(),
+            mailer,
+            None,
+            3,
+            60,
+            DispatcherMetrics::init(&Registry::new()),
+        );
+        NotificationsService::init(
+            db.clone(),
+            dispatcher,
+            WebhooksService::init(db, 3, 60),
+            3600,
+        )
+    }
+
+    async fn create_task(conn: &Surreal<Any>, user_id: Thing) -> Task {
+        let data = TaskData::Day(DayTask {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from("2000000"),
+            date: Utc::now().date_naive() + Days::new(1),
+        });
+        insert_new_task(conn.clone(), user_id, data, TaskSettings::default(), 10)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn missing_telegram_chat_is_not_retried() {
+        let service = service().await;
+        let conn = service.db.get_connection().await;
+        let user = insert_new_user(
+            &conn,
+            String::from("user"),
+            String::from("user@example.com"),
+            String::from("password"),
+        )
+        .await
+        .unwrap();
+        let task = create_task(&conn, user.id).await;
+
+        assert!(service.notify_task_availability(&conn, &task, &[]).await);
+        assert_eq!(count_pending_outbox_messages(&conn).await.unwrap(), 1);
+        assert!(list_due_digest_items(&conn, Utc::now())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_channel_is_retried_alone() {
+        let service = service().await;
+        let conn = service.db.get_connection().await;
+        // The email has no address to go to without the user
+        let task = create_task(&conn, Thing::from(("users", "missing"))).await;
+
+        assert!(service.notify_task_availability(&conn, &task, &[]).await);
+        assert_eq!(count_pending_outbox_messages(&conn).await.unwrap(), 0);
+        let items = list_due_digest_items(&conn, Utc::now()).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].channels.email);
+        assert!(!items[0].channels.telegram);
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    /// When the planned notification leaves, `None` when it is skipped.
+    fn planned_at(dispatch: Dispatch, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match dispatch {
+            Dispatch::Skip => None,
+            Dispatch::Now(_) => Some(now),
+            Dispatch::Later(_, send_at) => Some(send_at),
+        }
+    }
+
+    fn night_settings() -> NotificationSettings {
+        NotificationSettings {
+            quiet_hours: Some(QuietHours {
+                start: NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            }),
+            timezone: String::from("Europe/Moscow"),
+            ..NotificationSettings::default()
+        }
+    }
+
+    #[test]
+    fn quiet_hours_wrap_around_midnight() {
+        let quiet_hours = night_settings().quiet_hours.unwrap();
+        for (time, quiet) in [(22, false), (23, true), (3, true), (8, false), (12, false)] {
+            let time = NaiveTime::from_hms_opt(time, 0, 0).unwrap();
+            assert_eq!(quiet_hours.contains(time), quiet, "{time}");
+        }
+    }
+
+    #[test]
+    fn quiet_hours_end_in_the_user_time_zone() {
+        let end = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        // 00:30 and 22:30 in Moscow both wait for 08:00 Moscow time, 05:00 UTC
+        for now in ["2024-08-01T21:30:00Z", "2024-08-01T19:30:00Z"] {
+            assert_eq!(
+                quiet_hours_end_after(at(now), end, Tz::Europe__Moscow),
+                at("2024-08-02T05:00:00Z"),
+                "{now}"
+            );
+        }
+    }
+
+    #[test]
+    fn digests_are_aligned_to_windows() {
+        let now = at("2024-08-01T10:20:00Z");
+        assert_eq!(next_digest_at(now, 3600), at("2024-08-01T11:00:00Z"));
+        assert_eq!(
+            next_digest_at(at("2024-08-01T11:00:00Z"), 3600),
+            at("2024-08-01T12:00:00Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn plan_dispatch_follows_settings_and_overrides() {
+        let service = service().await;
+        let conn = service.db.get_connection().await;
+        let mut task = create_task(&conn, Thing::from(("users", "user"))).await;
+        let day = at("2024-08-01T09:00:00Z");
+        let night = at("2024-08-01T21:30:00Z");
+        let morning = at("2024-08-02T05:00:00Z");
+        let settings = night_settings();
+
+        assert_eq!(
+            planned_at(plan_dispatch(&settings, &task, day, 3600), day),
+            Some(day)
+        );
+        assert_eq!(
+            planned_at(plan_dispatch(&settings, &task, night, 3600), night),
+            Some(morning)
+        );
+        let digest = NotificationSettings {
+            delivery: DeliveryMode::Digest,
+            ..settings.clone()
+        };
+        assert_eq!(
+            planned_at(plan_dispatch(&digest, &task, day, 3600), day),
+            Some(at("2024-08-01T10:00:00Z"))
+        );
+
+        task.notifications = Some(TaskNotificationOverrides {
+            quiet_hours: Some(false),
+            delivery: Some(DeliveryMode::Instant),
+            ..TaskNotificationOverrides::default()
+        });
+        assert_eq!(
+            planned_at(plan_dispatch(&digest, &task, night, 3600), night),
+            Some(night)
+        );
+        task.notifications = Some(TaskNotificationOverrides {
+            channels: Some(NotificationChannels {
+                email: false,
+                telegram: false,
+                webhook: true,
+            }),
+            ..TaskNotificationOverrides::default()
+        });
+        assert_eq!(
+            planned_at(plan_dispatch(&settings, &task, day, 3600), day),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn send_due_digests_sends_one_email_per_user() {
+        let service = service().await;
+        let conn = service.db.get_connection().await;
+        let user = insert_new_user(
+            &conn,
+            String::from("user"),
+            String::from("user@example.com"),
+            String::from("password"),
+        )
+        .await
+        .unwrap();
+        let settings = NotificationSettings {
+            delivery: DeliveryMode::Digest,
+            ..NotificationSettings::default()
+        };
+        set_notification_settings_for_user(&conn, user.id.clone(), settings)
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let task = create_task(&conn, user.id.clone()).await;
+            assert!(service.notify_task_availability(&conn, &task, &[]).await);
+        }
+        assert_eq!(count_pending_outbox_messages(&conn).await.unwrap(), 0);
+
+        // Nothing is due before the digest window
+        assert_eq!(service.send_due_digests().await.unwrap(), 0);
+        conn.query("UPDATE notification_digest_items SET send_after = time::now()")
+            .await
+            .unwrap();
+        assert_eq!(service.send_due_digests().await.unwrap(), 1);
+
+        let messages = list_due_outbox_messages(&conn, 10).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0].subject.as_deref(),
+            Some("Updates on your tasks")
+        );
+        assert!(list_due_digest_items(&conn, Utc::now())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,526 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use derive_more::Display;
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    header::{COOKIE, SET_COOKIE},
+    Url,
+};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+
+use crate::{
+    config::RzdHttpConfig,
+    models::rzd::tasks::{DateRangeTask, DatesTask, DayTask, TaskData},
+    services::rzd::guard::RzdGuard,
+};
+
+const TIMETABLE_PATH: &str = "/timetable/public/ru";
+const TRAINS_LAYER_ID: &str = "5827";
+const CARS_LAYER_ID: &str = "5764";
+pub(crate) const DATE_FORMAT: &str = "%d.%m.%Y";
+const TIME_FORMAT: &str = "%H:%M";
+
+#[derive(Debug, Display)]
+pub enum RzdClientError {
+    RequestError(reqwest::Error),
+    DecodeError(serde_json::Error),
+    UnexpectedResponse(String),
+    /// RZD was still preparing the result of the RID after every poll.
+    #[display(fmt = "no result for RID {} after all polls", _0)]
+    ResultNotReady(u64),
+    /// RZD is failing and requests are paused by the circuit breaker.
+    CircuitOpen,
+}
+
+impl RzdClientError {
+    /// Whether the error comes from RZD or the network being unwell rather than from the query.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RequestError(err) => !err
+                .status()
+                .is_some_and(|status| status.is_client_error() && status.as_u16() != 429),
+            Self::ResultNotReady(_) | Self::CircuitOpen => true,
+            Self::DecodeError(_) | Self::UnexpectedResponse(_) => false,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Deserialize, Clone, Debug)]
+pub struct TrainCarClass {
+    #[serde(rename = "typeLoc")]
+    pub car_type: String,
+    #[serde(rename = "freeSeats")]
+    pub free_seats: u32,
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub tariff: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Train {
+    pub number: String,
+    pub date0: String,
+    pub time0: String,
+    pub date1: String,
+    pub time1: String,
+    #[serde(default)]
+    pub cars: Vec<TrainCarClass>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Clone, Debug)]
+pub struct CarSeats {
+    /// Seat placement code, e.g. `dn` for lower and `up` for upper berths.
+    #[serde(rename = "type", default)]
+    pub seat_type: String,
+    pub free: u32,
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub tariff: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Car {
+    #[serde(rename = "typeLoc")]
+    pub car_type: String,
+    #[serde(default)]
+    pub seats: Vec<CarSeats>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TrainCars {
+    pub number: String,
+    pub date0: String,
+    pub time0: String,
+    pub date1: String,
+    pub time1: String,
+    #[serde(default)]
+    pub cars: Vec<Car>,
+}
+
+#[derive(Deserialize)]
+struct TrainsRoute {
+    #[serde(default)]
+    list: Vec<Train>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+pub(crate) struct TimetableResponse {
+    result: String,
+    /// Id of the request RZD is still preparing, sent with the `RID` result.
+    #[serde(rename = "RID", default)]
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    rid: Option<u64>,
+    #[serde(default)]
+    tp: Vec<TrainsRoute>,
+    #[serde(default)]
+    lst: Vec<TrainCars>,
+}
+
+/// The timetable API answers a query with a RID and prepares the result in the background.
+enum TimetableAnswer {
+    Ready(TimetableResponse),
+    Pending(Option<u64>),
+}
+
+impl TimetableResponse {
+    fn answer(body: &str) -> Result<TimetableAnswer, RzdClientError> {
+        match serde_json::from_str::<TimetableResponse>(body) {
+            Ok(timetable) if timetable.result == "OK" => Ok(TimetableAnswer::Ready(timetable)),
+            Ok(timetable) if timetable.result == "RID" => {
+                Ok(TimetableAnswer::Pending(timetable.rid))
+            }
+            Ok(timetable) => Err(RzdClientError::UnexpectedResponse(format!(
+                "unexpected result {}",
+                timetable.result
+            ))),
+            Err(err) => Err(RzdClientError::DecodeError(err)),
+        }
+    }
+
+    /// Decodes a raw timetable body the way both the real API and fixtures return it.
+    pub(crate) fn decode(body: &str) -> Result<Self, RzdClientError> {
+        match Self::answer(body)? {
+            TimetableAnswer::Ready(timetable) => Ok(timetable),
+            TimetableAnswer::Pending(_) => Err(RzdClientError::UnexpectedResponse(String::from(
+                "unexpected result RID",
+            ))),
+        }
+    }
+
+    pub(crate) fn into_trains(self) -> Vec<Train> {
+        self.tp.into_iter().flat_map(|route| route.list).collect()
+    }
+
+    pub(crate) fn into_train_cars(self) -> Vec<TrainCars> {
+        self.lst
+    }
+}
+
+/// Query for all trains between two stations on a day (`DD.MM.YYYY`).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SearchTrainsQuery {
+    pub from_point_code: String,
+    pub to_point_code: String,
+    pub date: String,
+}
+
+/// Query for cars and seats of a single train identified by its number and departure time.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TrainCarsQuery {
+    pub from_point_code: String,
+    pub to_point_code: String,
+    pub date: String,
+    pub time: String,
+    pub tnum: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RzdQuery {
+    SearchTrains(SearchTrainsQuery),
+    TrainCars(TrainCarsQuery),
+}
+
+impl RzdQuery {
+    /// Queries needed to check a task, one per travel date that has not passed yet.
+    pub fn for_task(value: &TaskData) -> Vec<Self> {
+        let today = Utc::now().date_naive();
+        match value {
+            TaskData::Train(task) => vec![Self::TrainCars(TrainCarsQuery {
+                from_point_code: task.from_point_code.clone(),
+                to_point_code: task.to_point_code.clone(),
+                date: task.date.format(DATE_FORMAT).to_string(),
+                time: task.time.format(TIME_FORMAT).to_string(),
+                tnum: task.tnum.clone(),
+            })],
+            TaskData::Day(DayTask {
+                from_point_code,
+                to_point_code,
+                ..
+            })
+            | TaskData::Dates(DatesTask {
+                from_point_code,
+                to_point_code,
+                ..
+            })
+            | TaskData::DateRange(DateRangeTask {
+                from_point_code,
+                to_point_code,
+                ..
+            }) => value
+                .dates()
+                .into_iter()
+                .filter(|date| *date >= today)
+                .map(|date| {
+                    Self::SearchTrains(SearchTrainsQuery {
+                        from_point_code: from_point_code.clone(),
+                        to_point_code: to_point_code.clone(),
+                        date: date.format(DATE_FORMAT).to_string(),
+                    })
+                })
+                .collect(),
+            TaskData::RoundTrip(task) => [&task.outbound, &task.return_leg]
+                .into_iter()
+                .flat_map(|leg| Self::for_task(&TaskData::Day(leg.clone())))
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait RzdClient: Send + Sync {
+    async fn search_trains(&self, query: &SearchTrainsQuery) -> Result<Vec<Train>, RzdClientError>;
+
+    async fn get_train_cars(
+        &self,
+        query: &TrainCarsQuery,
+    ) -> Result<Vec<TrainCars>, RzdClientError>;
+
+    /// Whether requests can be sent now, false while RZD is considered down.
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpRzdClient {
+    http: reqwest::Client,
+    timetable_url: Url,
+    config: RzdHttpConfig,
+    guard: RzdGuard,
+}
+
+impl HttpRzdClient {
+    pub fn init(config: RzdHttpConfig, guard: RzdGuard) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout))
+            .build()
+            .unwrap();
+        let timetable_url = Url::parse(format!("{}{}", config.api_url, TIMETABLE_PATH).as_str())
+            .expect("RZD_API_URL must be a valid URL");
+
+        Self {
+            http,
+            timetable_url,
+            config,
+            guard,
+        }
+    }
+
+    /// Sends one HTTP request under the rate limit and the circuit breaker of the guard.
+    async fn request(
+        &self,
+        jar: &Jar,
+        query: &[(&str, &str)],
+    ) -> Result<TimetableAnswer, RzdClientError> {
+        self.guard.permit().await?;
+        let r = self.send(jar, query).await;
+        self.guard.record(r.as_ref().map(|_| ()));
+        r
+    }
+
+    async fn send(
+        &self,
+        jar: &Jar,
+        query: &[(&str, &str)],
+    ) -> Result<TimetableAnswer, RzdClientError> {
+        let mut request = self.http.get(self.timetable_url.clone()).query(query);
+        if let Some(cookies) = jar.cookies(&self.timetable_url) {
+            request = request.header(COOKIE, cookies);
+        }
+
+        let r = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        let response = match r {
+            Ok(response) => response,
+            Err(err) => return Err(RzdClientError::RequestError(err)),
+        };
+        jar.set_cookies(
+            &mut response.headers().get_all(SET_COOKIE).iter(),
+            response.url(),
+        );
+
+        match response.text().await {
+            Ok(body) => TimetableResponse::answer(body.as_str()),
+            Err(err) => Err(RzdClientError::RequestError(err)),
+        }
+    }
+
+    /// Sends the query, then asks for the result of the RID RZD answers with until it is ready.
+    async fn get_timetable(
+        &self,
+        layer_id: &str,
+        query: &[(&str, &str)],
+    ) -> Result<TimetableResponse, RzdClientError> {
+        // RZD ties the RID to the session cookies, so every query gets its own jar
+        let jar = Jar::default();
+        let mut params = vec![("layer_id", layer_id)];
+        params.extend_from_slice(query);
+
+        let mut rid = match self.request(&jar, &params).await? {
+            TimetableAnswer::Ready(timetable) => return Ok(timetable),
+            TimetableAnswer::Pending(Some(rid)) => rid,
+            TimetableAnswer::Pending(None) => {
+                return Err(RzdClientError::UnexpectedResponse(String::from(
+                    "RID result without RID",
+                )))
+            }
+        };
+
+        for _ in 0..self.config.rid_max_polls {
+            tokio::time::sleep(Duration::from_millis(self.config.rid_poll_delay)).await;
+
+            let rid_param = rid.to_string();
+            let answer = self
+                .request(&jar, &[("layer_id", layer_id), ("rid", rid_param.as_str())])
+                .await?;
+            match answer {
+                TimetableAnswer::Ready(timetable) => return Ok(timetable),
+                TimetableAnswer::Pending(next_rid) => rid = next_rid.unwrap_or(rid),
+            }
+        }
+
+        Err(RzdClientError::ResultNotReady(rid))
+    }
+}
+
+#[async_trait]
+impl RzdClient for HttpRzdClient {
+    async fn search_trains(&self, query: &SearchTrainsQuery) -> Result<Vec<Train>, RzdClientError> {
+        let timetable = self
+            .get_timetable(
+                TRAINS_LAYER_ID,
+                &[
+                    ("dir", "0"),
+                    ("tfl", "3"),
+                    ("checkSeats", "1"),
+                    ("code0", query.from_point_code.as_str()),
+                    ("code1", query.to_point_code.as_str()),
+                    ("dt0", query.date.as_str()),
+                ],
+            )
+            .await?;
+
+        Ok(timetable.into_trains())
+    }
+
+    async fn get_train_cars(
+        &self,
+        query: &TrainCarsQuery,
+    ) -> Result<Vec<TrainCars>, RzdClientError> {
+        let timetable = self
+            .get_timetable(
+                CARS_LAYER_ID,
+                &[
+                    ("dir", "0"),
+                    ("code0", query.from_point_code.as_str()),
+                    ("code1", query.to_point_code.as_str()),
+                    ("dt0", query.date.as_str()),
+                    ("time0", query.time.as_str()),
+                    ("tnum0", query.tnum.as_str()),
+                ],
+            )
+            .await?;
+
+        Ok(timetable.into_train_cars())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use prometheus::Registry;
+
+    use super::*;
+    use crate::{
+        config::RzdLimitsConfig,
+        utils::test_server::{ScriptedResponse, TestServer},
+    };
+
+    const TRAINS: &str = include_str!("../../../fixtures/rzd/trains.json");
+
+    fn client(server: &TestServer, request_timeout: u64, rid_max_polls: u32) -> HttpRzdClient {
+        let limits = RzdLimitsConfig {
+            rate_limit: 1000.0,
+            rate_burst: 100,
+            max_retries: 0,
+            retry_delay: 0,
+            circuit_failures: 100,
+            circuit_open_duration: 60,
+        };
+        let config = RzdHttpConfig {
+            api_url: server.url.clone(),
+            request_timeout,
+            rid_poll_delay: 0,
+            rid_max_polls,
+        };
+        HttpRzdClient::init(config, RzdGuard::init(limits, &Registry::new()))
+    }
+
+    fn query() -> SearchTrainsQuery {
+        SearchTrainsQuery {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from("2000000"),
+            date: String::from("01.08.2024"),
+        }
+    }
+
+    fn rid(rid: &str) -> ScriptedResponse {
+        ScriptedResponse::json(format!(r#"{{"result": "RID", "RID": "{rid}"}}"#).as_str())
+    }
+
+    #[tokio::test]
+    async fn search_trains_returns_immediate_result() {
+        let server = TestServer::start(vec![ScriptedResponse::json(TRAINS)]).await;
+
+        let trains = client(&server, 5, 3).search_trains(&query()).await.unwrap();
+
+        assert_eq!(trains[0].number, "016А");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert!(requests[0].body.is_empty());
+        assert!(requests[0].target.starts_with(TIMETABLE_PATH));
+        assert!(requests[0].target.contains("layer_id=5827"));
+        assert!(requests[0].target.contains("code0=2004000"));
+        assert!(requests[0].target.contains("dt0=01.08.2024"));
+    }
+
+    #[tokio::test]
+    async fn search_trains_polls_rid_with_session_cookie() {
+        let server = TestServer::start(vec![
+            rid("123").with_header("Set-Cookie", "JSESSIONID=abc; Path=/"),
+            ScriptedResponse::json(TRAINS),
+        ])
+        .await;
+
+        let trains = client(&server, 5, 3).search_trains(&query()).await.unwrap();
+
+        assert_eq!(trains[0].number, "016А");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].target.contains("rid=123"));
+        assert!(requests[1].target.contains("layer_id=5827"));
+        assert_eq!(requests[1].header("cookie"), Some("JSESSIONID=abc"));
+    }
+
+    #[tokio::test]
+    async fn search_trains_follows_new_rid() {
+        let server =
+            TestServer::start(vec![rid("1"), rid("2"), ScriptedResponse::json(TRAINS)]).await;
+
+        let r = client(&server, 5, 3).search_trains(&query()).await;
+
+        assert!(r.is_ok());
+        let requests = server.requests();
+        assert!(requests[1].target.contains("rid=1"));
+        assert!(requests[2].target.contains("rid=2"));
+    }
+
+    #[tokio::test]
+    async fn search_trains_fails_on_rid_without_rid() {
+        let server = TestServer::start(vec![ScriptedResponse::json(r#"{"result": "RID"}"#)]).await;
+
+        let r = client(&server, 5, 3).search_trains(&query()).await;
+
+        assert!(matches!(r, Err(RzdClientError::UnexpectedResponse(_))));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_trains_gives_up_after_max_polls() {
+        let server = TestServer::start(vec![rid("7"), rid("7"), rid("7"), rid("7")]).await;
+
+        let r = client(&server, 5, 2).search_trains(&query()).await;
+
+        match r {
+            Err(err @ RzdClientError::ResultNotReady(7)) => assert!(err.is_transient()),
+            r => panic!("expected ResultNotReady, got {r:?}"),
+        }
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn search_trains_times_out() {
+        let server = TestServer::start(vec![
+            ScriptedResponse::json(TRAINS).with_delay(Duration::from_secs(3))
+        ])
+        .await;
+
+        let r = client(&server, 1, 3).search_trains(&query()).await;
+
+        match r {
+            Err(err @ RzdClientError::RequestError(_)) => {
+                assert!(matches!(&err, RzdClientError::RequestError(err) if err.is_timeout()));
+                assert!(err.is_transient());
+            }
+            r => panic!("expected a timeout, got {r:?}"),
+        }
+    }
+}
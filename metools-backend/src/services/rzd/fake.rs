@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::services::rzd::client::{
+    RzdClient, RzdClientError, SearchTrainsQuery, TimetableResponse, Train, TrainCars,
+    TrainCarsQuery,
+};
+
+/// Offline [`RzdClient`] answering from canned timetable JSON fixtures.
+///
+/// Fixtures use the RZD wire format. A query-specific file
+/// (`trains_{from}_{to}_{date}.json`, `cars_{tnum}_{date}.json`) takes precedence
+/// over the generic `trains.json` / `cars.json`.
+#[derive(Clone)]
+pub struct FakeRzdClient {
+    fixtures_path: PathBuf,
+}
+
+impl FakeRzdClient {
+    pub fn init(fixtures_path: PathBuf) -> Self {
+        Self { fixtures_path }
+    }
+
+    async fn read_fixture(
+        &self,
+        specific_name: String,
+        generic_name: &str,
+    ) -> Result<TimetableResponse, RzdClientError> {
+        let specific_path = self.fixtures_path.join(specific_name);
+        let path = if specific_path.exists() {
+            specific_path
+        } else {
+            self.fixtures_path.join(generic_name)
+        };
+
+        match tokio::fs::read_to_string(&path).await {
+            Ok(body) => TimetableResponse::decode(body.as_str()),
+            Err(err) => Err(RzdClientError::UnexpectedResponse(format!(
+                "cant read fixture {}: {err}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Fresh copy of the fixtures directory with extra `(file name, fixture)` copies, for tests
+/// answering queries whose file names hold dates known only at run time.
+#[cfg(test)]
+pub fn fixtures_path_with(files: &[(String, &str)]) -> PathBuf {
+    let source = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/rzd");
+    let path = std::env::temp_dir().join(format!("metools-rzd-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&path).unwrap();
+    for name in ["trains.json", "cars.json"] {
+        std::fs::copy(source.join(name), path.join(name)).unwrap();
+    }
+    for (name, fixture) in files {
+        std::fs::copy(source.join(fixture), path.join(name)).unwrap();
+    }
+    path
+}
+
+#[async_trait]
+impl RzdClient for FakeRzdClient {
+    async fn search_trains(&self, query: &SearchTrainsQuery) -> Result<Vec<Train>, RzdClientError> {
+        let timetable = self
+            .read_fixture(
+                format!(
+                    "trains_{}_{}_{}.json",
+                    query.from_point_code, query.to_point_code, query.date
+                ),
+                "trains.json",
+            )
+            .await?;
+
+        Ok(timetable.into_trains())
+    }
+
+    async fn get_train_cars(
+        &self,
+        query: &TrainCarsQuery,
+    ) -> Result<Vec<TrainCars>, RzdClientError> {
+        let timetable = self
+            .read_fixture(
+                format!("cars_{}_{}.json", query.tnum, query.date),
+                "cars.json",
+            )
+            .await?;
+
+        Ok(timetable.into_train_cars())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fixtures_path_with, FakeRzdClient};
+    use crate::services::rzd::client::{
+        RzdClient, RzdClientError, SearchTrainsQuery, TrainCarsQuery,
+    };
+
+    fn search_query(to_point_code: &str) -> SearchTrainsQuery {
+        SearchTrainsQuery {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from(to_point_code),
+            date: String::from("01.08.2024"),
+        }
+    }
+
+    #[tokio::test]
+    async fn search_trains_prefers_query_specific_fixture() {
+        let client = FakeRzdClient::init(fixtures_path_with(&[(
+            String::from("trains_2004000_2060001_01.08.2024.json"),
+            "trains_sold_out.json",
+        )]));
+
+        let trains = client
+            .search_trains(&search_query("2000000"))
+            .await
+            .unwrap();
+        assert_eq!(trains.len(), 2);
+        assert_eq!(trains[0].number, "016А");
+        assert_eq!(trains[0].cars[1].tariff, 5142);
+
+        let trains = client
+            .search_trains(&search_query("2060001"))
+            .await
+            .unwrap();
+        assert_eq!(trains.len(), 1);
+        assert_eq!(trains[0].number, "060А");
+        assert!(trains[0].cars.iter().all(|car| car.free_seats == 0));
+    }
+
+    #[tokio::test]
+    async fn get_train_cars_reads_seats() {
+        let client = FakeRzdClient::init(fixtures_path_with(&[]));
+
+        let trains = client
+            .get_train_cars(&TrainCarsQuery {
+                from_point_code: String::from("2004000"),
+                to_point_code: String::from("2000000"),
+                date: String::from("01.08.2024"),
+                time: String::from("23:55"),
+                tnum: String::from("016А"),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(trains[0].number, "016А");
+        assert_eq!(trains[0].cars[0].car_type, "Купе");
+        assert_eq!(trains[0].cars[0].seats[0].free, 2);
+    }
+
+    #[tokio::test]
+    async fn search_trains_reports_error_results() {
+        let client = FakeRzdClient::init(fixtures_path_with(&[(
+            String::from("trains_2004000_2060001_01.08.2024.json"),
+            "trains_error.json",
+        )]));
+
+        let err = client
+            .search_trains(&search_query("2060001"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, RzdClientError::UnexpectedResponse(_)));
+        assert!(!err.is_transient());
+    }
+}
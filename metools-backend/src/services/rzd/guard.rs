@@ -0,0 +1,409 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts, Registry};
+use serde::Serialize;
+
+use crate::{
+    config::RzdLimitsConfig,
+    services::rzd::client::{
+        RzdClient, RzdClientError, SearchTrainsQuery, Train, TrainCars, TrainCarsQuery,
+    },
+};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    /// Requests fail without reaching RZD until the circuit half-opens.
+    Open,
+    /// One trial request decides whether the circuit closes or opens again.
+    HalfOpen,
+}
+
+impl CircuitState {
+    fn as_gauge(&self) -> i64 {
+        match self {
+            Self::Closed => 0,
+            Self::HalfOpen => 1,
+            Self::Open => 2,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+struct Circuit {
+    state: CircuitState,
+    failures: u32,
+    opened_at: Option<Instant>,
+    trial_running: bool,
+}
+
+#[derive(Clone)]
+struct RzdMetrics {
+    requests: IntCounterVec,
+    retries: IntCounter,
+    throttled: IntCounter,
+    circuit_state: IntGauge,
+}
+
+impl RzdMetrics {
+    fn init(registry: &Registry) -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("rzd_requests_total", "Requests to RZD by outcome"),
+            &["outcome"],
+        )
+        .unwrap();
+        let retries =
+            IntCounter::new("rzd_request_retries_total", "Retried requests to RZD").unwrap();
+        let throttled = IntCounter::new(
+            "rzd_requests_throttled_total",
+            "Requests to RZD that waited for the rate limiter",
+        )
+        .unwrap();
+        let circuit_state = IntGauge::new(
+            "rzd_circuit_state",
+            "State of the RZD circuit breaker: 0 closed, 1 half-open, 2 open",
+        )
+        .unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(retries.clone())).unwrap();
+        registry.register(Box::new(throttled.clone())).unwrap();
+        registry.register(Box::new(circuit_state.clone())).unwrap();
+
+        Self {
+            requests,
+            retries,
+            throttled,
+            circuit_state,
+        }
+    }
+}
+
+/// Outcome label of a request for metrics.
+fn outcome(r: &Result<(), &RzdClientError>) -> &'static str {
+    match r {
+        Ok(()) => "success",
+        Err(RzdClientError::RequestError(err)) if err.is_timeout() => "timeout",
+        Err(RzdClientError::RequestError(err)) => match err.status() {
+            Some(status) if status.as_u16() == 429 => "rate_limited",
+            Some(status) if status.is_server_error() => "server_error",
+            _ => "request_error",
+        },
+        Err(RzdClientError::DecodeError(_)) | Err(RzdClientError::UnexpectedResponse(_)) => {
+            "bad_response"
+        }
+        Err(RzdClientError::ResultNotReady(_)) => "not_ready",
+        Err(RzdClientError::CircuitOpen) => "circuit_open",
+    }
+}
+
+fn is_retryable(err: &RzdClientError) -> bool {
+    match err {
+        RzdClientError::RequestError(err) => {
+            err.is_timeout()
+                || err.is_connect()
+                || err
+                    .status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+/// Rate limiter and circuit breaker shared by everything that talks to RZD.
+#[derive(Clone)]
+pub struct RzdGuard {
+    limits: RzdLimitsConfig,
+    bucket: Arc<tokio::sync::Mutex<TokenBucket>>,
+    circuit: Arc<Mutex<Circuit>>,
+    metrics: RzdMetrics,
+}
+
+impl RzdGuard {
+    pub fn init(limits: RzdLimitsConfig, registry: &Registry) -> Self {
+        let bucket = TokenBucket {
+            tokens: limits.rate_burst as f64,
+            refilled_at: Instant::now(),
+        };
+        let circuit = Circuit {
+            state: CircuitState::Closed,
+            failures: 0,
+            opened_at: None,
+            trial_running: false,
+        };
+
+        Self {
+            limits,
+            bucket: Arc::new(tokio::sync::Mutex::new(bucket)),
+            circuit: Arc::new(Mutex::new(circuit)),
+            metrics: RzdMetrics::init(registry),
+        }
+    }
+
+    /// Waits until the token bucket allows one more request.
+    async fn acquire(&self) {
+        // The lock is held while waiting, so waiting requests go out in order
+        let mut bucket = self.bucket.lock().await;
+        let rate = self.limits.rate_limit.max(f64::MIN_POSITIVE);
+        let burst = self.limits.rate_burst.max(1) as f64;
+
+        let now = Instant::now();
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.refilled_at).as_secs_f64() * rate)
+            .min(burst);
+        bucket.refilled_at = now;
+        if bucket.tokens < 1.0 {
+            self.metrics.throttled.inc();
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+            tokio::time::sleep(wait).await;
+            bucket.tokens = 1.0;
+            bucket.refilled_at = Instant::now();
+        }
+        bucket.tokens -= 1.0;
+    }
+
+    fn open_duration(&self) -> Duration {
+        Duration::from_secs(self.limits.circuit_open_duration)
+    }
+
+    /// Current state, an open circuit turns half-open once its time is up.
+    pub fn circuit_state(&self) -> CircuitState {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.state == CircuitState::Open
+            && circuit
+                .opened_at
+                .is_some_and(|opened_at| opened_at.elapsed() >= self.open_duration())
+        {
+            circuit.state = CircuitState::HalfOpen;
+            circuit.trial_running = false;
+            self.metrics.circuit_state.set(circuit.state.as_gauge());
+        }
+        circuit.state.clone()
+    }
+
+    /// Whether a request may go out, only one trial request passes a half-open circuit.
+    fn allow_request(&self) -> bool {
+        let state = self.circuit_state();
+        let mut circuit = self.circuit.lock().unwrap();
+        match state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if circuit.trial_running => false,
+            CircuitState::HalfOpen => {
+                circuit.trial_running = true;
+                true
+            }
+        }
+    }
+
+    /// Lets one HTTP request to RZD go out once the rate limit allows, fails while the circuit is open.
+    pub async fn permit(&self) -> Result<(), RzdClientError> {
+        if !self.allow_request() {
+            self.metrics
+                .requests
+                .with_label_values(&[outcome(&Err(&RzdClientError::CircuitOpen))])
+                .inc();
+            return Err(RzdClientError::CircuitOpen);
+        }
+        self.acquire().await;
+        Ok(())
+    }
+
+    /// Counts the outcome of a permitted request towards the metrics and the circuit.
+    ///
+    /// Only transient errors count as failures, an error caused by the query itself shows that
+    /// RZD answers.
+    pub fn record(&self, r: Result<(), &RzdClientError>) {
+        self.metrics
+            .requests
+            .with_label_values(&[outcome(&r)])
+            .inc();
+
+        let mut circuit = self.circuit.lock().unwrap();
+        circuit.trial_running = false;
+        match r {
+            Err(err) if err.is_transient() => {
+                circuit.failures += 1;
+                if circuit.state == CircuitState::HalfOpen
+                    || circuit.failures >= self.limits.circuit_failures
+                {
+                    if circuit.state != CircuitState::Open {
+                        log::warn!(
+                            "RZD circuit is open after {} failed requests",
+                            circuit.failures
+                        );
+                    }
+                    circuit.state = CircuitState::Open;
+                    circuit.opened_at = Some(Instant::now());
+                }
+            }
+            Ok(()) | Err(_) => {
+                circuit.failures = 0;
+                circuit.state = CircuitState::Closed;
+            }
+        }
+        self.metrics.circuit_state.set(circuit.state.as_gauge());
+    }
+
+    /// Runs a query, retrying timeouts, 429 and 5xx answers with backoff.
+    ///
+    /// The rate limit and the circuit apply to every HTTP request the query makes, see [`Self::permit`].
+    async fn call<T, F, Fut>(&self, request: F) -> Result<T, RzdClientError>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, RzdClientError>>,
+    {
+        let mut retries = 0;
+        loop {
+            match request().await {
+                Err(err) if is_retryable(&err) && retries < self.limits.max_retries => {
+                    let delay = self.limits.retry_delay << retries.min(16);
+                    retries += 1;
+                    self.metrics.retries.inc();
+                    log::warn!("Retrying RZD request in {delay}ms: {err}");
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                r => return r,
+            }
+        }
+    }
+}
+
+/// [`RzdClient`] that retries queries of another client and reports it unavailable while the
+/// circuit of the [`RzdGuard`] is open.
+pub struct GuardedRzdClient {
+    client: Arc<dyn RzdClient>,
+    guard: RzdGuard,
+}
+
+impl GuardedRzdClient {
+    pub fn init(client: Arc<dyn RzdClient>, guard: RzdGuard) -> Self {
+        Self { client, guard }
+    }
+}
+
+#[async_trait]
+impl RzdClient for GuardedRzdClient {
+    async fn search_trains(&self, query: &SearchTrainsQuery) -> Result<Vec<Train>, RzdClientError> {
+        self.guard.call(|| self.client.search_trains(query)).await
+    }
+
+    async fn get_train_cars(
+        &self,
+        query: &TrainCarsQuery,
+    ) -> Result<Vec<TrainCars>, RzdClientError> {
+        self.guard.call(|| self.client.get_train_cars(query)).await
+    }
+
+    fn is_available(&self) -> bool {
+        self.guard.circuit_state() != CircuitState::Open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(rate_limit: f64, rate_burst: u32, circuit_open_duration: u64) -> RzdGuard {
+        let limits = RzdLimitsConfig {
+            rate_limit,
+            rate_burst,
+            max_retries: 0,
+            retry_delay: 0,
+            circuit_failures: 2,
+            circuit_open_duration,
+        };
+        RzdGuard::init(limits, &Registry::new())
+    }
+
+    fn transient() -> RzdClientError {
+        RzdClientError::ResultNotReady(1)
+    }
+
+    #[tokio::test]
+    async fn permit_waits_for_tokens_after_the_burst() {
+        let guard = guard(10.0, 2, 60);
+
+        let started = Instant::now();
+        guard.permit().await.unwrap();
+        guard.permit().await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert_eq!(guard.metrics.throttled.get(), 0);
+
+        guard.permit().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        assert_eq!(guard.metrics.throttled.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_transient_failures_in_a_row() {
+        let guard = guard(1000.0, 10, 60);
+
+        guard.record(Err(&transient()));
+        guard.record(Ok(()));
+        guard.record(Err(&transient()));
+        assert_eq!(guard.circuit_state(), CircuitState::Closed);
+
+        guard.record(Err(&transient()));
+        assert_eq!(guard.circuit_state(), CircuitState::Open);
+        assert!(matches!(
+            guard.permit().await,
+            Err(RzdClientError::CircuitOpen)
+        ));
+    }
+
+    #[tokio::test]
+    async fn errors_caused_by_the_query_do_not_open_circuit() {
+        let guard = guard(1000.0, 10, 60);
+
+        for _ in 0..5 {
+            let err = RzdClientError::UnexpectedResponse(String::from("unexpected result Error"));
+            guard.record(Err(&err));
+        }
+
+        assert_eq!(guard.circuit_state(), CircuitState::Closed);
+        assert!(guard.permit().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn half_open_circuit_lets_one_trial_through() {
+        // The circuit half-opens as soon as it opens
+        let guard = guard(1000.0, 10, 0);
+        guard.record(Err(&transient()));
+        guard.record(Err(&transient()));
+        assert_eq!(guard.circuit_state(), CircuitState::HalfOpen);
+
+        assert!(guard.permit().await.is_ok());
+        assert!(matches!(
+            guard.permit().await,
+            Err(RzdClientError::CircuitOpen)
+        ));
+
+        // A successful trial closes the circuit
+        guard.record(Ok(()));
+        assert_eq!(guard.circuit_state(), CircuitState::Closed);
+        assert!(guard.permit().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn failed_trial_opens_circuit_again() {
+        let guard = guard(1000.0, 10, 0);
+        guard.record(Err(&transient()));
+        guard.record(Err(&transient()));
+        assert!(guard.permit().await.is_ok());
+
+        guard.record(Err(&transient()));
+
+        let circuit = guard.circuit.lock().unwrap();
+        assert_eq!(circuit.state, CircuitState::Open);
+        assert!(!circuit.trial_running);
+    }
+}
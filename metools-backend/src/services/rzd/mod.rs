@@ -0,0 +1,4 @@
+pub mod client;
+pub mod fake;
+pub mod guard;
+pub mod poller;
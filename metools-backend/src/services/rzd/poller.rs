@@ -0,0 +1,1254 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use derive_more::Display;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use surrealdb::{engine::any::Any, Surreal};
+
+use crate::{
+    config::DBConfig,
+    models::rzd::{
+        task_checks::insert_task_check,
+        task_prices::insert_task_prices,
+        tasks::{
+            list_tasks_due_for_check, set_task_check, set_task_notified_availability,
+            set_task_tracked_prices, AvailableCarClass, AvailableTrain, ClassPrice, LegCheck,
+            PriceAlert, PriceDrop, PriceKey, RoundTripTask, SeatType, Task, TaskCheck,
+            TaskCheckStatus, TaskData, TaskFilters, TasksDBError, TrackedPrice, TripLeg,
+        },
+    },
+    services::{
+        events::{TaskEventKind, TaskEventsService},
+        notifications::NotificationsService,
+        rzd::client::{RzdClient, RzdClientError, RzdQuery, Train, TrainCars, DATE_FORMAT},
+    },
+};
+
+/// Up to this share of the interval a check can come early to share RZD answers with other tasks.
+const MAX_EARLY_PERCENT: u64 = 10;
+/// Tasks due further ahead are never pulled into a run, in seconds.
+const MAX_JOIN_AHEAD: u64 = 3600;
+
+#[derive(Debug, Display)]
+pub enum TaskCheckError {
+    RzdClientError(Arc<RzdClientError>),
+}
+
+impl TaskCheckError {
+    /// Whether the task itself is at fault, only such errors can fail the task.
+    fn is_task_error(&self) -> bool {
+        match self {
+            Self::RzdClientError(err) => !err.is_transient(),
+        }
+    }
+}
+
+/// Answers of RZD within one run, tasks watching the same route and date share one query.
+#[derive(Default)]
+struct QueryResults {
+    results: HashMap<RzdQuery, Result<Vec<TrainOffers>, Arc<RzdClientError>>>,
+    queries: usize,
+}
+
+#[derive(Clone)]
+pub struct RzdPoller {
+    db: DBConfig,
+    client: Arc<dyn RzdClient>,
+    notifications: NotificationsService,
+    events: TaskEventsService,
+    max_failed_checks: u32,
+    /// Seconds between checks of tasks without their own interval.
+    default_check_interval: u64,
+    max_checks_per_run: usize,
+}
+
+/// Seats of one car class, seat type and price as returned by RZD.
+#[derive(Clone)]
+struct SeatOffer {
+    car_type: String,
+    seat_type: Option<SeatType>,
+    free_seats: u32,
+    price: u32,
+}
+
+#[derive(Clone)]
+struct TrainOffers {
+    number: String,
+    departure_date: String,
+    departure_time: String,
+    arrival_date: String,
+    arrival_time: String,
+    offers: Vec<SeatOffer>,
+}
+
+fn seat_type_from_code(code: &str) -> Option<SeatType> {
+    match code {
+        "dn" => Some(SeatType::Lower),
+        "up" => Some(SeatType::Upper),
+        "ldn" => Some(SeatType::SideLower),
+        "lup" => Some(SeatType::SideUpper),
+        _ => None,
+    }
+}
+
+fn offer_matches_filters(offer: &SeatOffer, filters: &TaskFilters) -> bool {
+    let car_type_matches = filters.car_types.is_empty()
+        || filters
+            .car_types
+            .iter()
+            .any(|car_type| car_type.to_lowercase() == offer.car_type.to_lowercase());
+    let seat_type_matches = filters.seat_types.is_empty()
+        || offer
+            .seat_type
+            .as_ref()
+            .is_some_and(|seat_type| filters.seat_types.contains(seat_type));
+    let price_matches = match filters.max_price {
+        Some(max_price) => offer.price <= max_price,
+        None => true,
+    };
+
+    offer.free_seats > 0 && car_type_matches && seat_type_matches && price_matches
+}
+
+fn collect_car_classes(offers: Vec<SeatOffer>, filters: &TaskFilters) -> Vec<AvailableCarClass> {
+    let mut car_classes: BTreeMap<String, AvailableCarClass> = BTreeMap::new();
+
+    for offer in offers
+        .into_iter()
+        .filter(|offer| offer_matches_filters(offer, filters))
+    {
+        let car_class = car_classes
+            .entry(offer.car_type.clone())
+            .or_insert(AvailableCarClass {
+                car_type: offer.car_type,
+                free_seats: 0,
+                min_price: offer.price,
+            });
+        car_class.free_seats += offer.free_seats;
+        car_class.min_price = car_class.min_price.min(offer.price);
+    }
+    let min_seats = filters.min_seats.unwrap_or(1);
+    car_classes
+        .into_values()
+        .filter(|car_class| car_class.free_seats >= min_seats)
+        .collect()
+}
+
+/// Identifies what is available so that users are notified only when it changes.
+fn availability_keys(trains: &[AvailableTrain]) -> Vec<String> {
+    let mut keys: Vec<String> = trains
+        .iter()
+        .flat_map(|train| {
+            train.car_classes.iter().map(move |car_class| {
+                format!(
+                    "{} {} {} {}",
+                    train.number, train.departure_date, train.departure_time, car_class.car_type
+                )
+            })
+        })
+        .collect();
+    keys.sort();
+    keys
+}
+
+fn seats_status(trains: &[AvailableTrain]) -> TaskCheckStatus {
+    if trains.is_empty() {
+        TaskCheckStatus::NoSeats
+    } else {
+        TaskCheckStatus::SeatsFound
+    }
+}
+
+fn available_dates(trains: &[AvailableTrain]) -> Vec<NaiveDate> {
+    let dates: BTreeSet<NaiveDate> = trains
+        .iter()
+        .filter_map(|train| NaiveDate::parse_from_str(&train.departure_date, DATE_FORMAT).ok())
+        .collect();
+    dates.into_iter().collect()
+}
+
+/// Minimum price of every car class on each travel date and leg of the found trains.
+fn min_prices(trains: &[AvailableTrain]) -> BTreeMap<PriceKey, u32> {
+    let mut prices: BTreeMap<PriceKey, u32> = BTreeMap::new();
+    for train in trains {
+        let date = match NaiveDate::parse_from_str(&train.departure_date, DATE_FORMAT) {
+            Ok(date) => date,
+            Err(_) => continue,
+        };
+        for car_class in train.car_classes.iter() {
+            let key = PriceKey {
+                date,
+                leg: train.leg.clone(),
+                car_type: car_class.car_type.clone(),
+            };
+            prices
+                .entry(key)
+                .and_modify(|price| *price = (*price).min(car_class.min_price))
+                .or_insert(car_class.min_price);
+        }
+    }
+    prices
+}
+
+/// Updates tracked prices with the latest ones and returns the classes the user should hear about.
+fn track_prices(
+    alert: &PriceAlert,
+    tracked_prices: &[TrackedPrice],
+    prices: &BTreeMap<PriceKey, u32>,
+) -> (Vec<TrackedPrice>, Vec<PriceDrop>) {
+    let mut new_tracked_prices: BTreeMap<PriceKey, TrackedPrice> = tracked_prices
+        .iter()
+        .map(|tracked| (tracked.key.clone(), tracked.clone()))
+        .collect();
+    let mut drops: Vec<PriceDrop> = Vec::new();
+
+    for (key, price) in prices {
+        let price = *price;
+        let (last_price, baseline_price) = match new_tracked_prices.get(key) {
+            Some(tracked) => (Some(tracked.last_price), tracked.baseline_price),
+            None => (None, price),
+        };
+        let crossed_below = alert.below_price.is_some_and(|below_price| {
+            let was_above = match last_price {
+                Some(last_price) => last_price > below_price,
+                None => true,
+            };
+            price <= below_price && was_above
+        });
+        let dropped = alert.drop_percent.is_some_and(|drop_percent| {
+            u64::from(price) * 100 <= u64::from(baseline_price) * u64::from(100 - drop_percent)
+        });
+
+        let baseline_price = if crossed_below || dropped {
+            drops.push(PriceDrop {
+                key: key.clone(),
+                price,
+                baseline_price,
+            });
+            price
+        } else {
+            baseline_price.max(price)
+        };
+        new_tracked_prices.insert(
+            key.clone(),
+            TrackedPrice {
+                key: key.clone(),
+                last_price: price,
+                baseline_price,
+            },
+        );
+    }
+    (new_tracked_prices.into_values().collect(), drops)
+}
+
+/// Nearest travel date of the task that has not passed yet.
+fn next_travel_date(task: &Task, today: NaiveDate) -> Option<NaiveDate> {
+    task.data
+        .dates()
+        .into_iter()
+        .filter(|date| *date >= today)
+        .min()
+}
+
+/// Multiplier of the check interval, tasks far from departure are checked less often.
+fn urgency_factor(days_left: Option<i64>) -> u64 {
+    match days_left {
+        Some(days) if days <= 2 => 1,
+        Some(days) if days <= 7 => 2,
+        Some(days) if days <= 30 => 4,
+        Some(_) => 8,
+        None => 1,
+    }
+}
+
+/// Seconds between checks of the task, longer for tasks far from departure.
+fn check_interval(task: &Task, now: DateTime<Utc>, default_check_interval: u64) -> u64 {
+    let days_left =
+        next_travel_date(task, now.date_naive()).map(|date| (date - now.date_naive()).num_days());
+    task.check_interval.unwrap_or(default_check_interval) * urgency_factor(days_left)
+}
+
+/// How early a check can come to share RZD answers, also how late a run can start and still keep
+/// the task's phase.
+fn early_slot(interval: u64) -> u64 {
+    (interval * MAX_EARLY_PERCENT / 100).max(1)
+}
+
+/// Phase of the interval for tasks sharing the query, taken from a digest of the query so it
+/// does not change between restarts or builds.
+fn query_phase(query: &RzdQuery, interval: u64) -> u64 {
+    let key = match query {
+        RzdQuery::SearchTrains(query) => format!(
+            "trains {} {} {}",
+            query.from_point_code, query.to_point_code, query.date
+        ),
+        RzdQuery::TrainCars(query) => format!(
+            "cars {} {} {} {} {}",
+            query.from_point_code, query.to_point_code, query.date, query.time, query.tnum
+        ),
+    };
+    let digest = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes) % interval
+}
+
+/// Time of the next check at the phase of the interval picked by the task's first query.
+///
+/// Tasks sharing a query come due in the same run while different queries spread load on RZD.
+/// A task out of its phase waits for it once, later checks keep the phase.
+fn phase_check_at(task: &Task, now: DateTime<Utc>, default_check_interval: u64) -> DateTime<Utc> {
+    let interval = check_interval(task, now, default_check_interval).max(1);
+    let phase = match RzdQuery::for_task(&task.data).first() {
+        Some(query) => query_phase(query, interval),
+        None => 0,
+    };
+    let earliest = now.timestamp() as u64 + interval - early_slot(interval);
+    let wait = (interval + phase - earliest % interval) % interval;
+
+    DateTime::from_timestamp((earliest + wait) as i64, 0).unwrap_or(now)
+}
+
+/// Time of the next check, the phase of the task's queries plus random jitter.
+///
+/// The jitter stays within half of the early slot, so tasks of one query still join the run of
+/// the first of them that comes due and the next check keeps the phase.
+fn next_check_at(task: &Task, now: DateTime<Utc>, default_check_interval: u64) -> DateTime<Utc> {
+    let interval = check_interval(task, now, default_check_interval).max(1);
+    let jitter = OsRng.next_u64() % (early_slot(interval) / 2).max(1);
+
+    phase_check_at(task, now, default_check_interval) + TimeDelta::seconds(jitter as i64)
+}
+
+/// Whether a task can be checked with the run: it is due within its early slot and the run
+/// already queries RZD for everything the task needs.
+fn can_join_run(
+    task: &Task,
+    queries: &HashSet<RzdQuery>,
+    now: DateTime<Utc>,
+    default_check_interval: u64,
+) -> bool {
+    let near = task.next_check_at.as_ref().map_or(true, |at| {
+        let slot = early_slot(check_interval(task, now, default_check_interval));
+        at.to_utc() <= now + TimeDelta::seconds(slot as i64)
+    });
+    let task_queries = RzdQuery::for_task(&task.data);
+    near && !task_queries.is_empty() && task_queries.iter().all(|query| queries.contains(query))
+}
+
+impl From<Train> for TrainOffers {
+    fn from(value: Train) -> Self {
+        Self {
+            offers: value
+                .cars
+                .into_iter()
+                .map(|car| SeatOffer {
+                    car_type: car.car_type,
+                    seat_type: None,
+                    free_seats: car.free_seats,
+                    price: car.tariff,
+                })
+                .collect(),
+            number: value.number,
+            departure_date: value.date0,
+            departure_time: value.time0,
+            arrival_date: value.date1,
+            arrival_time: value.time1,
+        }
+    }
+}
+
+impl From<TrainCars> for TrainOffers {
+    fn from(value: TrainCars) -> Self {
+        Self {
+            offers: value
+                .cars
+                .into_iter()
+                .flat_map(|car| {
+                    car.seats.into_iter().map(move |seats| SeatOffer {
+                        car_type: car.car_type.clone(),
+                        seat_type: seat_type_from_code(seats.seat_type.as_str()),
+                        free_seats: seats.free,
+                        price: seats.tariff,
+                    })
+                })
+                .collect(),
+            number: value.number,
+            departure_date: value.date0,
+            departure_time: value.time0,
+            arrival_date: value.date1,
+            arrival_time: value.time1,
+        }
+    }
+}
+
+impl TrainOffers {
+    fn into_available_train(self, filters: &TaskFilters) -> AvailableTrain {
+        AvailableTrain {
+            car_classes: collect_car_classes(self.offers, filters),
+            number: self.number,
+            departure_date: self.departure_date,
+            departure_time: self.departure_time,
+            arrival_date: self.arrival_date,
+            arrival_time: self.arrival_time,
+            leg: None,
+        }
+    }
+}
+
+impl RzdPoller {
+    pub fn init(
+        db: DBConfig,
+        client: Arc<dyn RzdClient>,
+        notifications: NotificationsService,
+        events: TaskEventsService,
+        max_failed_checks: u32,
+        default_check_interval: u64,
+        max_checks_per_run: usize,
+    ) -> Self {
+        Self {
+            db,
+            client,
+            notifications,
+            events,
+            max_failed_checks,
+            default_check_interval,
+            max_checks_per_run,
+        }
+    }
+
+    async fn run_query(
+        &self,
+        query: RzdQuery,
+        results: &mut QueryResults,
+    ) -> Result<Vec<TrainOffers>, TaskCheckError> {
+        if let Some(r) = results.results.get(&query) {
+            return r.clone().map_err(TaskCheckError::RzdClientError);
+        }
+
+        let r = match query.clone() {
+            RzdQuery::SearchTrains(query) => self
+                .client
+                .search_trains(&query)
+                .await
+                .map(|trains| trains.into_iter().map(TrainOffers::from).collect()),
+            RzdQuery::TrainCars(query) => self
+                .client
+                .get_train_cars(&query)
+                .await
+                .map(|trains| trains.into_iter().map(TrainOffers::from).collect()),
+        };
+
+        let r = r.map_err(Arc::new);
+        results.queries += 1;
+        results.results.insert(query, r.clone());
+
+        match r {
+            Ok(trains) => Ok(trains),
+            Err(err) => Err(TaskCheckError::RzdClientError(err)),
+        }
+    }
+
+    async fn find_available_trains(
+        &self,
+        data: &TaskData,
+        filters: &TaskFilters,
+        results: &mut QueryResults,
+    ) -> Result<Vec<AvailableTrain>, TaskCheckError> {
+        let mut trains: Vec<AvailableTrain> = Vec::new();
+        for query in RzdQuery::for_task(data) {
+            let offers = self.run_query(query, results).await?;
+            trains.extend(
+                offers
+                    .into_iter()
+                    .map(|train| train.into_available_train(filters))
+                    .filter(|train| !train.car_classes.is_empty()),
+            );
+        }
+        Ok(trains)
+    }
+
+    /// Checks both legs; the task has seats once both legs do, or any leg with `alert_per_leg`.
+    async fn check_round_trip(
+        &self,
+        task: &RoundTripTask,
+        filters: &TaskFilters,
+        results: &mut QueryResults,
+    ) -> Result<TaskCheck, TaskCheckError> {
+        let mut trains: Vec<AvailableTrain> = Vec::new();
+        let mut legs: Vec<LegCheck> = Vec::new();
+        for (leg, leg_task) in [
+            (TripLeg::Outbound, &task.outbound),
+            (TripLeg::Return, &task.return_leg),
+        ] {
+            let leg_trains = self
+                .find_available_trains(&TaskData::Day(leg_task.clone()), filters, results)
+                .await?;
+            legs.push(LegCheck {
+                leg: leg.clone(),
+                status: seats_status(&leg_trains),
+            });
+            trains.extend(leg_trains.into_iter().map(|train| AvailableTrain {
+                leg: Some(leg.clone()),
+                ..train
+            }));
+        }
+
+        let legs_with_seats = legs
+            .iter()
+            .filter(|leg| leg.status == TaskCheckStatus::SeatsFound)
+            .count();
+        let status = if legs_with_seats == legs.len() || (task.alert_per_leg && legs_with_seats > 0)
+        {
+            TaskCheckStatus::SeatsFound
+        } else {
+            TaskCheckStatus::NoSeats
+        };
+        Ok(TaskCheck {
+            status,
+            available_dates: available_dates(&trains),
+            trains,
+            legs,
+            error: None,
+        })
+    }
+
+    async fn check_task(
+        &self,
+        task: &Task,
+        results: &mut QueryResults,
+    ) -> Result<TaskCheck, TaskCheckError> {
+        match &task.data {
+            TaskData::RoundTrip(round_trip) => {
+                self.check_round_trip(round_trip, &task.filters, results)
+                    .await
+            }
+            data => self
+                .find_available_trains(data, &task.filters, results)
+                .await
+                .map(|trains| TaskCheck {
+                    status: seats_status(&trains),
+                    available_dates: available_dates(&trains),
+                    trains,
+                    legs: Vec::new(),
+                    error: None,
+                }),
+        }
+    }
+
+    /// Notifies the user when availability changed, returns whether any notification was sent.
+    async fn notify_about_check(
+        &self,
+        conn: &Surreal<Any>,
+        task: &Task,
+        check: &TaskCheck,
+    ) -> bool {
+        let availability = match check.status {
+            TaskCheckStatus::SeatsFound => Some(availability_keys(&check.trains)),
+            TaskCheckStatus::NoSeats => None,
+            TaskCheckStatus::Error => return false,
+        };
+        if availability == task.notified_availability {
+            return false;
+        }
+        let notified = availability.is_some();
+        if notified {
+            self.events.publish(
+                task.user.clone(),
+                task.id.clone(),
+                TaskEventKind::SeatsFound {
+                    trains: check.trains.clone(),
+                },
+            );
+        }
+
+        let queued = match availability {
+            Some(_) => {
+                self.notifications
+                    .notify_task_availability(conn, task, &check.trains)
+                    .await
+            }
+            None => true,
+        };
+
+        // Webhooks get both directions of the change whatever became of the user channels,
+        // deliveries are retried by the worker
+        self.notifications
+            .notify_availability_changed(conn, task, &check.status, &check.trains)
+            .await;
+        if !queued {
+            return false;
+        }
+
+        if let Err(err) = set_task_notified_availability(conn, task.id.clone(), availability).await
+        {
+            log::error!(
+                "Error on saving notified availability for task {}: {err}",
+                task.id
+            );
+        }
+        notified
+    }
+
+    /// Stores the price series point and sends a price alert when the task asks for it.
+    async fn track_task_prices(&self, conn: &Surreal<Any>, task: &Task, check: &TaskCheck) {
+        if check.status != TaskCheckStatus::SeatsFound {
+            return;
+        }
+        let prices = min_prices(&check.trains);
+        let class_prices: Vec<ClassPrice> = prices
+            .iter()
+            .map(|(key, price)| ClassPrice {
+                key: key.clone(),
+                min_price: *price,
+            })
+            .collect();
+        if let Err(err) = insert_task_prices(conn, task.id.clone(), class_prices.clone()).await {
+            log::error!("Error on saving prices for task {}: {err}", task.id);
+        }
+        let previous_prices = match &task.last_check {
+            Some(last_check) => min_prices(&last_check.trains),
+            None => BTreeMap::new(),
+        };
+        if prices != previous_prices {
+            self.events.publish(
+                task.user.clone(),
+                task.id.clone(),
+                TaskEventKind::PricesChanged {
+                    prices: class_prices,
+                },
+            );
+        }
+
+        let alert = match &task.price_alert {
+            Some(alert) => alert,
+            None => return,
+        };
+        let (tracked_prices, drops) = track_prices(alert, &task.tracked_prices, &prices);
+        if !drops.is_empty()
+            && !self
+                .notifications
+                .notify_price_drops(conn, task, &drops)
+                .await
+        {
+            return;
+        }
+        if let Err(err) = set_task_tracked_prices(conn, task.id.clone(), tracked_prices).await {
+            log::error!("Error on saving tracked prices for task {}: {err}", task.id);
+        }
+    }
+
+    /// Checks active tasks whose next check time has come and schedules their next check.
+    pub async fn check_due_tasks(&self) -> Result<usize, TasksDBError> {
+        if !self.client.is_available() {
+            log::warn!("Skipping rzd checks while the circuit is open");
+            return Ok(0);
+        }
+        let conn = self.db.get_connection().await;
+        let tasks = match list_tasks_due_for_check(conn.clone(), MAX_JOIN_AHEAD).await {
+            Ok(tasks) => tasks,
+            Err(err) => return Err(err),
+        };
+        let now = Utc::now();
+        let (mut tasks, waiting): (Vec<Task>, Vec<Task>) = tasks.into_iter().partition(|task| {
+            task.next_check_at
+                .as_ref()
+                .map_or(true, |at| at.to_utc() <= now)
+        });
+        // Tasks closest to departure go first when there are more than one run can check
+        let today = now.date_naive();
+        tasks.sort_by_key(|task| {
+            (
+                next_travel_date(task, today).unwrap_or(NaiveDate::MAX),
+                task.next_check_at.clone().map(|at| at.to_utc()),
+            )
+        });
+        let over_limit = tasks.split_off(self.max_checks_per_run.min(tasks.len()));
+        // Other subscribers of the run's queries are checked from the same RZD answers
+        let queries: HashSet<RzdQuery> = tasks
+            .iter()
+            .flat_map(|task| RzdQuery::for_task(&task.data))
+            .collect();
+        tasks.extend(
+            over_limit
+                .into_iter()
+                .chain(waiting)
+                .filter(|task| can_join_run(task, &queries, now, self.default_check_interval)),
+        );
+        let mut results = QueryResults::default();
+        let mut checked = 0;
+        for task in tasks.iter() {
+            // The rest keep their schedule and are checked once RZD is back
+            if !self.client.is_available() {
+                log::warn!("Stopping rzd checks, the circuit is open");
+                break;
+            }
+            let (check, count_failure) = match self.check_task(task, &mut results).await {
+                Ok(check) => (check, false),
+                // The task was not checked at all, it keeps its schedule
+                Err(TaskCheckError::RzdClientError(err))
+                    if matches!(*err, RzdClientError::CircuitOpen) =>
+                {
+                    log::warn!("Stopping rzd checks, the circuit opened");
+                    break;
+                }
+                Err(err) => {
+                    log::warn!("Error on checking task {}: {err}", task.id);
+                    let check = TaskCheck {
+                        status: TaskCheckStatus::Error,
+                        trains: Vec::new(),
+                        available_dates: Vec::new(),
+                        legs: Vec::new(),
+                        error: Some(err.to_string()),
+                    };
+                    (check, err.is_task_error())
+                }
+            };
+            checked += 1;
+            let notified = self.notify_about_check(&conn, task, &check).await;
+            self.track_task_prices(&conn, task, &check).await;
+            if let Err(err) =
+                insert_task_check(&conn, task.id.clone(), check.clone(), notified).await
+            {
+                log::error!("Error on saving check history for task {}: {err}", task.id);
+            }
+            self.events.publish(
+                task.user.clone(),
+                task.id.clone(),
+                TaskEventKind::Checked {
+                    check: check.clone(),
+                },
+            );
+            let next_check_at = next_check_at(task, Utc::now(), self.default_check_interval);
+            let r = set_task_check(
+                &conn,
+                task.id.clone(),
+                check,
+                count_failure,
+                self.max_failed_checks,
+                next_check_at,
+            )
+            .await;
+            match r {
+                Ok(status) if status != task.status => self.events.publish(
+                    task.user.clone(),
+                    task.id.clone(),
+                    TaskEventKind::StatusChanged { status },
+                ),
+                Ok(_) => {}
+                Err(err) => log::error!("Error on saving check for task {}: {err}", task.id),
+            }
+        }
+        if checked > 0 {
+            log::info!("Ran {} rzd queries for {checked} tasks", results.queries);
+        }
+        Ok(checked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Days;
+    use lettre::SmtpTransport;
+    use prometheus::Registry;
+    use surrealdb::sql::{Datetime, Thing};
+
+    use super::*;
+    use crate::{
+        config::{RzdHttpConfig, RzdLimitsConfig},
+        models::{
+            notifications::{
+                set_notification_settings_for_user, DeliveryMode, NotificationSettings,
+            },
+            outbox::{list_due_outbox_messages, OutboxChannel},
+            rzd::{
+                task_checks::list_task_checks,
+                tasks::{
+                    get_task_by_id_for_user, insert_new_task, DayTask, TaskSettings, TaskStatus,
+                },
+            },
+            users::insert_new_user,
+            webhooks::{create_webhook, list_due_webhook_deliveries},
+        },
+        services::{
+            dispatcher::{DispatcherMetrics, NotificationsDispatcher},
+            mailer::MailerService,
+            rzd::{
+                client::{HttpRzdClient, SearchTrainsQuery},
+                fake::{fixtures_path_with, FakeRzdClient},
+                guard::RzdGuard,
+            },
+            webhooks::WebhooksService,
+        },
+        utils::{
+            test_db::test_db_config,
+            test_server::{ScriptedResponse, TestServer},
+        },
+    };
+
+    fn tomorrow() -> NaiveDate {
+        Utc::now().date_naive() + Days::new(1)
+    }
+
+    /// Poller over an in-memory database and the fake client, `(to_point_code, fixture)` pairs
+    /// answer the searches from Saint Petersburg tomorrow, other routes get `trains.json`.
+    async fn poller(routes: &[(&str, &str)], max_failed_checks: u32) -> RzdPoller {
+        let date = tomorrow().format(DATE_FORMAT).to_string();
+        let fixtures: Vec<(String, &str)> = routes
+            .iter()
+            .map(|(to_point_code, fixture)| {
+                (
+                    format!("trains_2004000_{to_point_code}_{date}.json"),
+                    *fixture,
+                )
+            })
+            .collect();
+
+        poller_with_client(
+            Arc::new(FakeRzdClient::init(fixtures_path_with(&fixtures))),
+            max_failed_checks,
+        )
+        .await
+    }
+
+    async fn poller_with_client(client: Arc<dyn RzdClient>, max_failed_checks: u32) -> RzdPoller {
+        let db = test_db_config().await;
+        let mailer = MailerService::init(
+            SmtpTransport::unencrypted_localhost(),
+            String::from("metools@example.com"),
+            String::from("http://localhost"),
+        );
+        let dispatcher = NotificationsDispatcher::init(
+            db.clone(),
+            mailer,
+            None,
+            3,
+            60,
+            DispatcherMetrics::init(&Registry::new()),
+        );
+        let notifications = NotificationsService::init(
+            db.clone(),
+            dispatcher,
+            WebhooksService::init(db.clone(), 3, 60),
+            3600,
+        );
+        RzdPoller::init(
+            db,
+            client,
+            notifications,
+            TaskEventsService::init(),
+            max_failed_checks,
+            60,
+            10,
+        )
+    }
+
+    /// Creates a user watching the route from Saint Petersburg tomorrow.
+    async fn create_task(poller: &RzdPoller, to_point_code: &str) -> Task {
+        create_task_with(poller, to_point_code, TaskSettings::default()).await
+    }
+
+    async fn create_task_with(
+        poller: &RzdPoller,
+        to_point_code: &str,
+        settings: TaskSettings,
+    ) -> Task {
+        let conn = poller.db.get_connection().await;
+        let user = insert_new_user(
+            &conn,
+            String::from("user"),
+            String::from("user@example.com"),
+            String::from("password"),
+        )
+        .await
+        .unwrap();
+
+        insert_new_task(
+            conn,
+            user.id,
+            TaskData::Day(DayTask {
+                from_point_code: String::from("2004000"),
+                to_point_code: String::from(to_point_code),
+                date: tomorrow(),
+            }),
+            settings,
+            10,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn reload(poller: &RzdPoller, task: &Task) -> Task {
+        let conn = poller.db.get_connection().await;
+        get_task_by_id_for_user(conn, task.user.clone(), task.id.clone())
+            .await
+            .unwrap()
+    }
+
+    /// Brings the next check of every task forward to now.
+    async fn make_tasks_due(poller: &RzdPoller) {
+        let conn = poller.db.get_connection().await;
+        conn.query("UPDATE rzd_tasks SET next_check_at = time::now()")
+            .await
+            .unwrap();
+    }
+
+    fn day_task(id: &str, to_point_code: &str, check_interval: u64) -> Task {
+        Task {
+            id: Thing::from(("rzd_tasks", id)),
+            created_at: Datetime::default(),
+            data: TaskData::Day(DayTask {
+                from_point_code: String::from("2000000"),
+                to_point_code: String::from(to_point_code),
+                date: Utc::now().date_naive() + Days::new(1),
+            }),
+            user: Thing::from(("users", "user")),
+            filters: TaskFilters::default(),
+            status: TaskStatus::Active,
+            failed_checks: 0,
+            last_checked_at: None,
+            last_check: None,
+            notified_availability: None,
+            last_notified_at: None,
+            price_alert: None,
+            tracked_prices: Vec::new(),
+            check_interval: Some(check_interval),
+            notifications: None,
+            next_check_at: None,
+        }
+    }
+
+    #[test]
+    fn phase_check_at_puts_tasks_of_one_query_in_one_phase() {
+        let phase = phase_check_at(&day_task("a", "2004000", 600), Utc::now(), 60);
+
+        // A run late by less than the early slot keeps the phase
+        let late = phase + TimeDelta::seconds(17);
+        assert_eq!(
+            phase_check_at(&day_task("a", "2004000", 600), late, 60),
+            phase + TimeDelta::seconds(600)
+        );
+        // A task checked out of phase waits for it
+        let out_of_phase = phase - TimeDelta::seconds(400);
+        assert_eq!(
+            phase_check_at(&day_task("b", "2004000", 600), out_of_phase, 60),
+            phase + TimeDelta::seconds(600)
+        );
+        // Every check of a longer interval falls on a check of the shorter one
+        let longer = phase_check_at(&day_task("c", "2004000", 1200), phase, 60);
+        assert_eq!((longer - phase).num_seconds() % 600, 0);
+    }
+
+    #[test]
+    fn query_phase_is_stable() {
+        let query = RzdQuery::SearchTrains(SearchTrainsQuery {
+            from_point_code: String::from("2000000"),
+            to_point_code: String::from("2004000"),
+            date: String::from("01.08.2024"),
+        });
+
+        // Digest of "trains 2000000 2004000 01.08.2024"
+        assert_eq!(query_phase(&query, 1 << 32), 0x2685_cc74);
+    }
+
+    #[test]
+    fn next_check_at_adds_jitter_within_the_early_slot() {
+        let task = day_task("a", "2004000", 600);
+        let now = Utc::now();
+        let phase = phase_check_at(&task, now, 60);
+
+        let checks: HashSet<DateTime<Utc>> = (0..50)
+            .map(|_| next_check_at(&task, now, 60))
+            .inspect(|at| {
+                assert!(*at >= phase && *at < phase + TimeDelta::seconds(30));
+                // A check late by its jitter keeps the phase
+                assert_eq!(
+                    phase_check_at(&task, *at, 60),
+                    phase + TimeDelta::seconds(600)
+                );
+            })
+            .collect();
+        assert!(checks.len() > 1);
+    }
+
+    fn price_key(car_type: &str) -> PriceKey {
+        PriceKey {
+            date: tomorrow(),
+            leg: None,
+            car_type: String::from(car_type),
+        }
+    }
+
+    /// Runs `track_prices` over a series of prices of one class, returns the alerted ones.
+    fn alerted_prices(alert: &PriceAlert, series: &[u32]) -> Vec<u32> {
+        let mut tracked_prices: Vec<TrackedPrice> = Vec::new();
+        let mut alerted = Vec::new();
+        for price in series {
+            let prices = BTreeMap::from([(price_key("Плацкартный"), *price)]);
+            let (new_tracked_prices, drops) = track_prices(alert, &tracked_prices, &prices);
+            alerted.extend(drops.iter().map(|drop| drop.price));
+            tracked_prices = new_tracked_prices;
+        }
+        alerted
+    }
+
+    #[test]
+    fn track_prices_alerts_when_crossing_below_price() {
+        let alert = PriceAlert {
+            below_price: Some(3000),
+            drop_percent: None,
+        };
+        // Only crossings alert, staying below or going back up does not
+        assert_eq!(
+            alerted_prices(&alert, &[3500, 2900, 2800, 3200, 3000]),
+            vec![2900, 3000]
+        );
+        // A price already below on the first check alerts too
+        assert_eq!(alerted_prices(&alert, &[2500, 2500]), vec![2500]);
+    }
+
+    #[test]
+    fn track_prices_measures_drops_from_the_highest_price_since_alert() {
+        let alert = PriceAlert {
+            below_price: None,
+            drop_percent: Some(10),
+        };
+        // 4000 raises the baseline, 3600 is 10% off it and becomes the new baseline
+        assert_eq!(
+            alerted_prices(&alert, &[3800, 4000, 3700, 3600, 3500, 3240]),
+            vec![3600, 3240]
+        );
+    }
+
+    #[test]
+    fn track_prices_keeps_classes_apart() {
+        let alert = PriceAlert {
+            below_price: Some(3000),
+            drop_percent: None,
+        };
+        let prices = BTreeMap::from([(price_key("Купе"), 5000), (price_key("Плацкартный"), 2500)]);
+        let (tracked_prices, drops) = track_prices(&alert, &[], &prices);
+        assert_eq!(tracked_prices.len(), 2);
+        assert_eq!(drops.len(), 1);
+        assert_eq!(drops[0].key, price_key("Плацкартный"));
+        assert_eq!(drops[0].baseline_price, 2500);
+    }
+
+    #[tokio::test]
+    async fn check_due_tasks_sends_price_alerts_once() {
+        let poller = poller(&[], 3).await;
+        let settings = TaskSettings {
+            price_alert: Some(PriceAlert {
+                below_price: Some(1_000_000),
+                drop_percent: None,
+            }),
+            ..TaskSettings::default()
+        };
+        let task = create_task_with(&poller, "2000000", settings).await;
+        let conn = poller.db.get_connection().await;
+        let price_alerts = || async {
+            list_due_outbox_messages(&conn, 10)
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|message| message.subject.as_deref() == Some("Prices dropped"))
+                .count()
+        };
+
+        poller.check_due_tasks().await.unwrap();
+        assert_eq!(price_alerts().await, 1);
+        let checked = reload(&poller, &task).await;
+        assert!(!checked.tracked_prices.is_empty());
+
+        // Prices that stay below the threshold are not alerted again
+        make_tasks_due(&poller).await;
+        poller.check_due_tasks().await.unwrap();
+        assert_eq!(price_alerts().await, 1);
+    }
+
+    #[test]
+    fn can_join_run_when_due_soon_and_queries_are_run() {
+        let now = Utc::now();
+        let mut task = day_task("a", "2004000", 600);
+        let queries: HashSet<RzdQuery> = RzdQuery::for_task(&task.data).into_iter().collect();
+
+        task.next_check_at = Some((now + TimeDelta::seconds(300)).into());
+        assert!(!can_join_run(&task, &queries, now, 60));
+
+        task.next_check_at = Some((now + TimeDelta::seconds(50)).into());
+        assert!(can_join_run(&task, &queries, now, 60));
+
+        let other = day_task("b", "2010000", 600);
+        let other_queries: HashSet<RzdQuery> =
+            RzdQuery::for_task(&other.data).into_iter().collect();
+        assert!(!can_join_run(&task, &other_queries, now, 60));
+    }
+
+    #[tokio::test]
+    async fn check_due_tasks_notifies_about_found_seats_once() {
+        let poller = poller(&[], 3).await;
+        let task = create_task(&poller, "2000000").await;
+
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+
+        let checked = reload(&poller, &task).await;
+        assert_eq!(checked.status, TaskStatus::Active);
+        let check = checked.last_check.unwrap();
+        assert_eq!(check.status, TaskCheckStatus::SeatsFound);
+        // The second train has no cars with seats
+        assert_eq!(check.trains.len(), 1);
+        assert_eq!(check.trains[0].number, "016А");
+        assert!(checked.notified_availability.is_some());
+
+        let conn = poller.db.get_connection().await;
+        let messages = list_due_outbox_messages(&conn, 10).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].channel, OutboxChannel::Email);
+        assert_eq!(messages[0].recipient.as_deref(), Some("user@example.com"));
+        assert!(messages[0].body.contains("016А"));
+
+        // The same seats found again are not notified about
+        make_tasks_due(&poller).await;
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+        assert_eq!(list_due_outbox_messages(&conn, 10).await.unwrap().len(), 1);
+
+        let checks = list_task_checks(conn, task.id.clone(), None, 10)
+            .await
+            .unwrap();
+        let notified: Vec<bool> = checks.iter().map(|check| check.notified).collect();
+        assert_eq!(notified.iter().filter(|notified| **notified).count(), 1);
+        assert_eq!(notified.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn check_due_tasks_queues_webhooks_when_user_channels_fail() {
+        let poller = poller(&[], 3).await;
+        let task = create_task(&poller, "2000000").await;
+        let conn = poller.db.get_connection().await;
+        create_webhook(
+            &conn,
+            task.user.clone(),
+            String::from("https://example.com/hook"),
+        )
+        .await
+        .unwrap();
+        let settings = NotificationSettings {
+            delivery: DeliveryMode::Digest,
+            ..NotificationSettings::default()
+        };
+        set_notification_settings_for_user(&conn, task.user.clone(), settings)
+            .await
+            .unwrap();
+        // Digest items can no longer be stored
+        conn.query("DEFINE FIELD send_after ON notification_digest_items TYPE bool")
+            .await
+            .unwrap();
+
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+
+        let deliveries = list_due_webhook_deliveries(&conn, 10).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        // The user channels are retried on the next check
+        let checked = reload(&poller, &task).await;
+        assert!(checked.notified_availability.is_none());
+    }
+
+    #[tokio::test]
+    async fn check_due_tasks_records_sold_out_trains_without_notifying() {
+        let poller = poller(&[("2060001", "trains_sold_out.json")], 3).await;
+        let task = create_task(&poller, "2060001").await;
+
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+
+        let checked = reload(&poller, &task).await;
+        assert_eq!(checked.status, TaskStatus::Active);
+        let check = checked.last_check.unwrap();
+        assert_eq!(check.status, TaskCheckStatus::NoSeats);
+        assert!(check.trains.is_empty());
+        assert!(checked.notified_availability.is_none());
+
+        let conn = poller.db.get_connection().await;
+        assert!(list_due_outbox_messages(&conn, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        // Checked tasks wait for their next check
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn check_due_tasks_fails_task_after_max_failed_checks() {
+        let poller = poller(&[("2060001", "trains_error.json")], 2).await;
+        let task = create_task(&poller, "2060001").await;
+
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+        let checked = reload(&poller, &task).await;
+        assert_eq!(checked.status, TaskStatus::Active);
+        assert_eq!(checked.failed_checks, 1);
+        let check = checked.last_check.unwrap();
+        assert_eq!(check.status, TaskCheckStatus::Error);
+        assert!(check.error.is_some());
+
+        make_tasks_due(&poller).await;
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+        let checked = reload(&poller, &task).await;
+        assert_eq!(checked.status, TaskStatus::Failed);
+        assert_eq!(checked.failed_checks, 2);
+
+        // Failed tasks are not checked anymore and nobody is notified
+        make_tasks_due(&poller).await;
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 0);
+        let conn = poller.db.get_connection().await;
+        assert!(list_due_outbox_messages(&conn, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_due_tasks_reads_seats_through_http_client() {
+        let server = TestServer::start(vec![
+            ScriptedResponse::json(r#"{"result": "RID", "RID": "77"}"#)
+                .with_header("Set-Cookie", "JSESSIONID=abc; Path=/"),
+            ScriptedResponse::json(include_str!("../../../fixtures/rzd/trains.json")),
+        ])
+        .await;
+        let limits = RzdLimitsConfig {
+            rate_limit: 1000.0,
+            rate_burst: 100,
+            max_retries: 0,
+            retry_delay: 0,
+            circuit_failures: 100,
+            circuit_open_duration: 60,
+        };
+        let config = RzdHttpConfig {
+            api_url: server.url.clone(),
+            request_timeout: 5,
+            rid_poll_delay: 0,
+            rid_max_polls: 3,
+        };
+        let client = HttpRzdClient::init(config, RzdGuard::init(limits, &Registry::new()));
+        let poller = poller_with_client(Arc::new(client), 3).await;
+        let task = create_task(&poller, "2000000").await;
+
+        assert_eq!(poller.check_due_tasks().await.unwrap(), 1);
+
+        let check = reload(&poller, &task).await.last_check.unwrap();
+        assert_eq!(check.status, TaskCheckStatus::SeatsFound);
+        assert_eq!(check.trains[0].number, "016А");
+        let conn = poller.db.get_connection().await;
+        assert_eq!(list_due_outbox_messages(&conn, 10).await.unwrap().len(), 1);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let date = tomorrow().format(DATE_FORMAT).to_string();
+        assert!(requests[0].target.contains(format!("dt0={date}").as_str()));
+        assert!(requests[0].target.contains("code1=2000000"));
+        assert!(requests[1].target.contains("rid=77"));
+        assert_eq!(requests[1].header("cookie"), Some("JSESSIONID=abc"));
+    }
+}
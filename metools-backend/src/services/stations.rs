@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use derive_more::Display;
+
+use crate::{
+    config::DBConfig,
+    models::rzd::{
+        stations::{search_stations, upsert_stations, Station, StationsDBError},
+        tasks::validate_station_code,
+    },
+};
+
+const IMPORT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Display)]
+pub enum StationsServiceError {
+    StationsDBError(StationsDBError),
+    ImportError(String),
+}
+
+#[derive(Clone)]
+pub struct StationsService {
+    db: DBConfig,
+}
+
+/// Reads a station dataset, either a JSON array or a CSV file with a `code,name,name_latin` header.
+fn read_stations_file(path: &Path) -> Result<Vec<Station>, String> {
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension == "json");
+    if is_json {
+        let body = match std::fs::read_to_string(path) {
+            Ok(body) => body,
+            Err(err) => return Err(err.to_string()),
+        };
+        return match serde_json::from_str::<Vec<Station>>(body.as_str()) {
+            Ok(stations) => Ok(stations),
+            Err(err) => Err(err.to_string()),
+        };
+    }
+
+    let mut reader = match csv::Reader::from_path(path) {
+        Ok(reader) => reader,
+        Err(err) => return Err(err.to_string()),
+    };
+    match reader.deserialize::<Station>().collect() {
+        Ok(stations) => Ok(stations),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+impl StationsService {
+    pub fn init(db: DBConfig) -> Self {
+        Self { db }
+    }
+
+    pub async fn search_stations(
+        &self,
+        query: String,
+        limit: usize,
+    ) -> Result<Vec<Station>, StationsServiceError> {
+        let r = search_stations(self.db.get_connection().await, query, limit).await;
+
+        match r {
+            Ok(stations) => Ok(stations),
+            Err(err) => Err(StationsServiceError::StationsDBError(err)),
+        }
+    }
+
+    pub async fn import_stations(&self, path: &Path) -> Result<usize, StationsServiceError> {
+        let stations = match read_stations_file(path) {
+            Ok(stations) => stations,
+            Err(err) => return Err(StationsServiceError::ImportError(err)),
+        };
+        // Tasks are checked against the directory, so it must only hold codes tasks accept
+        let invalid_codes: Vec<&str> = stations
+            .iter()
+            .filter(|station| validate_station_code(station.code.as_str()).is_err())
+            .map(|station| station.code.as_str())
+            .collect();
+        if !invalid_codes.is_empty() {
+            return Err(StationsServiceError::ImportError(format!(
+                "Invalid station codes: {}",
+                invalid_codes.join(", ")
+            )));
+        }
+        let conn = self.db.get_connection().await;
+
+        let mut imported = 0;
+        for chunk in stations.chunks(IMPORT_CHUNK_SIZE) {
+            match upsert_stations(conn.clone(), chunk.to_vec()).await {
+                Ok(count) => imported += count,
+                Err(err) => return Err(StationsServiceError::StationsDBError(err)),
+            }
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db::test_db_config;
+
+    fn write_dataset(name: &str, body: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{name}", std::process::id()));
+        std::fs::write(&path, body).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn import_rejects_invalid_station_codes() {
+        let service = StationsService::init(test_db_config().await);
+        let path = write_dataset(
+            "stations.csv",
+            "code,name,name_latin\n2000000,Москва,Moskva\n20040,Санкт-Петербург,Sankt-Peterburg\n",
+        );
+
+        let r = service.import_stations(&path).await;
+        assert!(matches!(r, Err(StationsServiceError::ImportError(err)) if err.contains("20040")));
+        assert!(service
+            .search_stations(String::from("моск"), 10)
+            .await
+            .unwrap()
+            .is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
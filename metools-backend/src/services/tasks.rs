@@ -1,37 +1,114 @@
-use std::collections::HashMap;
-
+use chrono::{DateTime, Utc};
 use derive_more::Display;
-use surrealdb::sql::Thing;
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
+use validator::ValidationError;
 
 use crate::{
-    config::DBConfig,
-    models::rzd::tasks::{
-        delete_all_tasks_for_user, delete_task_by_id_for_user, insert_new_task,
-        list_all_users_tasks, Task, TasksDBError,
+    config::{DBConfig, TaskQuota, TaskQuotas},
+    models::{
+        notifications::TaskNotificationOverrides,
+        rzd::{
+            stations::{find_unknown_station_codes, has_stations, StationsDBError},
+            task_checks::{list_task_checks, TaskCheckRecord, TaskChecksDBError},
+            task_prices::{list_task_prices, TaskPrice, TaskPricesDBError},
+            tasks::{
+                activate_task_for_user, delete_all_tasks_for_user, delete_task_by_id_for_user,
+                get_task_by_id_for_user, insert_new_task, list_users_tasks,
+                set_task_notifications_for_user, set_task_status, update_task_for_user,
+                validate_task_filters, PriceAlert, Task, TaskData, TaskFilters, TaskSettings,
+                TaskStatus, TasksDBError, TasksQuery,
+            },
+        },
+        users::{get_user_by_id, UsersDBError},
     },
+    services::events::{TaskEventKind, TaskEventsService},
+    utils::pagination::{split_page, Cursor},
 };
 
 #[derive(Debug, Display)]
 pub enum TasksServiceError {
     TasksDBError(TasksDBError),
-    UnknownError,
+    InvalidTaskStatus(TaskStatus),
+    StationsDBError(StationsDBError),
+    TaskChecksDBError(TaskChecksDBError),
+    TaskPricesDBError(TaskPricesDBError),
+    #[display(fmt = "UnknownStationCodes({:?})", _0)]
+    UnknownStationCodes(Vec<String>),
+    InvalidTaskFilters(ValidationError),
+    UsersDBError(UsersDBError),
+    ActiveTasksLimitReached(usize),
+    CheckIntervalTooShort(u64),
+}
+async fn check_station_codes(
+    conn: Surreal<Any>,
+    task_data: &TaskData,
+) -> Result<(), TasksServiceError> {
+    // Until stations are imported every code would look unknown
+    match has_stations(conn.clone()).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(()),
+        Err(err) => return Err(TasksServiceError::StationsDBError(err)),
+    }
+    match find_unknown_station_codes(conn, task_data.station_codes()).await {
+        Ok(codes) if !codes.is_empty() => Err(TasksServiceError::UnknownStationCodes(codes)),
+        Ok(_) => Ok(()),
+        Err(err) => Err(TasksServiceError::StationsDBError(err)),
+    }
+}
+
+/// Reports writes refused by the active tasks quota as such.
+fn tasks_db_error(err: TasksDBError) -> TasksServiceError {
+    match err {
+        TasksDBError::ActiveTasksLimitReached(limit) => {
+            TasksServiceError::ActiveTasksLimitReached(limit)
+        }
+        err => TasksServiceError::TasksDBError(err),
+    }
 }
+
 #[derive(Clone)]
 pub struct TasksService {
     db: DBConfig,
+    quotas: TaskQuotas,
+    events: TaskEventsService,
 }
 
 impl TasksService {
-    pub fn init(db: DBConfig) -> Self {
-        Self { db }
+    pub fn init(db: DBConfig, quotas: TaskQuotas, events: TaskEventsService) -> Self {
+        Self { db, quotas, events }
     }
+
+    async fn get_user_quota(
+        &self,
+        conn: Surreal<Any>,
+        user_id: Thing,
+    ) -> Result<&TaskQuota, TasksServiceError> {
+        match get_user_by_id(conn, user_id).await {
+            Ok(user) => Ok(self.quotas.for_role(user.role.as_str())),
+            Err(err) => Err(TasksServiceError::UsersDBError(err)),
+        }
+    }
+
+    /// Returns a page of tasks and the cursor of the next page if there is one.
     pub async fn list_tasks_for_user(
         &self,
         user_id: Thing,
-    ) -> Result<Vec<Task>, TasksServiceError> {
-        let tasks = list_all_users_tasks(self.db.get_connection().await, user_id).await;
+        query: TasksQuery,
+    ) -> Result<(Vec<Task>, Option<String>), TasksServiceError> {
+        let limit = query.limit;
+        let tasks = list_users_tasks(
+            self.db.get_connection().await,
+            user_id,
+            TasksQuery {
+                limit: limit + 1,
+                ..query
+            },
+        )
+        .await;
         match tasks {
-            Ok(tasks) => Ok(tasks),
+            Ok(tasks) => Ok(split_page(tasks, limit, |task| {
+                Cursor::new(&task.created_at, &task.id)
+            })),
             Err(err) => Err(TasksServiceError::TasksDBError(err)),
         }
     }
@@ -39,20 +116,30 @@ impl TasksService {
     pub async fn create_task_for_user(
         &self,
         user_id: Thing,
-        task_type: String,
-        task_data: HashMap<String, String>,
+        task_data: TaskData,
+        filters: TaskFilters,
+        price_alert: Option<PriceAlert>,
+        check_interval: Option<u64>,
     ) -> Result<Task, TasksServiceError> {
-        let r = insert_new_task(
-            self.db.get_connection().await,
-            user_id,
-            task_type,
-            task_data,
-        )
-        .await;
+        let conn = self.db.get_connection().await;
+        let quota = self.get_user_quota(conn.clone(), user_id.clone()).await?;
+        if check_interval.is_some_and(|check_interval| check_interval < quota.min_check_interval) {
+            return Err(TasksServiceError::CheckIntervalTooShort(
+                quota.min_check_interval,
+            ));
+        }
+        check_station_codes(conn.clone(), &task_data).await?;
+
+        let settings = TaskSettings {
+            filters,
+            price_alert,
+            check_interval: Some(check_interval.unwrap_or(quota.min_check_interval)),
+        };
+        let r = insert_new_task(conn, user_id, task_data, settings, quota.max_active_tasks).await;
 
         match r {
             Ok(task) => Ok(task),
-            Err(err) => Err(TasksServiceError::TasksDBError(err)),
+            Err(err) => Err(tasks_db_error(err)),
         }
     }
 
@@ -80,4 +167,499 @@ impl TasksService {
             Err(err) => Err(TasksServiceError::TasksDBError(err)),
         }
     }
+
+    pub async fn get_task_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+    ) -> Result<Task, TasksServiceError> {
+        let r = get_task_by_id_for_user(self.db.get_connection().await, user_id, task_id).await;
+
+        match r {
+            Ok(task) => Ok(task),
+            Err(err) => Err(TasksServiceError::TasksDBError(err)),
+        }
+    }
+
+    /// Applies the given changes on top of the stored task, `None` keeps the current value.
+    pub async fn update_task_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+        task_data: Option<TaskData>,
+        filters: Option<TaskFilters>,
+        price_alert: Option<Option<PriceAlert>>,
+        check_interval: Option<u64>,
+    ) -> Result<Task, TasksServiceError> {
+        let conn = self.db.get_connection().await;
+        let task = match get_task_by_id_for_user(conn.clone(), user_id.clone(), task_id).await {
+            Ok(task) => task,
+            Err(err) => return Err(TasksServiceError::TasksDBError(err)),
+        };
+
+        let reset_checks = task_data.is_some();
+        let quota = self.get_user_quota(conn.clone(), user_id.clone()).await?;
+        if check_interval.is_some_and(|check_interval| check_interval < quota.min_check_interval) {
+            return Err(TasksServiceError::CheckIntervalTooShort(
+                quota.min_check_interval,
+            ));
+        }
+        if let Some(task_data) = &task_data {
+            check_station_codes(conn.clone(), task_data).await?;
+        }
+        let task_data = task_data.unwrap_or(task.data);
+        let filters = filters.unwrap_or(task.filters);
+        let price_alert = price_alert.unwrap_or(task.price_alert);
+        if let Err(err) = validate_task_filters(&task_data, &filters) {
+            return Err(TasksServiceError::InvalidTaskFilters(err));
+        }
+        let settings = TaskSettings {
+            filters,
+            price_alert,
+            check_interval: check_interval.or(task.check_interval),
+        };
+
+        let r = update_task_for_user(
+            conn,
+            user_id.clone(),
+            task.id,
+            task_data,
+            settings,
+            reset_checks,
+            quota.max_active_tasks,
+        )
+        .await;
+
+        match r {
+            Ok(updated) => {
+                // A new route reactivates finished tasks
+                if updated.status != task.status {
+                    self.events.publish(
+                        user_id,
+                        updated.id.clone(),
+                        TaskEventKind::StatusChanged {
+                            status: updated.status.clone(),
+                        },
+                    );
+                }
+                Ok(updated)
+            }
+            Err(err) => Err(tasks_db_error(err)),
+        }
+    }
+
+    /// Replaces the task's overrides of the user's notification settings.
+    pub async fn set_task_notifications_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+        notifications: TaskNotificationOverrides,
+    ) -> Result<Task, TasksServiceError> {
+        let r = set_task_notifications_for_user(
+            self.db.get_connection().await,
+            user_id,
+            task_id,
+            notifications,
+        )
+        .await;
+
+        match r {
+            Ok(task) => Ok(task),
+            Err(err) => Err(TasksServiceError::TasksDBError(err)),
+        }
+    }
+
+    async fn change_task_status_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+        allowed_statuses: &[TaskStatus],
+        new_status: TaskStatus,
+    ) -> Result<(), TasksServiceError> {
+        let conn = self.db.get_connection().await;
+        let task = match get_task_by_id_for_user(conn.clone(), user_id.clone(), task_id).await {
+            Ok(task) => task,
+            Err(err) => return Err(TasksServiceError::TasksDBError(err)),
+        };
+        if !allowed_statuses.contains(&task.status) {
+            return Err(TasksServiceError::InvalidTaskStatus(task.status));
+        }
+
+        let r = if new_status == TaskStatus::Active {
+            let quota = self.get_user_quota(conn.clone(), user_id.clone()).await?;
+            activate_task_for_user(
+                &conn,
+                user_id.clone(),
+                task.id.clone(),
+                quota.max_active_tasks,
+            )
+            .await
+        } else {
+            set_task_status(&conn, task.id.clone(), new_status.clone()).await
+        };
+
+        match r {
+            Ok(()) => {
+                self.events.publish(
+                    user_id,
+                    task.id,
+                    TaskEventKind::StatusChanged { status: new_status },
+                );
+                Ok(())
+            }
+            Err(err) => Err(tasks_db_error(err)),
+        }
+    }
+
+    pub async fn pause_task_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+    ) -> Result<(), TasksServiceError> {
+        self.change_task_status_for_user(
+            user_id,
+            task_id,
+            &[TaskStatus::Active],
+            TaskStatus::Paused,
+        )
+        .await
+    }
+
+    pub async fn resume_task_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+    ) -> Result<(), TasksServiceError> {
+        self.change_task_status_for_user(
+            user_id,
+            task_id,
+            &[TaskStatus::Paused, TaskStatus::Failed],
+            TaskStatus::Active,
+        )
+        .await
+    }
+
+    /// Returns a page of check history and the cursor of the next page if there is one.
+    pub async fn list_task_checks_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+        cursor: Option<Cursor>,
+        limit: usize,
+    ) -> Result<(Vec<TaskCheckRecord>, Option<String>), TasksServiceError> {
+        let conn = self.db.get_connection().await;
+        let task = match get_task_by_id_for_user(conn.clone(), user_id, task_id).await {
+            Ok(task) => task,
+            Err(err) => return Err(TasksServiceError::TasksDBError(err)),
+        };
+
+        let r = list_task_checks(conn, task.id, cursor, limit + 1).await;
+
+        match r {
+            Ok(checks) => Ok(split_page(checks, limit, |check| {
+                Cursor::new(&check.created_at, &check.id)
+            })),
+            Err(err) => Err(TasksServiceError::TaskChecksDBError(err)),
+        }
+    }
+
+    pub async fn list_task_prices_for_user(
+        &self,
+        user_id: Thing,
+        task_id: Thing,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<TaskPrice>, TasksServiceError> {
+        let conn = self.db.get_connection().await;
+        let task = match get_task_by_id_for_user(conn.clone(), user_id, task_id).await {
+            Ok(task) => task,
+            Err(err) => return Err(TasksServiceError::TasksDBError(err)),
+        };
+
+        let r = list_task_prices(conn, task.id, since).await;
+
+        match r {
+            Ok(prices) => Ok(prices),
+            Err(err) => Err(TasksServiceError::TaskPricesDBError(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Days;
+
+    use super::*;
+    use crate::{
+        models::{
+            rzd::{
+                stations::{upsert_stations, Station},
+                task_checks::insert_task_check,
+                tasks::{DayTask, TaskCheck, TaskCheckStatus},
+            },
+            users::insert_new_user,
+        },
+        utils::{pagination::EncodedCursor, test_db::test_db_config},
+    };
+
+    fn quotas(max_active_tasks: usize) -> TaskQuotas {
+        let quota = TaskQuota {
+            max_active_tasks,
+            min_check_interval: 300,
+        };
+        TaskQuotas {
+            user: quota.clone(),
+            premium: quota,
+        }
+    }
+
+    fn day_task(to_point_code: &str) -> TaskData {
+        TaskData::Day(DayTask {
+            from_point_code: String::from("2004000"),
+            to_point_code: String::from(to_point_code),
+            date: Utc::now().date_naive() + Days::new(1),
+        })
+    }
+
+    async fn seed_stations(service: &TasksService) {
+        let stations = ["2004000", "2000000", "2010000"]
+            .into_iter()
+            .map(|code| Station {
+                code: String::from(code),
+                name: format!("Станция {code}"),
+                name_latin: format!("Station {code}"),
+            })
+            .collect();
+        upsert_stations(service.db.get_connection().await, stations)
+            .await
+            .unwrap();
+    }
+
+    async fn service(max_active_tasks: usize) -> (TasksService, Thing) {
+        let db = test_db_config().await;
+        let conn = db.get_connection().await;
+        let user = insert_new_user(
+            &conn,
+            String::from("user"),
+            String::from("user@example.com"),
+            String::from("password"),
+        )
+        .await
+        .unwrap();
+        let service = TasksService::init(db, quotas(max_active_tasks), TaskEventsService::init());
+        (service, user.id)
+    }
+
+    #[tokio::test]
+    async fn create_applies_plan_quota() {
+        let (service, user_id) = service(1).await;
+        let create = |check_interval: Option<u64>| {
+            service.create_task_for_user(
+                user_id.clone(),
+                day_task("2000000"),
+                TaskFilters::default(),
+                None,
+                check_interval,
+            )
+        };
+
+        let r = create(Some(60)).await;
+        assert!(matches!(
+            r,
+            Err(TasksServiceError::CheckIntervalTooShort(300))
+        ));
+        // The plan's shortest interval is the default
+        let task = create(None).await.unwrap();
+        assert_eq!(task.check_interval, Some(300));
+        let r = create(Some(600)).await;
+        assert!(matches!(
+            r,
+            Err(TasksServiceError::ActiveTasksLimitReached(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn update_and_resume_apply_plan_quota() {
+        let (service, user_id) = service(1).await;
+        let create = || {
+            service.create_task_for_user(
+                user_id.clone(),
+                day_task("2000000"),
+                TaskFilters::default(),
+                None,
+                None,
+            )
+        };
+        let paused = create().await.unwrap();
+
+        let r = service
+            .update_task_for_user(
+                user_id.clone(),
+                paused.id.clone(),
+                None,
+                None,
+                None,
+                Some(60),
+            )
+            .await;
+        assert!(matches!(
+            r,
+            Err(TasksServiceError::CheckIntervalTooShort(300))
+        ));
+
+        // Paused tasks leave room for another active one
+        service
+            .pause_task_for_user(user_id.clone(), paused.id.clone())
+            .await
+            .unwrap();
+        create().await.unwrap();
+        let r = service
+            .resume_task_for_user(user_id.clone(), paused.id.clone())
+            .await;
+        assert!(matches!(
+            r,
+            Err(TasksServiceError::ActiveTasksLimitReached(1))
+        ));
+        let task = service.get_task_for_user(user_id, paused.id).await.unwrap();
+        assert_eq!(task.status, TaskStatus::Paused);
+    }
+
+    #[tokio::test]
+    async fn station_codes_are_checked_once_stations_are_imported() {
+        let (service, user_id) = service(10).await;
+        let create = |to_point_code: &'static str| {
+            service.create_task_for_user(
+                user_id.clone(),
+                day_task(to_point_code),
+                TaskFilters::default(),
+                None,
+                None,
+            )
+        };
+        assert!(create("2060001").await.is_ok());
+
+        seed_stations(&service).await;
+        assert!(create("2000000").await.is_ok());
+        let r = create("2060001").await;
+        assert!(
+            matches!(r, Err(TasksServiceError::UnknownStationCodes(codes)) if codes == ["2060001"])
+        );
+    }
+
+    #[tokio::test]
+    async fn new_route_reactivates_finished_task_and_publishes_status() {
+        let (service, user_id) = service(10).await;
+        seed_stations(&service).await;
+        let task = service
+            .create_task_for_user(
+                user_id.clone(),
+                day_task("2000000"),
+                TaskFilters::default(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let conn = service.db.get_connection().await;
+        set_task_status(&conn, task.id.clone(), TaskStatus::Fulfilled)
+            .await
+            .unwrap();
+        let mut events = service.events.receiver();
+
+        let task = service
+            .update_task_for_user(
+                user_id.clone(),
+                task.id,
+                Some(day_task("2010000")),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(task.status, TaskStatus::Active);
+
+        let (event_user_id, event) = events.try_recv().unwrap();
+        assert_eq!(event_user_id, user_id);
+        assert!(matches!(
+            event.kind,
+            TaskEventKind::StatusChanged {
+                status: TaskStatus::Active
+            }
+        ));
+
+        // Settings alone keep the status and publish nothing
+        service
+            .update_task_for_user(user_id, task.id, None, None, None, Some(600))
+            .await
+            .unwrap();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn check_history_pages_walk_all_checks_newest_first() {
+        let (service, user_id) = service(10).await;
+        let task = service
+            .create_task_for_user(
+                user_id.clone(),
+                day_task("2000000"),
+                TaskFilters::default(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let conn = service.db.get_connection().await;
+        for _ in 0..5 {
+            let check = TaskCheck {
+                status: TaskCheckStatus::NoSeats,
+                trains: Vec::new(),
+                available_dates: Vec::new(),
+                legs: Vec::new(),
+                error: None,
+            };
+            insert_task_check(&conn, task.id.clone(), check, false)
+                .await
+                .unwrap();
+        }
+
+        let mut checks: Vec<TaskCheckRecord> = Vec::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let (page, next_cursor) = service
+                .list_task_checks_for_user(user_id.clone(), task.id.clone(), cursor, 2)
+                .await
+                .unwrap();
+            pages += 1;
+            assert!(page.len() <= 2);
+            checks.extend(page);
+            match next_cursor {
+                Some(next_cursor) => {
+                    let next_cursor: EncodedCursor =
+                        serde_json::from_value(serde_json::json!(next_cursor)).unwrap();
+                    cursor = Some(next_cursor.0);
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(checks.len(), 5);
+        for pair in checks.windows(2) {
+            let (newer, older) = (&pair[0], &pair[1]);
+            assert!(
+                newer.created_at > older.created_at
+                    || (newer.created_at == older.created_at && newer.id > older.id)
+            );
+        }
+
+        // Other users cannot read the history
+        let r = service
+            .list_task_checks_for_user(Thing::from(("users", "other")), task.id, None, 2)
+            .await;
+        assert!(matches!(
+            r,
+            Err(TasksServiceError::TasksDBError(TasksDBError::TaskNotFound))
+        ));
+    }
 }
@@ -0,0 +1,443 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use derive_more::Display;
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
+use uuid::Uuid;
+
+use crate::{
+    config::DBConfig,
+    models::{
+        notifications::NotificationContent,
+        rzd::tasks::{AvailableTrain, PriceDrop, TripLeg},
+        telegram::{
+            create_telegram_link_code, delete_telegram_chat_by_chat_id,
+            delete_telegram_chat_for_user, get_telegram_chat_for_user, link_telegram_chat,
+            take_telegram_link_code, TelegramChat, TelegramDBError,
+        },
+    },
+    services::telegram::client::{Message, TelegramClient, TelegramClientError},
+};
+
+const LINK_CODE_TTL_MINUTES: i64 = 15;
+
+#[derive(Debug, Display)]
+pub enum TelegramServiceError {
+    TelegramDBError(TelegramDBError),
+    TelegramClientError(TelegramClientError),
+}
+
+/// One-time code the user sends to the bot to link their chat.
+pub struct TelegramLink {
+    pub code: String,
+    /// Deep link opening the bot with the code already filled in.
+    pub link: String,
+    pub valid_until: DateTime<Utc>,
+}
+
+pub fn format_task_availability(trains: &[AvailableTrain]) -> String {
+    let mut text = String::from("Seats are available for your task:\n");
+    let mut current_leg: Option<TripLeg> = None;
+    for train in trains {
+        if train.leg != current_leg {
+            match train.leg {
+                Some(TripLeg::Outbound) => text.push_str("\nOutbound\n"),
+                Some(TripLeg::Return) => text.push_str("\nReturn\n"),
+                None => {}
+            }
+            current_leg = train.leg.clone();
+        }
+        text.push_str(
+            format!(
+                "\nTrain {}, departure {} {}, arrival {} {}\n",
+                train.number,
+                train.departure_date,
+                train.departure_time,
+                train.arrival_date,
+                train.arrival_time
+            )
+            .as_str(),
+        );
+        for car_class in train.car_classes.iter() {
+            text.push_str(
+                format!(
+                    "{}: {} seats from {} RUB\n",
+                    car_class.car_type, car_class.free_seats, car_class.min_price
+                )
+                .as_str(),
+            );
+        }
+    }
+    text
+}
+
+/// Puts queued notifications in one message.
+pub fn format_digest(contents: &[NotificationContent]) -> String {
+    let text: Vec<String> = contents
+        .iter()
+        .map(|content| match content {
+            NotificationContent::Availability { trains } => format_task_availability(trains),
+            NotificationContent::PriceDrops { drops } => format_price_drops(drops),
+        })
+        .collect();
+    text.join("\n")
+}
+
+pub fn format_price_drops(drops: &[PriceDrop]) -> String {
+    let mut text = String::from("Prices dropped for your task:\n");
+    for drop in drops {
+        text.push_str(
+            format!(
+                "{} {}: from {} RUB, was {} RUB\n",
+                drop.key.date_label(),
+                drop.key.car_type,
+                drop.price,
+                drop.baseline_price
+            )
+            .as_str(),
+        );
+    }
+    text
+}
+
+#[derive(Clone)]
+pub struct TelegramService {
+    db: DBConfig,
+    client: Arc<dyn TelegramClient>,
+    bot_username: String,
+}
+
+impl TelegramService {
+    pub fn init(db: DBConfig, client: Arc<dyn TelegramClient>, bot_username: String) -> Self {
+        Self {
+            db,
+            client,
+            bot_username,
+        }
+    }
+
+    pub async fn create_link_for_user(
+        &self,
+        user_id: Thing,
+    ) -> Result<TelegramLink, TelegramServiceError> {
+        let code = Uuid::new_v4().simple().to_string();
+        let valid_until = Utc::now() + Duration::minutes(LINK_CODE_TTL_MINUTES);
+
+        let r = create_telegram_link_code(
+            &self.db.get_connection().await,
+            user_id,
+            code.clone(),
+            valid_until,
+        )
+        .await;
+
+        match r {
+            Ok(_) => Ok(TelegramLink {
+                link: format!("https://t.me/{}?start={}", self.bot_username, code),
+                code,
+                valid_until,
+            }),
+            Err(err) => Err(TelegramServiceError::TelegramDBError(err)),
+        }
+    }
+
+    pub async fn get_chat_for_user(
+        &self,
+        user_id: Thing,
+    ) -> Result<TelegramChat, TelegramServiceError> {
+        let r = get_telegram_chat_for_user(&self.db.get_connection().await, user_id).await;
+
+        match r {
+            Ok(chat) => Ok(chat),
+            Err(err) => Err(TelegramServiceError::TelegramDBError(err)),
+        }
+    }
+
+    pub async fn unlink_chat_for_user(&self, user_id: Thing) -> Result<(), TelegramServiceError> {
+        let r = delete_telegram_chat_for_user(&self.db.get_connection().await, user_id).await;
+
+        match r {
+            Ok(()) => Ok(()),
+            Err(err) => Err(TelegramServiceError::TelegramDBError(err)),
+        }
+    }
+
+    async fn reply(&self, chat_id: i64, text: &str) {
+        if let Err(err) = self.client.send_message(chat_id, text).await {
+            log::error!("Error on replying to telegram chat {chat_id}: {err}");
+        }
+    }
+
+    /// Handles `/start <code>` sent from the deep link and `/stop`.
+    async fn handle_message(&self, conn: &Surreal<Any>, message: Message) {
+        let chat_id = message.chat.id;
+        let text = message.text.unwrap_or_default();
+        let mut words = text.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some("/start"), Some(code)) => {
+                let user_id = match take_telegram_link_code(conn, code.to_string()).await {
+                    Ok(user_id) => user_id,
+                    Err(TelegramDBError::LinkCodeNotFound) => {
+                        self.reply(
+                            chat_id,
+                            "The link code is invalid or expired, get a new one in MeTools.",
+                        )
+                        .await;
+                        return;
+                    }
+                    Err(err) => {
+                        log::error!("Error on taking telegram link code: {err}");
+                        return;
+                    }
+                };
+                match link_telegram_chat(conn, user_id, chat_id, message.chat.username).await {
+                    Ok(_) => {
+                        self.reply(
+                            chat_id,
+                            "The chat is linked, alerts about your tasks will come here.",
+                        )
+                        .await
+                    }
+                    Err(err) => log::error!("Error on linking telegram chat {chat_id}: {err}"),
+                }
+            }
+            (Some("/start"), None) => {
+                self.reply(
+                    chat_id,
+                    "Link Telegram in MeTools to get alerts about your tasks here.",
+                )
+                .await
+            }
+            (Some("/stop"), _) => match delete_telegram_chat_by_chat_id(conn, chat_id).await {
+                Ok(()) | Err(TelegramDBError::ChatNotFound) => {
+                    self.reply(
+                        chat_id,
+                        "The chat is unlinked, no more alerts will come here.",
+                    )
+                    .await
+                }
+                Err(err) => log::error!("Error on unlinking telegram chat {chat_id}: {err}"),
+            },
+            _ => {}
+        }
+    }
+
+    /// Handles one batch of bot updates and returns the offset of the next batch.
+    pub async fn process_updates(
+        &self,
+        offset: Option<i64>,
+    ) -> Result<Option<i64>, TelegramServiceError> {
+        let updates = match self.client.get_updates(offset).await {
+            Ok(updates) => updates,
+            Err(err) => return Err(TelegramServiceError::TelegramClientError(err)),
+        };
+
+        let conn = self.db.get_connection().await;
+        let mut next_offset = offset;
+        for update in updates {
+            next_offset = Some(update.update_id + 1);
+            if let Some(message) = update.message {
+                self.handle_message(&conn, message).await;
+            }
+        }
+        Ok(next_offset)
+    }
+
+    /// Sends a message to the user's linked chat, returns whether there is one.
+    pub async fn send_to_user(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+        text: &str,
+    ) -> Result<bool, TelegramServiceError> {
+        let chat = match get_telegram_chat_for_user(conn, user_id).await {
+            Ok(chat) => chat,
+            Err(TelegramDBError::ChatNotFound) => return Ok(false),
+            Err(err) => return Err(TelegramServiceError::TelegramDBError(err)),
+        };
+
+        match self.client.send_message(chat.chat_id, text).await {
+            Ok(()) => Ok(true),
+            Err(err) => Err(TelegramServiceError::TelegramClientError(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use surrealdb::sql::Thing;
+
+    use super::TelegramService;
+    use crate::{
+        models::telegram::TelegramDBError,
+        services::telegram::{
+            bot::TelegramServiceError,
+            client::{Chat, Message, TelegramClient, TelegramClientError, Update},
+        },
+        utils::test_db::test_db_config,
+    };
+
+    const CHAT_ID: i64 = 42;
+
+    /// Stand-in for the Bot API handing out scripted update batches and recording replies.
+    #[derive(Default)]
+    struct FakeTelegramClient {
+        updates: Mutex<VecDeque<Vec<Update>>>,
+        sent: Mutex<Vec<(i64, String)>>,
+    }
+
+    impl FakeTelegramClient {
+        fn push_message(&self, update_id: i64, text: &str) {
+            self.updates.lock().unwrap().push_back(vec![Update {
+                update_id,
+                message: Some(Message {
+                    chat: Chat {
+                        id: CHAT_ID,
+                        username: Some(String::from("user")),
+                    },
+                    text: Some(String::from(text)),
+                }),
+            }]);
+        }
+
+        fn last_sent(&self) -> Option<(i64, String)> {
+            self.sent.lock().unwrap().last().cloned()
+        }
+    }
+
+    #[async_trait]
+    impl TelegramClient for FakeTelegramClient {
+        async fn get_updates(
+            &self,
+            _offset: Option<i64>,
+        ) -> Result<Vec<Update>, TelegramClientError> {
+            Ok(self.updates.lock().unwrap().pop_front().unwrap_or_default())
+        }
+
+        async fn send_message(&self, chat_id: i64, text: &str) -> Result<(), TelegramClientError> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((chat_id, String::from(text)));
+            Ok(())
+        }
+    }
+
+    async fn service() -> (TelegramService, Arc<FakeTelegramClient>) {
+        let client = Arc::new(FakeTelegramClient::default());
+        let service = TelegramService::init(
+            test_db_config().await,
+            client.clone(),
+            String::from("metools_bot"),
+        );
+        (service, client)
+    }
+
+    fn user() -> Thing {
+        Thing::from(("users", "user"))
+    }
+
+    fn is_chat_not_found(r: Result<impl std::fmt::Debug, TelegramServiceError>) -> bool {
+        matches!(
+            r,
+            Err(TelegramServiceError::TelegramDBError(
+                TelegramDBError::ChatNotFound
+            ))
+        )
+    }
+
+    #[tokio::test]
+    async fn start_with_code_links_chat_once() {
+        let (service, client) = service().await;
+        let link = service.create_link_for_user(user()).await.unwrap();
+        assert_eq!(
+            link.link,
+            format!("https://t.me/metools_bot?start={}", link.code)
+        );
+
+        client.push_message(7, format!("/start {}", link.code).as_str());
+        let offset = service.process_updates(None).await.unwrap();
+
+        assert_eq!(offset, Some(8));
+        let chat = service.get_chat_for_user(user()).await.unwrap();
+        assert_eq!(chat.chat_id, CHAT_ID);
+        assert_eq!(chat.username.as_deref(), Some("user"));
+        assert_eq!(
+            client.last_sent(),
+            Some((
+                CHAT_ID,
+                String::from("The chat is linked, alerts about your tasks will come here.")
+            ))
+        );
+
+        // Codes are one-time
+        client.push_message(8, format!("/start {}", link.code).as_str());
+        service.process_updates(offset).await.unwrap();
+        assert_eq!(
+            client.last_sent(),
+            Some((
+                CHAT_ID,
+                String::from("The link code is invalid or expired, get a new one in MeTools.")
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn start_with_unknown_code_does_not_link_chat() {
+        let (service, client) = service().await;
+
+        client.push_message(1, "/start unknown");
+        service.process_updates(None).await.unwrap();
+
+        assert!(is_chat_not_found(service.get_chat_for_user(user()).await));
+        assert_eq!(
+            client.last_sent(),
+            Some((
+                CHAT_ID,
+                String::from("The link code is invalid or expired, get a new one in MeTools.")
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_unlinks_chat() {
+        let (service, client) = service().await;
+        let link = service.create_link_for_user(user()).await.unwrap();
+        client.push_message(1, format!("/start {}", link.code).as_str());
+        let offset = service.process_updates(None).await.unwrap();
+
+        client.push_message(2, "/stop");
+        service.process_updates(offset).await.unwrap();
+
+        assert!(is_chat_not_found(service.get_chat_for_user(user()).await));
+        assert_eq!(
+            client.last_sent(),
+            Some((
+                CHAT_ID,
+                String::from("The chat is unlinked, no more alerts will come here.")
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn unlink_chat_for_user_stops_messages() {
+        let (service, client) = service().await;
+        let link = service.create_link_for_user(user()).await.unwrap();
+        client.push_message(1, format!("/start {}", link.code).as_str());
+        service.process_updates(None).await.unwrap();
+
+        service.unlink_chat_for_user(user()).await.unwrap();
+
+        assert!(is_chat_not_found(service.get_chat_for_user(user()).await));
+        let conn = service.db.get_connection().await;
+        let sent = service.send_to_user(&conn, user(), "Tickets found").await;
+        assert!(matches!(sent, Ok(false)));
+    }
+}
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use derive_more::Display;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+/// How long `getUpdates` waits for new messages before returning an empty list, in seconds.
+const UPDATES_TIMEOUT: u64 = 30;
+/// Requests give up this long after `getUpdates` should have answered on its own, in seconds.
+const REQUEST_TIMEOUT: u64 = UPDATES_TIMEOUT + 10;
+
+#[derive(Debug, Display)]
+pub enum TelegramClientError {
+    RequestError(reqwest::Error),
+    DecodeError(serde_json::Error),
+    UnexpectedResponse(String),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Chat {
+    pub id: i64,
+    pub username: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Message {
+    pub chat: Chat,
+    pub text: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct BotApiResponse {
+    ok: bool,
+    description: Option<String>,
+    result: Option<Value>,
+}
+
+#[async_trait]
+pub trait TelegramClient: Send + Sync {
+    /// Long polls updates starting from `offset`, confirming all updates before it.
+    async fn get_updates(&self, offset: Option<i64>) -> Result<Vec<Update>, TelegramClientError>;
+
+    async fn send_message(&self, chat_id: i64, text: &str) -> Result<(), TelegramClientError>;
+}
+
+/// Bot API client, `base_url` can point to a local stand-in instead of `https://api.telegram.org`.
+#[derive(Clone)]
+pub struct HttpTelegramClient {
+    http: reqwest::Client,
+    base_url: String,
+    bot_token: String,
+}
+
+impl HttpTelegramClient {
+    pub fn init(base_url: String, bot_token: String) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT))
+                .build()
+                .unwrap(),
+            base_url,
+            bot_token,
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, TelegramClientError> {
+        let r = self
+            .http
+            .post(format!(
+                "{}/bot{}/{}",
+                self.base_url, self.bot_token, method
+            ))
+            .json(&params)
+            .send()
+            .await;
+
+        // The Bot API describes errors in the body, so it is read for any status.
+        // Request errors drop the URL as it contains the bot token and ends up in logs
+        let body = match r {
+            Ok(response) => match response.text().await {
+                Ok(body) => body,
+                Err(err) => return Err(TelegramClientError::RequestError(err.without_url())),
+            },
+            Err(err) => return Err(TelegramClientError::RequestError(err.without_url())),
+        };
+
+        let response = match serde_json::from_str::<BotApiResponse>(body.as_str()) {
+            Ok(response) => response,
+            Err(err) => return Err(TelegramClientError::DecodeError(err)),
+        };
+        if !response.ok {
+            return Err(TelegramClientError::UnexpectedResponse(
+                response.description.unwrap_or(format!("{method} failed")),
+            ));
+        }
+
+        match serde_json::from_value::<T>(response.result.unwrap_or(Value::Null)) {
+            Ok(result) => Ok(result),
+            Err(err) => Err(TelegramClientError::DecodeError(err)),
+        }
+    }
+}
+
+#[async_trait]
+impl TelegramClient for HttpTelegramClient {
+    async fn get_updates(&self, offset: Option<i64>) -> Result<Vec<Update>, TelegramClientError> {
+        self.call(
+            "getUpdates",
+            json!({
+                "offset": offset,
+                "timeout": UPDATES_TIMEOUT,
+                "allowed_updates": ["message"]
+            }),
+        )
+        .await
+    }
+
+    async fn send_message(&self, chat_id: i64, text: &str) -> Result<(), TelegramClientError> {
+        self.call::<Value>(
+            "sendMessage",
+            json!({
+                "chat_id": chat_id,
+                "text": text
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{HttpTelegramClient, TelegramClient, TelegramClientError};
+    use crate::utils::test_server::{ScriptedResponse, TestServer};
+
+    const TOKEN: &str = "123456:secret-token";
+
+    #[tokio::test]
+    async fn get_updates_posts_offset_and_reads_messages() {
+        let server = TestServer::start(vec![ScriptedResponse::json(
+            r#"{"ok":true,"result":[
+                {"update_id":10,"message":{"chat":{"id":42,"username":"user"},"text":"/start code"}},
+                {"update_id":11}
+            ]}"#,
+        )])
+        .await;
+        let client = HttpTelegramClient::init(server.url.clone(), String::from(TOKEN));
+
+        let updates = client.get_updates(Some(10)).await.unwrap();
+
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].update_id, 10);
+        let message = updates[0].message.as_ref().unwrap();
+        assert_eq!(message.chat.id, 42);
+        assert_eq!(message.text.as_deref(), Some("/start code"));
+        assert!(updates[1].message.is_none());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].target, format!("/bot{TOKEN}/getUpdates"));
+        let params: Value = serde_json::from_str(requests[0].body.as_str()).unwrap();
+        assert_eq!(params["offset"], 10);
+        assert_eq!(params["allowed_updates"][0], "message");
+    }
+
+    #[tokio::test]
+    async fn send_message_posts_chat_and_text() {
+        let server = TestServer::start(vec![ScriptedResponse::json(
+            r#"{"ok":true,"result":{"message_id":1}}"#,
+        )])
+        .await;
+        let client = HttpTelegramClient::init(server.url.clone(), String::from(TOKEN));
+
+        client.send_message(42, "Tickets found").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].target, format!("/bot{TOKEN}/sendMessage"));
+        let params: Value = serde_json::from_str(requests[0].body.as_str()).unwrap();
+        assert_eq!(params["chat_id"], 42);
+        assert_eq!(params["text"], "Tickets found");
+    }
+
+    #[tokio::test]
+    async fn send_message_reports_api_error_description() {
+        let mut response = ScriptedResponse::json(
+            r#"{"ok":false,"error_code":403,"description":"Forbidden: bot was blocked by the user"}"#,
+        );
+        response.status = 403;
+        let server = TestServer::start(vec![response]).await;
+        let client = HttpTelegramClient::init(server.url.clone(), String::from(TOKEN));
+
+        match client.send_message(42, "Tickets found").await {
+            Err(TelegramClientError::UnexpectedResponse(description)) => {
+                assert_eq!(description, "Forbidden: bot was blocked by the user")
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_errors_do_not_contain_token() {
+        // Nothing listens on the port once the listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{port}");
+        let client = HttpTelegramClient::init(url, String::from(TOKEN));
+
+        let err = client.get_updates(None).await.unwrap_err();
+
+        assert!(matches!(err, TelegramClientError::RequestError(_)));
+        assert!(!err.to_string().contains(TOKEN));
+        assert!(!format!("{err:?}").contains(TOKEN));
+    }
+}
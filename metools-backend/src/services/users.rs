@@ -4,7 +4,7 @@ use argon2::{
 use chrono::Days;
 use derive_more::Display;
 use rand_core::OsRng;
-use surrealdb::{sql::Thing, Error};
+use surrealdb::sql::Thing;
 use uuid::Uuid;
 
 use crate::{
@@ -19,7 +19,7 @@ use crate::{
             VerifyTokensDBError,
         },
     },
-    services::mailer::MailerService,
+    services::{dispatcher::NotificationsDispatcher, mailer::MailerService},
 };
 
 #[derive(Debug, Display)]
@@ -27,7 +27,6 @@ pub enum UsersServiceError {
     UsersDBError(UsersDBError),
     VerifyTokensDBError(VerifyTokensDBError),
     InvalidUserPassword,
-    CommitError(Error),
     UnknownError,
 }
 
@@ -35,11 +34,16 @@ pub enum UsersServiceError {
 pub struct UsersService {
     db: DBConfig,
     mailer: MailerService,
+    dispatcher: NotificationsDispatcher,
 }
 
 impl UsersService {
-    pub fn init(db: DBConfig, mailer: MailerService) -> Self {
-        Self { db, mailer }
+    pub fn init(db: DBConfig, mailer: MailerService, dispatcher: NotificationsDispatcher) -> Self {
+        Self {
+            db,
+            mailer,
+            dispatcher,
+        }
     }
 
     pub async fn register_user(
@@ -79,13 +83,20 @@ impl UsersService {
 
                 let r_verify_token = r_verify_token.unwrap();
 
+                // The mail is sent by the dispatcher, so signup does not depend on SMTP
                 let r_email = self
-                    .mailer
-                    .send_verification_mail(email, r_verify_token.token.0);
+                    .dispatcher
+                    .enqueue_email(
+                        &conn,
+                        Some(r_user.id.clone()),
+                        email,
+                        self.mailer.verification_mail(r_verify_token.token.0),
+                    )
+                    .await;
                 match r_email {
                     Ok(_) => Ok(r_user),
                     Err(err) => {
-                        log::error!("Error on sending email: {err}");
+                        log::error!("Error on queueing verification email: {err}");
                         Err(UsersServiceError::UnknownError)
                     }
                 }
@@ -109,7 +120,7 @@ impl UsersService {
                 let parsed_hash = PasswordHash::new(user.password.as_str()).unwrap();
                 let is_valid = Argon2::default()
                     .verify_password(password.as_bytes(), &parsed_hash)
-                    .map_or(false, |_| true);
+                    .is_ok();
                 if !is_valid {
                     return Err(UsersServiceError::InvalidUserPassword);
                 }
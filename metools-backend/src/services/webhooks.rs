@@ -0,0 +1,624 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{TimeDelta, Utc};
+use derive_more::Display;
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    Url,
+};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
+
+use crate::{
+    config::DBConfig,
+    models::webhooks::{
+        create_webhook, delete_webhook_for_user, get_webhook_for_user, get_webhook_secret,
+        insert_webhook_deliveries, list_due_webhook_deliveries, list_webhook_deliveries,
+        list_webhooks_for_user, set_webhook_delivery_attempt, set_webhook_secret,
+        DueWebhookDelivery, NewWebhookDelivery, Webhook, WebhookDelivery, WebhookDeliveryAttempt,
+        WebhookDeliveryStatus, WebhooksDBError,
+    },
+    utils::pagination::{split_page, Cursor},
+};
+
+pub const EVENT_HEADER: &str = "X-MeTools-Event";
+pub const DELIVERY_HEADER: &str = "X-MeTools-Delivery";
+pub const TIMESTAMP_HEADER: &str = "X-MeTools-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-MeTools-Signature";
+
+const MAX_WEBHOOKS_PER_USER: usize = 10;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const DUE_DELIVERIES_BATCH: usize = 100;
+
+#[derive(Debug, Display)]
+pub enum WebhooksServiceError {
+    WebhooksDBError(WebhooksDBError),
+    WebhooksLimitReached(usize),
+    #[display(fmt = "webhook URL is not allowed: {}", _0)]
+    ForbiddenUrl(String),
+}
+
+/// Whether deliveries may connect to the address. Loopback, private, link-local and other
+/// addresses of the network the service runs in are refused.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || first == 0
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (first == 100 && (64..128).contains(&second)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Addresses of the host, refused when any of them is not public.
+async fn resolve_public_host(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => addrs.collect(),
+        Err(err) => return Err(format!("cant resolve {host}: {err}")),
+    };
+    if addrs.is_empty() {
+        return Err(format!("{host} has no addresses"));
+    }
+    match addrs.iter().find(|addr| !is_public_address(addr.ip())) {
+        Some(addr) => Err(format!("{host} resolves to non-public {}", addr.ip())),
+        None => Ok(addrs),
+    }
+}
+
+/// Checks that the URL is http(s) and its host resolves to public addresses only.
+pub async fn check_webhook_url(url: &str) -> Result<(), String> {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(err) => return Err(err.to_string()),
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("unsupported scheme {}", url.scheme()));
+    }
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return Err(String::from("no host")),
+    };
+
+    resolve_public_host(host, url.port_or_known_default().unwrap_or(80))
+        .await
+        .map(|_| ())
+}
+
+/// Resolver of the delivery client, it refuses non-public addresses on every connection so that
+/// a host cannot be pointed inside the network after its URL was checked.
+struct PublicHostResolver;
+
+impl Resolve for PublicHostResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            match resolve_public_host(name.as_str(), 0).await {
+                Ok(addrs) => Ok(Box::new(addrs.into_iter()) as Addrs),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+}
+
+/// Signs `{timestamp}.{body}` so that receivers can also reject replayed deliveries.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[derive(Clone)]
+pub struct WebhooksService {
+    db: DBConfig,
+    http: reqwest::Client,
+    max_attempts: u32,
+    retry_delay: u64,
+    /// Lets tests deliver to a stand-in on the loopback interface.
+    #[cfg(test)]
+    allow_loopback: bool,
+}
+
+impl WebhooksService {
+    pub fn init(db: DBConfig, max_attempts: u32, retry_delay: u64) -> Self {
+        Self {
+            db,
+            // Redirects are not followed, they could lead inside the network
+            http: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .redirect(Policy::none())
+                .dns_resolver(Arc::new(PublicHostResolver))
+                .build()
+                .unwrap(),
+            max_attempts,
+            retry_delay,
+            #[cfg(test)]
+            allow_loopback: false,
+        }
+    }
+
+    async fn check_url(&self, url: &str) -> Result<(), WebhooksServiceError> {
+        #[cfg(test)]
+        if self.allow_loopback {
+            return Ok(());
+        }
+        check_webhook_url(url)
+            .await
+            .map_err(WebhooksServiceError::ForbiddenUrl)
+    }
+
+    pub async fn list_webhooks_for_user(
+        &self,
+        user_id: Thing,
+    ) -> Result<Vec<Webhook>, WebhooksServiceError> {
+        let r = list_webhooks_for_user(&self.db.get_connection().await, user_id).await;
+
+        match r {
+            Ok(webhooks) => Ok(webhooks),
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    pub async fn create_webhook_for_user(
+        &self,
+        user_id: Thing,
+        url: String,
+    ) -> Result<Webhook, WebhooksServiceError> {
+        let conn = self.db.get_connection().await;
+        match list_webhooks_for_user(&conn, user_id.clone()).await {
+            Ok(webhooks) if webhooks.len() >= MAX_WEBHOOKS_PER_USER => {
+                return Err(WebhooksServiceError::WebhooksLimitReached(
+                    MAX_WEBHOOKS_PER_USER,
+                ))
+            }
+            Ok(_) => {}
+            Err(err) => return Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+        self.check_url(url.as_str()).await?;
+        // Deliveries are signed from the start
+        self.get_or_create_secret(&conn, user_id.clone()).await?;
+
+        let r = create_webhook(&conn, user_id, url).await;
+
+        match r {
+            Ok(webhook) => Ok(webhook),
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    pub async fn delete_webhook_for_user(
+        &self,
+        user_id: Thing,
+        webhook_id: Thing,
+    ) -> Result<(), WebhooksServiceError> {
+        let r = delete_webhook_for_user(&self.db.get_connection().await, user_id, webhook_id).await;
+
+        match r {
+            Ok(()) => Ok(()),
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    async fn get_or_create_secret(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+    ) -> Result<String, WebhooksServiceError> {
+        match get_webhook_secret(conn, user_id.clone()).await {
+            Ok(secret) => Ok(secret),
+            Err(WebhooksDBError::SecretNotFound) => {
+                let secret = generate_secret();
+                match set_webhook_secret(conn, user_id, secret.clone()).await {
+                    Ok(()) => Ok(secret),
+                    Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+                }
+            }
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    pub async fn get_secret_for_user(
+        &self,
+        user_id: Thing,
+    ) -> Result<String, WebhooksServiceError> {
+        self.get_or_create_secret(&self.db.get_connection().await, user_id)
+            .await
+    }
+
+    /// Replaces the signing secret, deliveries still queued get signed with the new one.
+    pub async fn rotate_secret_for_user(
+        &self,
+        user_id: Thing,
+    ) -> Result<String, WebhooksServiceError> {
+        let secret = generate_secret();
+        let r = set_webhook_secret(&self.db.get_connection().await, user_id, secret.clone()).await;
+
+        match r {
+            Ok(()) => Ok(secret),
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    /// Returns a page of the delivery log and the cursor of the next page if there is one.
+    pub async fn list_deliveries_for_user(
+        &self,
+        user_id: Thing,
+        webhook_id: Thing,
+        cursor: Option<Cursor>,
+        limit: usize,
+    ) -> Result<(Vec<WebhookDelivery>, Option<String>), WebhooksServiceError> {
+        let conn = self.db.get_connection().await;
+        let webhook = match get_webhook_for_user(&conn, user_id, webhook_id).await {
+            Ok(webhook) => webhook,
+            Err(err) => return Err(WebhooksServiceError::WebhooksDBError(err)),
+        };
+
+        let r = list_webhook_deliveries(&conn, webhook.id, cursor, limit + 1).await;
+
+        match r {
+            Ok(deliveries) => Ok(split_page(deliveries, limit, |delivery| {
+                Cursor::new(&delivery.created_at, &delivery.id)
+            })),
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    /// Queues the event for every webhook of the user, returns the number of deliveries.
+    pub async fn enqueue_event(
+        &self,
+        conn: &Surreal<Any>,
+        user_id: Thing,
+        event: &str,
+        data: Value,
+    ) -> Result<usize, WebhooksServiceError> {
+        let webhooks = match list_webhooks_for_user(conn, user_id.clone()).await {
+            Ok(webhooks) => webhooks,
+            Err(err) => return Err(WebhooksServiceError::WebhooksDBError(err)),
+        };
+        let payload = json!({
+            "event": event,
+            "created_at": Utc::now(),
+            "data": data
+        });
+        let deliveries: Vec<NewWebhookDelivery> = webhooks
+            .into_iter()
+            .map(|webhook| NewWebhookDelivery {
+                webhook: webhook.id,
+                user: user_id.clone(),
+                event: event.to_string(),
+                payload: payload.clone(),
+            })
+            .collect();
+        let count = deliveries.len();
+
+        match insert_webhook_deliveries(conn, deliveries).await {
+            Ok(()) => Ok(count),
+            Err(err) => Err(WebhooksServiceError::WebhooksDBError(err)),
+        }
+    }
+
+    /// Posts the payload once, returning the response status or why it failed.
+    async fn send(&self, delivery: &DueWebhookDelivery) -> (Option<u16>, Option<String>) {
+        let body = delivery.payload.to_string();
+        let timestamp = Utc::now().timestamp();
+        let mut request = self
+            .http
+            .post(delivery.url.as_str())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(DELIVERY_HEADER, delivery.id.id.to_raw())
+            .header(TIMESTAMP_HEADER, timestamp.to_string());
+        if let Some(secret) = &delivery.secret {
+            request = request.header(
+                SIGNATURE_HEADER,
+                sign_payload(secret, timestamp, body.as_str()),
+            );
+        }
+
+        match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            // The body is not kept, the log would expose whatever the URL answers
+            Ok(response) => {
+                let status = response.status().as_u16();
+                (Some(status), Some(format!("Unexpected status {status}")))
+            }
+            Err(err) => (None, Some(err.to_string())),
+        }
+    }
+
+    /// Sends deliveries that are due and schedules retries with exponential backoff.
+    pub async fn deliver_due(&self) -> Result<usize, WebhooksServiceError> {
+        let conn = self.db.get_connection().await;
+        let deliveries = match list_due_webhook_deliveries(&conn, DUE_DELIVERIES_BATCH).await {
+            Ok(deliveries) => deliveries,
+            Err(err) => return Err(WebhooksServiceError::WebhooksDBError(err)),
+        };
+
+        for delivery in deliveries.iter() {
+            // The host is checked again as it may resolve elsewhere since the webhook was created
+            let (response_status, error, retry) = match self.check_url(&delivery.url).await {
+                Ok(()) => {
+                    let (response_status, error) = self.send(delivery).await;
+                    (response_status, error, true)
+                }
+                Err(err) => (None, Some(err.to_string()), false),
+            };
+            let attempts = delivery.attempts + 1;
+            let attempt = match error {
+                None => WebhookDeliveryAttempt {
+                    status: WebhookDeliveryStatus::Delivered,
+                    attempts,
+                    next_attempt_at: None,
+                    response_status,
+                    error: None,
+                },
+                Some(error) if !retry || attempts >= self.max_attempts => WebhookDeliveryAttempt {
+                    status: WebhookDeliveryStatus::Failed,
+                    attempts,
+                    next_attempt_at: None,
+                    response_status,
+                    error: Some(error),
+                },
+                Some(error) => WebhookDeliveryAttempt {
+                    status: WebhookDeliveryStatus::Pending,
+                    attempts,
+                    next_attempt_at: Some(
+                        Utc::now()
+                            + TimeDelta::seconds(
+                                (self.retry_delay << (attempts - 1).min(16)) as i64,
+                            ),
+                    ),
+                    response_status,
+                    error: Some(error),
+                },
+            };
+            if let Err(err) =
+                set_webhook_delivery_attempt(&conn, delivery.id.clone(), attempt).await
+            {
+                log::error!("Error on saving webhook delivery {}: {err}", delivery.id);
+            }
+        }
+        Ok(deliveries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use surrealdb::sql::Thing;
+
+    use super::*;
+    use crate::{
+        models::webhooks::create_webhook,
+        utils::{
+            test_db::test_db_config,
+            test_server::{ScriptedResponse, TestServer},
+        },
+    };
+
+    fn user() -> Thing {
+        Thing::from(("users", "user"))
+    }
+
+    async fn service(allow_loopback: bool) -> WebhooksService {
+        let mut service = WebhooksService::init(test_db_config().await, 3, 60);
+        service.allow_loopback = allow_loopback;
+        service
+    }
+
+    /// Queues one event for a webhook at `url` created without the URL check.
+    async fn queue_delivery(service: &WebhooksService, url: &str) -> Thing {
+        let conn = service.db.get_connection().await;
+        let webhook = create_webhook(&conn, user(), String::from(url))
+            .await
+            .unwrap();
+        service
+            .enqueue_event(&conn, user(), "task.checked", json!({"status": "no_seats"}))
+            .await
+            .unwrap();
+        webhook.id
+    }
+
+    async fn delivery(service: &WebhooksService, webhook_id: Thing) -> WebhookDelivery {
+        let (deliveries, _) = service
+            .list_deliveries_for_user(user(), webhook_id, None, 10)
+            .await
+            .unwrap();
+        deliveries[0].clone()
+    }
+
+    #[test]
+    fn is_public_address_refuses_internal_networks() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_address(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public_address(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn check_webhook_url_refuses_internal_hosts() {
+        for url in [
+            "http://localhost:8080/hook",
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[::ffff:10.0.0.1]/hook",
+            "ftp://93.184.216.34/hook",
+        ] {
+            assert!(check_webhook_url(url).await.is_err(), "{url}");
+        }
+        assert!(check_webhook_url("https://93.184.216.34/hook")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn create_webhook_refuses_internal_urls() {
+        let service = service(false).await;
+
+        let r = service
+            .create_webhook_for_user(user(), String::from("http://127.0.0.1:8000/hook"))
+            .await;
+
+        assert!(matches!(r, Err(WebhooksServiceError::ForbiddenUrl(_))));
+        assert!(service
+            .list_webhooks_for_user(user())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn deliver_due_fails_internal_urls_without_sending() {
+        let server = TestServer::start(vec![ScriptedResponse::json("{}")]).await;
+        let service = service(false).await;
+        let webhook_id = queue_delivery(&service, server.url.as_str()).await;
+
+        assert_eq!(service.deliver_due().await.unwrap(), 1);
+
+        let delivery = delivery(&service, webhook_id).await;
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 1);
+        assert!(delivery.response_status.is_none());
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deliver_due_does_not_follow_redirects_or_keep_bodies() {
+        let mut redirect = ScriptedResponse::json(r#"{"internal": "secret"}"#)
+            .with_header("Location", "http://169.254.169.254/latest/meta-data");
+        redirect.status = 302;
+        let server = TestServer::start(vec![redirect]).await;
+        let service = service(true).await;
+        let webhook_id = queue_delivery(&service, server.url.as_str()).await;
+
+        service.deliver_due().await.unwrap();
+
+        let delivery = delivery(&service, webhook_id).await;
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Pending);
+        assert_eq!(delivery.response_status, Some(302));
+        assert_eq!(delivery.error.as_deref(), Some("Unexpected status 302"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn sign_payload_is_hmac_of_timestamp_and_body() {
+        assert_eq!(
+            sign_payload("secret", 1700000000, r#"{"event":"task.checked"}"#),
+            "sha256=ff1e0c21949591e4e57138dfece2e7fa1458b8900ddd554dbe4e55364ead1b47"
+        );
+        // The timestamp is part of the signature
+        assert_ne!(
+            sign_payload("secret", 1700000001, r#"{"event":"task.checked"}"#),
+            sign_payload("secret", 1700000000, r#"{"event":"task.checked"}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn deliver_due_signs_requests_with_the_user_secret() {
+        let server = TestServer::start(vec![ScriptedResponse::json("{}")]).await;
+        let service = service(true).await;
+        let secret = service.rotate_secret_for_user(user()).await.unwrap();
+        let webhook_id = queue_delivery(&service, server.url.as_str()).await;
+
+        service.deliver_due().await.unwrap();
+
+        let delivery = delivery(&service, webhook_id).await;
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(delivery.response_status, Some(200));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.header(EVENT_HEADER), Some("task.checked"));
+        let timestamp: i64 = request.header(TIMESTAMP_HEADER).unwrap().parse().unwrap();
+        assert_eq!(
+            request.header(SIGNATURE_HEADER),
+            Some(sign_payload(&secret, timestamp, request.body.as_str()).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn deliver_due_backs_off_exponentially_then_gives_up() {
+        let failure = || {
+            let mut response = ScriptedResponse::json("{}");
+            response.status = 500;
+            response
+        };
+        let server = TestServer::start(vec![failure(), failure(), failure()]).await;
+        let service = service(true).await;
+        let webhook_id = queue_delivery(&service, server.url.as_str()).await;
+        let conn = service.db.get_connection().await;
+
+        for (attempts, delay) in [(1, 60), (2, 120)] {
+            let sent_at = Utc::now();
+            assert_eq!(service.deliver_due().await.unwrap(), 1);
+            let delivery = delivery(&service, webhook_id.clone()).await;
+            assert_eq!(delivery.status, WebhookDeliveryStatus::Pending);
+            assert_eq!(delivery.attempts, attempts);
+            let wait = delivery.next_attempt_at.to_utc() - sent_at;
+            assert!(wait >= TimeDelta::seconds(delay), "{wait}");
+            assert!(wait < TimeDelta::seconds(delay + 5), "{wait}");
+
+            // Not due until the backoff passes
+            assert_eq!(service.deliver_due().await.unwrap(), 0);
+            conn.query("UPDATE webhook_deliveries SET next_attempt_at = time::now()")
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(service.deliver_due().await.unwrap(), 1);
+        let delivery = delivery(&service, webhook_id).await;
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(service.deliver_due().await.unwrap(), 0);
+    }
+}
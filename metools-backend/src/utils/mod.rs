@@ -1,3 +1,8 @@
 pub mod macros;
+pub mod pagination;
 pub mod string;
+#[cfg(test)]
+pub mod test_db;
+#[cfg(test)]
+pub mod test_server;
 pub mod thing;
@@ -0,0 +1,71 @@
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+use utoipa::ToSchema;
+
+pub const DEFAULT_PAGE_LIMIT: usize = 20;
+
+/// Position after the last returned row of a list ordered by creation time, ties broken by id.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn new(created_at: &Datetime, id: &Thing) -> Self {
+        Self {
+            created_at: created_at.to_utc(),
+            id: id.to_string(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_string(self).unwrap())
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        let bytes = BASE64_URL_SAFE_NO_PAD.decode(s).ok()?;
+        serde_json::from_slice(bytes.as_slice()).ok()
+    }
+}
+
+/// Cursor as clients see it: an opaque string.
+#[derive(Clone, Debug)]
+pub struct EncodedCursor(pub Cursor);
+
+impl<'de> Deserialize<'de> for EncodedCursor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        match Cursor::decode(s.as_str()) {
+            Some(cursor) => Ok(EncodedCursor(cursor)),
+            None => Err(D::Error::custom("invalid cursor")),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Cuts the extra row fetched to detect a next page and returns the cursor pointing past the page.
+pub fn split_page<T>(
+    mut rows: Vec<T>,
+    limit: usize,
+    cursor: impl Fn(&T) -> Cursor,
+) -> (Vec<T>, Option<String>) {
+    if rows.len() <= limit {
+        return (rows, None);
+    }
+    rows.truncate(limit);
+    let next_cursor = rows.last().map(|row| cursor(row).encode());
+    (rows, next_cursor)
+}
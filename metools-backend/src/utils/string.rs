@@ -15,6 +15,28 @@ pub fn decode_from_base64_to_thing(s: String) -> Thing {
     Thing::from_str(decode_from_base64_string(s).as_str()).unwrap()
 }
 
+/// Like `decode_from_base64_to_thing` but for untrusted input.
+pub fn try_decode_from_base64_to_thing(s: &str) -> Option<Thing> {
+    let decoded = BASE64_URL_SAFE_NO_PAD.decode(s).ok()?;
+    Thing::from_str(String::from_utf8(decoded).ok()?.as_str()).ok()
+}
+
 pub fn encode_thing_to_base64_string(t: Thing) -> String {
     encode_to_base64_string(t.to_string())
 }
+
+/// Escapes text before it goes into HTML, e.g. values returned by RZD.
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,22 @@
+use surrealdb::engine::any;
+use surrealdb_migrations::MigrationRunner;
+
+use crate::config::DBConfig;
+
+/// Config of a fresh in-memory database with the migrations applied. Services built from it
+/// share the database.
+pub async fn test_db_config() -> DBConfig {
+    let mut config = DBConfig::init();
+    let db = any::connect("mem://").await.unwrap();
+    db.use_ns(config.surrealdb_ns.as_str())
+        .use_db(config.surrealdb_db.as_str())
+        .await
+        .unwrap();
+    MigrationRunner::new(&db)
+        .up()
+        .await
+        .expect("cant up migrations");
+
+    config.connection = Some(db);
+    config
+}
@@ -1,7 +1,7 @@
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use surrealdb::sql::Thing;
 
-use super::string::{decode_from_base64_to_thing, encode_thing_to_base64_string};
+use super::string::{encode_thing_to_base64_string, try_decode_from_base64_to_thing};
 
 #[derive(Clone)]
 pub struct Base64EncodedThing(pub Thing);
@@ -12,7 +12,9 @@ impl<'de> Deserialize<'de> for Base64EncodedThing {
         D: Deserializer<'de>,
     {
         let s: String = Deserialize::deserialize(deserializer)?;
-        Ok(Base64EncodedThing(decode_from_base64_to_thing(s)))
+        try_decode_from_base64_to_thing(s.as_str())
+            .map(Base64EncodedThing)
+            .ok_or_else(|| D::Error::custom("invalid record id"))
     }
 }
 
@@ -24,3 +26,20 @@ impl Serialize for Base64EncodedThing {
         serializer.serialize_str(encode_thing_to_base64_string(self.0.clone()).as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_rejects_malformed_ids() {
+        let thing = Thing::from(("rzd_tasks", "task"));
+        let encoded = serde_json::to_string(&Base64EncodedThing(thing.clone())).unwrap();
+        let decoded: Base64EncodedThing = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded.0, thing);
+
+        for malformed in ["\"not base64!\"", "\"bm90LWEtdGhpbmc\""] {
+            assert!(serde_json::from_str::<Base64EncodedThing>(malformed).is_err());
+        }
+    }
+}
@@ -1,21 +1,239 @@
 // This file is auto-generated by @hey-api/openapi-ts
 
-export const $CreateTaskData = {
+export const $AvailableCarClass = {
     type: 'object',
-    required: ['task_type', 'data'],
+    required: ['car_type', 'free_seats', 'min_price'],
     properties: {
-        data: {
+        car_type: {
+            type: 'string'
+        },
+        free_seats: {
+            type: 'integer',
+            format: 'uint32',
+            minimum: 0
+        },
+        min_price: {
+            type: 'integer',
+            format: 'uint32',
+            minimum: 0
+        }
+    }
+} as const;
+
+export const $AvailableTrain = {
+    type: 'object',
+    required: ['number', 'departure_date', 'departure_time', 'arrival_date', 'arrival_time', 'car_classes'],
+    properties: {
+        arrival_date: {
+            type: 'string'
+        },
+        arrival_time: {
+            type: 'string'
+        },
+        car_classes: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/AvailableCarClass'
+            }
+        },
+        departure_date: {
+            type: 'string'
+        },
+        departure_time: {
+            type: 'string'
+        },
+        leg: {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/TripLeg'
+                }
+            ],
+            nullable: true
+        },
+        number: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ClassPrice = {
+    allOf: [
+        {
+            $ref: '#/components/schemas/PriceKey'
+        },
+        {
+            type: 'object',
+            required: ['min_price'],
+            properties: {
+                min_price: {
+                    type: 'integer',
+                    format: 'uint32',
+                    minimum: 0
+                }
+            }
+        }
+    ],
+    description: 'Minimum price of a car class on one travel date and trip leg.'
+} as const;
+
+export const $ClientMessage = {
+    oneOf: [
+        {
+            type: 'object',
+            description: 'Starts sending events of the tasks, answered with the current state of each task.\nClients send it again after reconnecting.',
+            required: ['task_ids', 'type'],
+            properties: {
+                task_ids: {
+                    type: 'array',
+                    items: {
+                        type: 'string'
+                    }
+                },
+                type: {
+                    type: 'string',
+                    enum: ['subscribe']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['task_ids', 'type'],
+            properties: {
+                task_ids: {
+                    type: 'array',
+                    items: {
+                        type: 'string'
+                    }
+                },
+                type: {
+                    type: 'string',
+                    enum: ['unsubscribe']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['type'],
+            properties: {
+                type: {
+                    type: 'string',
+                    enum: ['ping']
+                }
+            }
+        }
+    ],
+    description: 'Message from the client, task ids are base64 encoded.',
+    discriminator: {
+        propertyName: 'type'
+    }
+} as const;
+
+export const $CreateTaskData = {
+    allOf: [
+        {
+            $ref: '#/components/schemas/TaskData'
+        },
+        {
             type: 'object',
-            additionalProperties: {
+            properties: {
+                check_interval: {
+                    type: 'integer',
+                    format: 'uint64',
+                    description: 'Seconds between checks, the shortest interval of the user\'s plan by default.',
+                    nullable: true,
+                    minimum: 0
+                },
+                filters: {
+                    $ref: '#/components/schemas/TaskFilters'
+                },
+                price_alert: {
+                    allOf: [
+                        {
+                            $ref: '#/components/schemas/PriceAlert'
+                        }
+                    ],
+                    nullable: true
+                }
+            }
+        }
+    ]
+} as const;
+
+export const $CreateWebhookData = {
+    type: 'object',
+    required: ['url'],
+    properties: {
+        url: {
+            type: 'string',
+            description: 'Events are posted here as JSON.'
+        }
+    }
+} as const;
+
+export const $DateRangeTask = {
+    type: 'object',
+    description: 'Watches every day from `date_from` to `date_to` inclusive.',
+    required: ['from_point_code', 'to_point_code', 'date_from', 'date_to'],
+    properties: {
+        date_from: {
+            type: 'string',
+            format: 'date'
+        },
+        date_to: {
+            type: 'string',
+            format: 'date'
+        },
+        from_point_code: {
+            type: 'string'
+        },
+        to_point_code: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $DatesTask = {
+    type: 'object',
+    description: 'Watches several arbitrary days at once.',
+    required: ['from_point_code', 'to_point_code', 'dates'],
+    properties: {
+        dates: {
+            type: 'array',
+            items: {
                 type: 'string'
             }
         },
-        task_type: {
+        from_point_code: {
+            type: 'string'
+        },
+        to_point_code: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $DayTask = {
+    type: 'object',
+    required: ['from_point_code', 'to_point_code', 'date'],
+    properties: {
+        date: {
+            type: 'string',
+            format: 'date'
+        },
+        from_point_code: {
+            type: 'string'
+        },
+        to_point_code: {
             type: 'string'
         }
     }
 } as const;
 
+export const $DeliveryMode = {
+    type: 'string',
+    enum: ['instant', 'digest']
+} as const;
+
 export const $ErrorResponse = {
     type: 'object',
     required: ['status', 'error'],
@@ -29,6 +247,19 @@ export const $ErrorResponse = {
     }
 } as const;
 
+export const $LegCheck = {
+    type: 'object',
+    required: ['leg', 'status'],
+    properties: {
+        leg: {
+            $ref: '#/components/schemas/TripLeg'
+        },
+        status: {
+            $ref: '#/components/schemas/TaskCheckStatus'
+        }
+    }
+} as const;
+
 export const $LoginData = {
     type: 'object',
     required: ['username', 'password'],
@@ -42,12 +273,158 @@ export const $LoginData = {
     }
 } as const;
 
-export const $ResponseCreateTask = {
+export const $NotificationChannels = {
+    type: 'object',
+    description: 'Where notifications are sent, every channel is on by default.',
+    properties: {
+        email: {
+            type: 'boolean'
+        },
+        telegram: {
+            type: 'boolean'
+        },
+        webhook: {
+            type: 'boolean'
+        }
+    }
+} as const;
+
+export const $NotificationSettings = {
+    type: 'object',
+    properties: {
+        channels: {
+            $ref: '#/components/schemas/NotificationChannels'
+        },
+        delivery: {
+            $ref: '#/components/schemas/DeliveryMode'
+        },
+        quiet_hours: {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/QuietHours'
+                }
+            ],
+            nullable: true
+        },
+        timezone: {
+            type: 'string',
+            description: 'IANA time zone the quiet hours are in, e.g. `Europe/Moscow`.'
+        }
+    }
+} as const;
+
+export const $PriceAlert = {
+    type: 'object',
+    description: 'Alerts about prices of car classes that already have seats.',
+    properties: {
+        below_price: {
+            type: 'integer',
+            format: 'uint32',
+            description: 'Alert once the minimum price of a car class falls to this value or below.',
+            nullable: true,
+            minimum: 0
+        },
+        drop_percent: {
+            type: 'integer',
+            format: 'uint32',
+            description: 'Alert once the minimum price of a car class drops by this percentage from its baseline.',
+            nullable: true,
+            minimum: 0
+        }
+    }
+} as const;
+
+export const $PriceKey = {
+    type: 'object',
+    description: 'A car class on one travel date and trip leg, its prices are tracked separately.',
+    required: ['date', 'car_type'],
+    properties: {
+        car_type: {
+            type: 'string'
+        },
+        date: {
+            type: 'string',
+            format: 'date'
+        },
+        leg: {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/TripLeg'
+                }
+            ],
+            nullable: true
+        }
+    }
+} as const;
+
+export const $PricePoint = {
+    type: 'object',
+    required: ['checked_at', 'min_price'],
+    properties: {
+        checked_at: {
+            type: 'string',
+            format: 'date-time'
+        },
+        min_price: {
+            type: 'integer',
+            format: 'uint32',
+            minimum: 0
+        }
+    }
+} as const;
+
+export const $PriceSeries = {
+    type: 'object',
+    required: ['car_type', 'points'],
+    properties: {
+        car_type: {
+            type: 'string'
+        },
+        date: {
+            type: 'string',
+            format: 'date',
+            description: 'Travel date, not set for prices stored before they were tracked per date.',
+            nullable: true
+        },
+        leg: {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/TripLeg'
+                }
+            ],
+            nullable: true
+        },
+        points: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/PricePoint'
+            }
+        }
+    }
+} as const;
+
+export const $QuietHours = {
+    type: 'object',
+    description: 'Local time window without messages, it wraps midnight when `start` is after `end`.',
+    required: ['start', 'end'],
+    properties: {
+        end: {
+            type: 'string',
+            example: '08:00'
+        },
+        start: {
+            type: 'string',
+            example: 1380
+        }
+    }
+} as const;
+
+export const $ResponseChangeTaskStatusForUser = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
         data: {
-            '$ref': '#/components/schemas/Task'
+            type: 'string'
         },
         status: {
             type: 'string'
@@ -55,12 +432,12 @@ export const $ResponseCreateTask = {
     }
 } as const;
 
-export const $ResponseDeleteAllTasksForUser = {
+export const $ResponseCreateTask = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
         data: {
-            type: 'string'
+            $ref: '#/components/schemas/ResponseListTasksData'
         },
         status: {
             type: 'string'
@@ -68,12 +445,12 @@ export const $ResponseDeleteAllTasksForUser = {
     }
 } as const;
 
-export const $ResponseDeleteTaskByIdForUser = {
+export const $ResponseCreateWebhook = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
         data: {
-            type: 'string'
+            $ref: '#/components/schemas/ResponseWebhookData'
         },
         status: {
             type: 'string'
@@ -81,15 +458,12 @@ export const $ResponseDeleteTaskByIdForUser = {
     }
 } as const;
 
-export const $ResponseListTasks = {
+export const $ResponseDeleteAllTasksForUser = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
         data: {
-            type: 'array',
-            items: {
-                '$ref': '#/components/schemas/Task'
-            }
+            type: 'string'
         },
         status: {
             type: 'string'
@@ -97,7 +471,7 @@ export const $ResponseListTasks = {
     }
 } as const;
 
-export const $ResponseLogin = {
+export const $ResponseDeleteTaskByIdForUser = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
@@ -110,12 +484,12 @@ export const $ResponseLogin = {
     }
 } as const;
 
-export const $ResponseMe = {
+export const $ResponseDeleteWebhook = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
         data: {
-            '$ref': '#/components/schemas/UserReturn'
+            type: 'string'
         },
         status: {
             type: 'string'
@@ -123,12 +497,12 @@ export const $ResponseMe = {
     }
 } as const;
 
-export const $ResponseSignUp = {
+export const $ResponseGetTask = {
     type: 'object',
     required: ['status', 'data'],
     properties: {
         data: {
-            '$ref': '#/components/schemas/UserReturn'
+            $ref: '#/components/schemas/ResponseListTasksData'
         },
         status: {
             type: 'string'
@@ -136,61 +510,1024 @@ export const $ResponseSignUp = {
     }
 } as const;
 
-export const $SignUpData = {
+export const $ResponseListTaskChecks = {
     type: 'object',
-    required: ['username', 'email', 'password', 'repeat_password'],
+    required: ['status', 'data'],
     properties: {
-        email: {
-            type: 'string'
+        data: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/ResponseTaskCheckData'
+            }
         },
-        password: {
-            type: 'string'
+        next_cursor: {
+            type: 'string',
+            description: 'Pass as `cursor` to get the next page, absent on the last page.',
+            nullable: true
         },
-        repeat_password: {
+        status: {
             type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseListTasks = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/ResponseListTasksData'
+            }
         },
-        username: {
+        next_cursor: {
+            type: 'string',
+            description: 'Pass as `cursor` to get the next page, absent on the last page.',
+            nullable: true
+        },
+        status: {
             type: 'string'
         }
     }
 } as const;
 
-export const $Task = {
+export const $ResponseListTasksData = {
+    allOf: [
+        {
+            $ref: '#/components/schemas/TaskData'
+        },
+        {
+            type: 'object',
+            required: ['id', 'created_at', 'user', 'filters', 'status'],
+            properties: {
+                check_interval: {
+                    type: 'integer',
+                    format: 'uint64',
+                    nullable: true,
+                    minimum: 0
+                },
+                created_at: {
+                    type: 'string',
+                    format: 'date-time'
+                },
+                filters: {
+                    $ref: '#/components/schemas/TaskFilters'
+                },
+                id: {
+                    type: 'string'
+                },
+                last_check: {
+                    allOf: [
+                        {
+                            $ref: '#/components/schemas/TaskCheck'
+                        }
+                    ],
+                    nullable: true
+                },
+                last_checked_at: {
+                    type: 'string',
+                    format: 'date-time',
+                    nullable: true
+                },
+                next_check_at: {
+                    type: 'string',
+                    format: 'date-time',
+                    nullable: true
+                },
+                notifications: {
+                    allOf: [
+                        {
+                            $ref: '#/components/schemas/TaskNotificationOverrides'
+                        }
+                    ],
+                    nullable: true
+                },
+                price_alert: {
+                    allOf: [
+                        {
+                            $ref: '#/components/schemas/PriceAlert'
+                        }
+                    ],
+                    nullable: true
+                },
+                status: {
+                    $ref: '#/components/schemas/TaskStatus'
+                },
+                user: {
+                    type: 'string'
+                }
+            }
+        }
+    ]
+} as const;
+
+export const $ResponseListWebhookDeliveries = {
     type: 'object',
-    required: ['id', 'created_at', 'type_', 'data', 'user_id'],
+    required: ['status', 'data'],
     properties: {
-        created_at: {
-            type: 'string',
-            format: 'date-time'
+        data: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/ResponseWebhookDeliveryData'
+            }
         },
-        data: {},
-        id: {
+        next_cursor: {
             type: 'string',
-            format: 'uuid'
+            description: 'Pass as `cursor` to get the next page, absent on the last page.',
+            nullable: true
         },
-        type_: {
+        status: {
             type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseListWebhooks = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/ResponseWebhookData'
+            }
         },
-        user_id: {
-            type: 'string',
-            format: 'uuid'
+        status: {
+            type: 'string'
         }
     }
 } as const;
 
-export const $UserReturn = {
+export const $ResponseLogin = {
     type: 'object',
-    required: ['id', 'username', 'email'],
+    required: ['status', 'data'],
     properties: {
-        email: {
+        data: {
             type: 'string'
         },
-        id: {
-            type: 'string',
-            format: 'uuid'
-        },
-        username: {
+        status: {
             type: 'string'
         }
     }
-} as const;
\ No newline at end of file
+} as const;
+
+export const $ResponseMe = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseMeData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseMeData = {
+    type: 'object',
+    required: ['created_at', 'username', 'email'],
+    properties: {
+        created_at: {
+            type: 'string',
+            format: 'date-time'
+        },
+        email: {
+            type: 'string'
+        },
+        username: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseNotificationSettings = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/NotificationSettings'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseSearchStations = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/Station'
+            }
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseSignup = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseSignupData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseSignupData = {
+    type: 'object',
+    required: ['created_at', 'username', 'email'],
+    properties: {
+        created_at: {
+            type: 'string',
+            format: 'date-time'
+        },
+        email: {
+            type: 'string'
+        },
+        username: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseTaskCheckData = {
+    allOf: [
+        {
+            $ref: '#/components/schemas/TaskCheck'
+        },
+        {
+            type: 'object',
+            required: ['checked_at', 'notified'],
+            properties: {
+                checked_at: {
+                    type: 'string',
+                    format: 'date-time'
+                },
+                notified: {
+                    type: 'boolean'
+                }
+            }
+        }
+    ]
+} as const;
+
+export const $ResponseTaskPrices = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseTaskPricesData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseTaskPricesData = {
+    type: 'object',
+    required: ['series'],
+    properties: {
+        series: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/PriceSeries'
+            }
+        }
+    }
+} as const;
+
+export const $ResponseTelegramChat = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseTelegramChatData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseTelegramChatData = {
+    type: 'object',
+    required: ['linked_at'],
+    properties: {
+        linked_at: {
+            type: 'string',
+            format: 'date-time'
+        },
+        username: {
+            type: 'string',
+            nullable: true
+        }
+    }
+} as const;
+
+export const $ResponseTelegramLink = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseTelegramLinkData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseTelegramLinkData = {
+    type: 'object',
+    required: ['code', 'link', 'valid_until'],
+    properties: {
+        code: {
+            type: 'string',
+            description: 'Send `/start <code>` to the bot if the link can not be opened.'
+        },
+        link: {
+            type: 'string'
+        },
+        valid_until: {
+            type: 'string',
+            format: 'date-time'
+        }
+    }
+} as const;
+
+export const $ResponseUnlinkTelegram = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            type: 'string'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseUpdateTask = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseListTasksData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseWebhookData = {
+    type: 'object',
+    required: ['id', 'created_at', 'url'],
+    properties: {
+        created_at: {
+            type: 'string',
+            format: 'date-time'
+        },
+        id: {
+            type: 'string'
+        },
+        url: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseWebhookDeliveryData = {
+    type: 'object',
+    required: ['id', 'created_at', 'event', 'payload', 'status', 'attempts', 'next_attempt_at'],
+    properties: {
+        attempts: {
+            type: 'integer',
+            format: 'uint32',
+            minimum: 0
+        },
+        created_at: {
+            type: 'string',
+            format: 'date-time'
+        },
+        error: {
+            type: 'string',
+            nullable: true
+        },
+        event: {
+            type: 'string'
+        },
+        id: {
+            type: 'string'
+        },
+        last_attempt_at: {
+            type: 'string',
+            format: 'date-time',
+            nullable: true
+        },
+        next_attempt_at: {
+            type: 'string',
+            format: 'date-time',
+            description: 'When the next attempt is made while the delivery is pending.'
+        },
+        payload: {},
+        response_status: {
+            type: 'integer',
+            format: 'uint16',
+            nullable: true,
+            minimum: 0
+        },
+        status: {
+            $ref: '#/components/schemas/WebhookDeliveryStatus'
+        }
+    }
+} as const;
+
+export const $ResponseWebhookSecret = {
+    type: 'object',
+    required: ['status', 'data'],
+    properties: {
+        data: {
+            $ref: '#/components/schemas/ResponseWebhookSecretData'
+        },
+        status: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $ResponseWebhookSecretData = {
+    type: 'object',
+    required: ['secret'],
+    properties: {
+        secret: {
+            type: 'string',
+            description: 'Key of the HMAC-SHA256 signature sent in `X-MeTools-Signature`.'
+        }
+    }
+} as const;
+
+export const $RoundTripTask = {
+    type: 'object',
+    description: 'Watches an outbound and a return journey together.',
+    required: ['outbound', 'return_leg'],
+    properties: {
+        alert_per_leg: {
+            type: 'boolean',
+            description: 'Notify as soon as any leg has seats instead of waiting for both.'
+        },
+        outbound: {
+            $ref: '#/components/schemas/DayTask'
+        },
+        return_leg: {
+            $ref: '#/components/schemas/DayTask'
+        }
+    }
+} as const;
+
+export const $SeatType = {
+    type: 'string',
+    enum: ['lower', 'upper', 'side_lower', 'side_upper']
+} as const;
+
+export const $ServerMessage = {
+    oneOf: [
+        {
+            type: 'object',
+            required: ['task_ids', 'type'],
+            properties: {
+                task_ids: {
+                    type: 'array',
+                    items: {
+                        type: 'string'
+                    }
+                },
+                type: {
+                    type: 'string',
+                    enum: ['subscribed']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['task_ids', 'type'],
+            properties: {
+                task_ids: {
+                    type: 'array',
+                    items: {
+                        type: 'string'
+                    }
+                },
+                type: {
+                    type: 'string',
+                    enum: ['unsubscribed']
+                }
+            }
+        },
+        {
+            type: 'object',
+            description: 'Current state of a task sent on subscription.',
+            required: ['task', 'type'],
+            properties: {
+                task: {
+                    $ref: '#/components/schemas/ResponseListTasksData'
+                },
+                type: {
+                    type: 'string',
+                    enum: ['task']
+                }
+            }
+        },
+        {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/TaskEvent'
+                },
+                {
+                    type: 'object',
+                    required: ['type'],
+                    properties: {
+                        type: {
+                            type: 'string',
+                            enum: ['event']
+                        }
+                    }
+                }
+            ]
+        },
+        {
+            type: 'object',
+            description: 'Events were missed because the client was too slow, it should resubscribe.',
+            required: ['skipped', 'type'],
+            properties: {
+                skipped: {
+                    type: 'integer',
+                    format: 'uint64',
+                    minimum: 0
+                },
+                type: {
+                    type: 'string',
+                    enum: ['lagged']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['type'],
+            properties: {
+                type: {
+                    type: 'string',
+                    enum: ['pong']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['error', 'status', 'type'],
+            properties: {
+                error: {
+                    type: 'string'
+                },
+                status: {
+                    type: 'string'
+                },
+                task_id: {
+                    type: 'string',
+                    nullable: true
+                },
+                type: {
+                    type: 'string',
+                    enum: ['error']
+                }
+            }
+        }
+    ],
+    description: 'Message from the server.',
+    discriminator: {
+        propertyName: 'type'
+    }
+} as const;
+
+export const $SignUpData = {
+    type: 'object',
+    required: ['username', 'email', 'password', 'repeat_password'],
+    properties: {
+        email: {
+            type: 'string'
+        },
+        password: {
+            type: 'string'
+        },
+        repeat_password: {
+            type: 'string'
+        },
+        username: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $SortOrder = {
+    type: 'string',
+    enum: ['asc', 'desc']
+} as const;
+
+export const $Station = {
+    type: 'object',
+    required: ['code', 'name', 'name_latin'],
+    properties: {
+        code: {
+            type: 'string',
+            description: 'RZD express code, e.g. 2000000 for Moscow'
+        },
+        name: {
+            type: 'string'
+        },
+        name_latin: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $TaskCheck = {
+    type: 'object',
+    required: ['status', 'trains'],
+    properties: {
+        available_dates: {
+            type: 'array',
+            items: {
+                type: 'string'
+            },
+            description: 'Travel dates on which seats were found.'
+        },
+        error: {
+            type: 'string',
+            nullable: true
+        },
+        legs: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/LegCheck'
+            },
+            description: 'Per-leg outcome of round-trip tasks.'
+        },
+        status: {
+            $ref: '#/components/schemas/TaskCheckStatus'
+        },
+        trains: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/AvailableTrain'
+            }
+        }
+    }
+} as const;
+
+export const $TaskCheckStatus = {
+    type: 'string',
+    enum: ['seats_found', 'no_seats', 'error']
+} as const;
+
+export const $TaskData = {
+    oneOf: [
+        {
+            type: 'object',
+            required: ['type', 'data'],
+            properties: {
+                data: {
+                    $ref: '#/components/schemas/DayTask'
+                },
+                type: {
+                    type: 'string',
+                    enum: ['day']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['type', 'data'],
+            properties: {
+                data: {
+                    $ref: '#/components/schemas/TrainTask'
+                },
+                type: {
+                    type: 'string',
+                    enum: ['train']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['type', 'data'],
+            properties: {
+                data: {
+                    $ref: '#/components/schemas/DatesTask'
+                },
+                type: {
+                    type: 'string',
+                    enum: ['dates']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['type', 'data'],
+            properties: {
+                data: {
+                    $ref: '#/components/schemas/DateRangeTask'
+                },
+                type: {
+                    type: 'string',
+                    enum: ['date_range']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['type', 'data'],
+            properties: {
+                data: {
+                    $ref: '#/components/schemas/RoundTripTask'
+                },
+                type: {
+                    type: 'string',
+                    enum: ['round_trip']
+                }
+            }
+        }
+    ],
+    discriminator: {
+        propertyName: 'type'
+    }
+} as const;
+
+export const $TaskEvent = {
+    allOf: [
+        {
+            $ref: '#/components/schemas/TaskEventKind'
+        },
+        {
+            type: 'object',
+            required: ['task_id'],
+            properties: {
+                task_id: {
+                    type: 'string',
+                    description: 'Base64 encoded task id.'
+                }
+            }
+        }
+    ],
+    description: 'Change of a task pushed to the streams of its owner.'
+} as const;
+
+export const $TaskEventKind = {
+    oneOf: [
+        {
+            type: 'object',
+            required: ['check', 'event'],
+            properties: {
+                check: {
+                    $ref: '#/components/schemas/TaskCheck'
+                },
+                event: {
+                    type: 'string',
+                    enum: ['checked']
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['trains', 'event'],
+            properties: {
+                event: {
+                    type: 'string',
+                    enum: ['seats_found']
+                },
+                trains: {
+                    type: 'array',
+                    items: {
+                        $ref: '#/components/schemas/AvailableTrain'
+                    }
+                }
+            }
+        },
+        {
+            type: 'object',
+            description: 'Minimal prices of car classes by travel date and leg after they changed since the previous check.',
+            required: ['prices', 'event'],
+            properties: {
+                event: {
+                    type: 'string',
+                    enum: ['prices_changed']
+                },
+                prices: {
+                    type: 'array',
+                    items: {
+                        $ref: '#/components/schemas/ClassPrice'
+                    }
+                }
+            }
+        },
+        {
+            type: 'object',
+            required: ['status', 'event'],
+            properties: {
+                event: {
+                    type: 'string',
+                    enum: ['status_changed']
+                },
+                status: {
+                    $ref: '#/components/schemas/TaskStatus'
+                }
+            }
+        }
+    ],
+    discriminator: {
+        propertyName: 'event'
+    }
+} as const;
+
+export const $TaskFilters = {
+    type: 'object',
+    description: 'Optional criteria a car class has to meet before the user is notified about it.',
+    properties: {
+        car_types: {
+            type: 'array',
+            items: {
+                type: 'string'
+            },
+            description: 'Car classes as named by RZD, e.g. `Купе`. Any class matches when empty.'
+        },
+        max_price: {
+            type: 'integer',
+            format: 'uint32',
+            nullable: true,
+            minimum: 0
+        },
+        min_seats: {
+            type: 'integer',
+            format: 'uint32',
+            nullable: true,
+            minimum: 0
+        },
+        seat_types: {
+            type: 'array',
+            items: {
+                $ref: '#/components/schemas/SeatType'
+            },
+            description: 'Seat types are only known for train tasks. Any seat matches when empty.'
+        }
+    }
+} as const;
+
+export const $TaskNotificationOverrides = {
+    type: 'object',
+    description: 'Task level changes to the user\'s settings, omitted fields follow the user\'s settings.',
+    properties: {
+        channels: {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/NotificationChannels'
+                }
+            ],
+            nullable: true
+        },
+        delivery: {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/DeliveryMode'
+                }
+            ],
+            nullable: true
+        },
+        quiet_hours: {
+            type: 'boolean',
+            description: '`false` lets notifications of the task through during quiet hours.',
+            nullable: true
+        }
+    }
+} as const;
+
+export const $TaskStatus = {
+    type: 'string',
+    enum: ['active', 'paused', 'fulfilled', 'expired', 'failed']
+} as const;
+
+export const $TaskType = {
+    type: 'string',
+    enum: ['day', 'train', 'dates', 'date_range', 'round_trip']
+} as const;
+
+export const $TrackedPrice = {
+    allOf: [
+        {
+            $ref: '#/components/schemas/PriceKey'
+        },
+        {
+            type: 'object',
+            required: ['last_price', 'baseline_price'],
+            properties: {
+                baseline_price: {
+                    type: 'integer',
+                    format: 'uint32',
+                    description: 'Price drops are measured from here: the highest price since the last alert.',
+                    minimum: 0
+                },
+                last_price: {
+                    type: 'integer',
+                    format: 'uint32',
+                    minimum: 0
+                }
+            }
+        }
+    ],
+    description: 'Price state of a car class used to detect threshold crossings and drops.'
+} as const;
+
+export const $TrainTask = {
+    type: 'object',
+    required: ['from_point_code', 'to_point_code', 'date', 'time', 'tnum'],
+    properties: {
+        date: {
+            type: 'string',
+            format: 'date'
+        },
+        from_point_code: {
+            type: 'string'
+        },
+        time: {
+            type: 'string',
+            example: 86100
+        },
+        tnum: {
+            type: 'string'
+        },
+        to_point_code: {
+            type: 'string'
+        }
+    }
+} as const;
+
+export const $TripLeg = {
+    type: 'string',
+    enum: ['outbound', 'return']
+} as const;
+
+export const $UpdateTaskData = {
+    allOf: [
+        {
+            allOf: [
+                {
+                    $ref: '#/components/schemas/TaskData'
+                }
+            ],
+            nullable: true
+        },
+        {
+            type: 'object',
+            properties: {
+                check_interval: {
+                    type: 'integer',
+                    format: 'uint64',
+                    nullable: true,
+                    minimum: 0
+                },
+                filters: {
+                    allOf: [
+                        {
+                            $ref: '#/components/schemas/TaskFilters'
+                        }
+                    ],
+                    nullable: true
+                },
+                price_alert: {
+                    allOf: [
+                        {
+                            $ref: '#/components/schemas/PriceAlert'
+                        }
+                    ],
+                    nullable: true
+                }
+            }
+        }
+    ],
+    description: 'Fields to change, omitted ones keep their current value.'
+} as const;
+
+export const $WebhookDeliveryStatus = {
+    type: 'string',
+    enum: ['pending', 'delivered', 'failed']
+} as const;
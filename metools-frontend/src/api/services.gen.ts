@@ -3,11 +3,228 @@
 import type { CancelablePromise } from './core/CancelablePromise';
 import { OpenAPI } from './core/OpenAPI';
 import { request as __request } from './core/request';
-import type { ListTasksData, ListTasksResponse, CreateTaskData2, CreateTaskResponse, DeleteAllTasksForUserData, DeleteAllTasksForUserResponse, DeleteTaskByIdForUserData, DeleteTaskByIdForUserResponse, LoginData2, LoginResponse, MeData, MeResponse, SignupData, SignupResponse } from './types.gen';
+import type { GetTelegramChatData, GetTelegramChatResponse, UnlinkTelegramChatData, UnlinkTelegramChatResponse, CreateTelegramLinkData, CreateTelegramLinkResponse, ListWebhooksData, ListWebhooksResponse, CreateWebhookData2, CreateWebhookResponse, GetWebhookSecretData, GetWebhookSecretResponse, RotateWebhookSecretData, RotateWebhookSecretResponse, DeleteWebhookData, DeleteWebhookResponse, ListWebhookDeliveriesData, ListWebhookDeliveriesResponse, SearchStationsData, SearchStationsResponse, ListTasksData, ListTasksResponse, CreateTaskData2, CreateTaskResponse, DeleteAllTasksForUserData, DeleteAllTasksForUserResponse, StreamTaskEventsData, StreamTaskEventsResponse, MonitorTasksData, MonitorTasksResponse, GetTaskByIdForUserData, GetTaskByIdForUserResponse, DeleteTaskByIdForUserData, DeleteTaskByIdForUserResponse, UpdateTaskByIdForUserData, UpdateTaskByIdForUserResponse, ListTaskChecksForUserData, ListTaskChecksForUserResponse, SetTaskNotificationsForUserData, SetTaskNotificationsForUserResponse, PauseTaskByIdForUserData, PauseTaskByIdForUserResponse, ListTaskPricesForUserData, ListTaskPricesForUserResponse, ResumeTaskByIdForUserData, ResumeTaskByIdForUserResponse, LoginData2, LoginResponse, MeData, MeResponse, GetNotificationSettingsData, GetNotificationSettingsResponse, UpdateNotificationSettingsData, UpdateNotificationSettingsResponse, SignupData, SignupResponse, VerifyUserData, VerifyUserResponse } from './types.gen';
 
 /**
  * @param data The data for the request.
- * @param data.xApiAuthToken Auth token
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseTelegramChat OK
+ * @throws ApiError
+ */
+export const getTelegramChat = (data: GetTelegramChatData): CancelablePromise<GetTelegramChatResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/notifications/telegram',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        404: 'Telegram chat is not linked',
+        500: 'INTERNAL_SERVER_ERROR',
+        503: 'Telegram notifications are not configured'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseUnlinkTelegram OK
+ * @throws ApiError
+ */
+export const unlinkTelegramChat = (data: UnlinkTelegramChatData): CancelablePromise<UnlinkTelegramChatResponse> => { return __request(OpenAPI, {
+    method: 'DELETE',
+    url: '/api/v1/notifications/telegram',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        404: 'Telegram chat is not linked',
+        500: 'INTERNAL_SERVER_ERROR',
+        503: 'Telegram notifications are not configured'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseTelegramLink OK
+ * @throws ApiError
+ */
+export const createTelegramLink = (data: CreateTelegramLinkData): CancelablePromise<CreateTelegramLinkResponse> => { return __request(OpenAPI, {
+    method: 'POST',
+    url: '/api/v1/notifications/telegram/link',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        500: 'INTERNAL_SERVER_ERROR',
+        503: 'Telegram notifications are not configured'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseListWebhooks OK
+ * @throws ApiError
+ */
+export const listWebhooks = (data: ListWebhooksData): CancelablePromise<ListWebhooksResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/notifications/webhooks',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @param data.requestBody
+ * @returns ResponseCreateWebhook OK
+ * @throws ApiError
+ */
+export const createWebhook = (data: CreateWebhookData2): CancelablePromise<CreateWebhookResponse> => { return __request(OpenAPI, {
+    method: 'POST',
+    url: '/api/v1/notifications/webhooks',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    body: data.requestBody,
+    mediaType: 'application/json',
+    errors: {
+        400: 'Data is not valid',
+        401: 'Unauthorized',
+        429: 'Webhooks limit is reached',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseWebhookSecret OK
+ * @throws ApiError
+ */
+export const getWebhookSecret = (data: GetWebhookSecretData): CancelablePromise<GetWebhookSecretResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/notifications/webhooks/secret',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseWebhookSecret OK
+ * @throws ApiError
+ */
+export const rotateWebhookSecret = (data: RotateWebhookSecretData): CancelablePromise<RotateWebhookSecretResponse> => { return __request(OpenAPI, {
+    method: 'POST',
+    url: '/api/v1/notifications/webhooks/secret/rotate',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.webhookId Base64 encoded webhook id
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseDeleteWebhook OK
+ * @throws ApiError
+ */
+export const deleteWebhook = (data: DeleteWebhookData): CancelablePromise<DeleteWebhookResponse> => { return __request(OpenAPI, {
+    method: 'DELETE',
+    url: '/api/v1/notifications/webhooks/{webhook_id}',
+    path: {
+        webhook_id: data.webhookId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        404: 'Webhook not found for user',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.webhookId Base64 encoded webhook id
+ * @param data.cursor next_cursor of the previous page
+ * @param data.limit Max number of deliveries, 20 by default
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseListWebhookDeliveries OK
+ * @throws ApiError
+ */
+export const listWebhookDeliveries = (data: ListWebhookDeliveriesData): CancelablePromise<ListWebhookDeliveriesResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/notifications/webhooks/{webhook_id}/deliveries',
+    path: {
+        webhook_id: data.webhookId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    query: {
+        cursor: data.cursor,
+        limit: data.limit
+    },
+    errors: {
+        400: 'Data is not valid',
+        401: 'Unauthorized',
+        404: 'Webhook not found for user',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.q Part of a station name in Russian or Latin
+ * @param data.limit Max number of stations, 20 by default
+ * @returns ResponseSearchStations OK
+ * @throws ApiError
+ */
+export const searchStations = (data: SearchStationsData): CancelablePromise<SearchStationsResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/rzd/stations',
+    query: {
+        q: data.q,
+        limit: data.limit
+    },
+    errors: {
+        400: 'Data is not valid',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.type Only tasks of this type
+ * @param data.status Only tasks with this status
+ * @param data.dateFrom Only tasks with travel dates on or after this day
+ * @param data.dateTo Only tasks with travel dates on or before this day
+ * @param data.stationCode Only tasks departing from or arriving at this station
+ * @param data.order Order by creation time, desc by default
+ * @param data.cursor next_cursor of the previous page
+ * @param data.limit Max number of tasks, 20 by default
+ * @param data.xApiAuthToken JWT auth token
  * @returns ResponseListTasks OK
  * @throws ApiError
  */
@@ -17,7 +234,18 @@ export const listTasks = (data: ListTasksData): CancelablePromise<ListTasksRespo
     headers: {
         'X-API-AUTH-TOKEN': data.xApiAuthToken
     },
+    query: {
+        type: data.type,
+        status: data.status,
+        date_from: data.dateFrom,
+        date_to: data.dateTo,
+        station_code: data.stationCode,
+        order: data.order,
+        cursor: data.cursor,
+        limit: data.limit
+    },
     errors: {
+        400: 'Data is not valid',
         401: 'Unauthorized',
         500: 'INTERNAL_SERVER_ERROR'
     }
@@ -25,7 +253,7 @@ export const listTasks = (data: ListTasksData): CancelablePromise<ListTasksRespo
 
 /**
  * @param data The data for the request.
- * @param data.xApiAuthToken Auth token
+ * @param data.xApiAuthToken JWT auth token
  * @param data.requestBody
  * @returns ResponseCreateTask OK
  * @throws ApiError
@@ -41,13 +269,15 @@ export const createTask = (data: CreateTaskData2): CancelablePromise<CreateTaskR
     errors: {
         400: 'Data is not valid',
         401: 'Unauthorized',
+        403: 'Check interval is shorter than the plan allows',
+        429: 'Active tasks limit of the plan is reached',
         500: 'INTERNAL_SERVER_ERROR'
     }
 }); };
 
 /**
  * @param data The data for the request.
- * @param data.xApiAuthToken Auth token
+ * @param data.xApiAuthToken JWT auth token
  * @returns ResponseDeleteAllTasksForUser OK
  * @throws ApiError
  */
@@ -65,8 +295,66 @@ export const deleteAllTasksForUser = (data: DeleteAllTasksForUserData): Cancelab
 
 /**
  * @param data The data for the request.
- * @param data.taskId Task id
- * @param data.xApiAuthToken Auth token
+ * @param data.xApiAuthToken JWT auth token
+ * @returns TaskEvent Server-Sent Events about checks, found seats and status changes of the user's tasks, the event name is the `event` field
+ * @throws ApiError
+ */
+export const streamTaskEvents = (data: StreamTaskEventsData): CancelablePromise<StreamTaskEventsResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/rzd/tasks/stream',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns void
+ * @throws ApiError
+ */
+export const monitorTasks = (data: MonitorTasksData): CancelablePromise<MonitorTasksResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/rzd/tasks/ws',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        400: 'Not a WebSocket handshake',
+        401: 'Unauthorized'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseGetTask OK
+ * @throws ApiError
+ */
+export const getTaskByIdForUser = (data: GetTaskByIdForUserData): CancelablePromise<GetTaskByIdForUserResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/rzd/tasks/{task_id}',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        404: 'Task not found for user',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.xApiAuthToken JWT auth token
  * @returns ResponseDeleteTaskByIdForUser OK
  * @throws ApiError
  */
@@ -86,6 +374,168 @@ export const deleteTaskByIdForUser = (data: DeleteTaskByIdForUserData): Cancelab
     }
 }); };
 
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.xApiAuthToken JWT auth token
+ * @param data.requestBody
+ * @returns ResponseUpdateTask OK
+ * @throws ApiError
+ */
+export const updateTaskByIdForUser = (data: UpdateTaskByIdForUserData): CancelablePromise<UpdateTaskByIdForUserResponse> => { return __request(OpenAPI, {
+    method: 'PATCH',
+    url: '/api/v1/rzd/tasks/{task_id}',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    body: data.requestBody,
+    mediaType: 'application/json',
+    errors: {
+        400: 'Data is not valid',
+        401: 'Unauthorized',
+        403: 'Check interval is shorter than the plan allows',
+        404: 'Task not found for user',
+        429: 'Active tasks limit of the plan is reached',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.cursor next_cursor of the previous page
+ * @param data.limit Max number of checks, 20 by default
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseListTaskChecks OK
+ * @throws ApiError
+ */
+export const listTaskChecksForUser = (data: ListTaskChecksForUserData): CancelablePromise<ListTaskChecksForUserResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/rzd/tasks/{task_id}/checks',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    query: {
+        cursor: data.cursor,
+        limit: data.limit
+    },
+    errors: {
+        400: 'Data is not valid',
+        401: 'Unauthorized',
+        404: 'Task not found for user',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.xApiAuthToken JWT auth token
+ * @param data.requestBody
+ * @returns ResponseUpdateTask OK
+ * @throws ApiError
+ */
+export const setTaskNotificationsForUser = (data: SetTaskNotificationsForUserData): CancelablePromise<SetTaskNotificationsForUserResponse> => { return __request(OpenAPI, {
+    method: 'PUT',
+    url: '/api/v1/rzd/tasks/{task_id}/notifications',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    body: data.requestBody,
+    mediaType: 'application/json',
+    errors: {
+        401: 'Unauthorized',
+        404: 'Task not found for user',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseChangeTaskStatusForUser OK
+ * @throws ApiError
+ */
+export const pauseTaskByIdForUser = (data: PauseTaskByIdForUserData): CancelablePromise<PauseTaskByIdForUserResponse> => { return __request(OpenAPI, {
+    method: 'POST',
+    url: '/api/v1/rzd/tasks/{task_id}/pause',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        404: 'Task not found for user',
+        409: 'Task is not active',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.since Only prices seen at or after this moment
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseTaskPrices OK
+ * @throws ApiError
+ */
+export const listTaskPricesForUser = (data: ListTaskPricesForUserData): CancelablePromise<ListTaskPricesForUserResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/rzd/tasks/{task_id}/prices',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    query: {
+        since: data.since
+    },
+    errors: {
+        400: 'Data is not valid',
+        401: 'Unauthorized',
+        404: 'Task not found for user',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.taskId Base64 encoded task id
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseChangeTaskStatusForUser OK
+ * @throws ApiError
+ */
+export const resumeTaskByIdForUser = (data: ResumeTaskByIdForUserData): CancelablePromise<ResumeTaskByIdForUserResponse> => { return __request(OpenAPI, {
+    method: 'POST',
+    url: '/api/v1/rzd/tasks/{task_id}/resume',
+    path: {
+        task_id: data.taskId
+    },
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        404: 'Task not found for user',
+        409: 'Task is not paused or failed',
+        429: 'Active tasks limit of the plan is reached',
+        500: 'INTERNAL_SERVER_ERROR'
+    }
+}); };
+
 /**
  * @param data The data for the request.
  * @param data.requestBody
@@ -104,7 +554,7 @@ export const login = (data: LoginData2): CancelablePromise<LoginResponse> => { r
 
 /**
  * @param data The data for the request.
- * @param data.xApiAuthToken Auth token
+ * @param data.xApiAuthToken JWT auth token
  * @returns ResponseMe OK
  * @throws ApiError
  */
@@ -122,8 +572,48 @@ export const me = (data: MeData): CancelablePromise<MeResponse> => { return __re
 
 /**
  * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
+ * @returns ResponseNotificationSettings OK
+ * @throws ApiError
+ */
+export const getNotificationSettings = (data: GetNotificationSettingsData): CancelablePromise<GetNotificationSettingsResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/users/me/notifications',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    errors: {
+        401: 'Unauthorized',
+        500: 'Internal server error'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.xApiAuthToken JWT auth token
  * @param data.requestBody
- * @returns ResponseSignUp OK
+ * @returns ResponseNotificationSettings OK
+ * @throws ApiError
+ */
+export const updateNotificationSettings = (data: UpdateNotificationSettingsData): CancelablePromise<UpdateNotificationSettingsResponse> => { return __request(OpenAPI, {
+    method: 'PUT',
+    url: '/api/v1/users/me/notifications',
+    headers: {
+        'X-API-AUTH-TOKEN': data.xApiAuthToken
+    },
+    body: data.requestBody,
+    mediaType: 'application/json',
+    errors: {
+        400: 'Data is not valid',
+        401: 'Unauthorized',
+        500: 'Internal server error'
+    }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.requestBody
+ * @returns ResponseSignup OK
  * @throws ApiError
  */
 export const signup = (data: SignupData): CancelablePromise<SignupResponse> => { return __request(OpenAPI, {
@@ -134,4 +624,24 @@ export const signup = (data: SignupData): CancelablePromise<SignupResponse> => {
     errors: {
         400: 'Data is not valid'
     }
+}); };
+
+/**
+ * @param data The data for the request.
+ * @param data.verifyKey Verify token
+ * @param data.redirect Redirect link
+ * @returns void
+ * @throws ApiError
+ */
+export const verifyUser = (data: VerifyUserData): CancelablePromise<VerifyUserResponse> => { return __request(OpenAPI, {
+    method: 'GET',
+    url: '/api/v1/users/verify',
+    query: {
+        verify_key: data.verifyKey,
+        redirect: data.redirect
+    },
+    errors: {
+        308: 'Redirect',
+        404: 'Verify token not found or expired'
+    }
 }); };
\ No newline at end of file
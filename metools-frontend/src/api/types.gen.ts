@@ -1,24 +1,176 @@
 // This file is auto-generated by @hey-api/openapi-ts
 
-export type CreateTaskData = {
-    data: {
-        [key: string]: (string);
-    };
-    task_type: string;
+export type AvailableCarClass = {
+    car_type: string;
+    free_seats: number;
+    min_price: number;
+};
+
+export type AvailableTrain = {
+    arrival_date: string;
+    arrival_time: string;
+    car_classes: Array<AvailableCarClass>;
+    departure_date: string;
+    departure_time: string;
+    leg?: TripLeg | null;
+    number: string;
+};
+
+/**
+ * Minimum price of a car class on one travel date and trip leg.
+ */
+export type ClassPrice = PriceKey & {
+    min_price: number;
+};
+
+/**
+ * Message from the client, task ids are base64 encoded.
+ */
+export type ClientMessage = {
+    task_ids: Array<string>;
+    type: 'subscribe';
+} | {
+    task_ids: Array<string>;
+    type: 'unsubscribe';
+} | {
+    type: 'ping';
+};
+
+export type CreateTaskData = TaskData & {
+    /**
+     * Seconds between checks, the shortest interval of the user's plan by default.
+     */
+    check_interval?: number | null;
+    filters?: TaskFilters;
+    price_alert?: PriceAlert | null;
+};
+
+export type CreateWebhookData = {
+    /**
+     * Events are posted here as JSON.
+     */
+    url: string;
+};
+
+/**
+ * Watches every day from `date_from` to `date_to` inclusive.
+ */
+export type DateRangeTask = {
+    date_from: string;
+    date_to: string;
+    from_point_code: string;
+    to_point_code: string;
+};
+
+/**
+ * Watches several arbitrary days at once.
+ */
+export type DatesTask = {
+    dates: Array<string>;
+    from_point_code: string;
+    to_point_code: string;
+};
+
+export type DayTask = {
+    date: string;
+    from_point_code: string;
+    to_point_code: string;
 };
 
+export type DeliveryMode = 'instant' | 'digest';
+
 export type ErrorResponse = {
     error: string;
     status: string;
 };
 
+export type LegCheck = {
+    leg: TripLeg;
+    status: TaskCheckStatus;
+};
+
 export type LoginData = {
     password: string;
     username: string;
 };
 
+/**
+ * Where notifications are sent, every channel is on by default.
+ */
+export type NotificationChannels = {
+    email?: boolean;
+    telegram?: boolean;
+    webhook?: boolean;
+};
+
+export type NotificationSettings = {
+    channels?: NotificationChannels;
+    delivery?: DeliveryMode;
+    quiet_hours?: QuietHours | null;
+    /**
+     * IANA time zone the quiet hours are in, e.g. `Europe/Moscow`.
+     */
+    timezone?: string;
+};
+
+/**
+ * Alerts about prices of car classes that already have seats.
+ */
+export type PriceAlert = {
+    /**
+     * Alert once the minimum price of a car class falls to this value or below.
+     */
+    below_price?: number | null;
+    /**
+     * Alert once the minimum price of a car class drops by this percentage from its baseline.
+     */
+    drop_percent?: number | null;
+};
+
+/**
+ * A car class on one travel date and trip leg, its prices are tracked separately.
+ */
+export type PriceKey = {
+    car_type: string;
+    date: string;
+    leg?: TripLeg | null;
+};
+
+export type PricePoint = {
+    checked_at: string;
+    min_price: number;
+};
+
+export type PriceSeries = {
+    car_type: string;
+    /**
+     * Travel date, not set for prices stored before they were tracked per date.
+     */
+    date?: string | null;
+    leg?: TripLeg | null;
+    points: Array<PricePoint>;
+};
+
+/**
+ * Local time window without messages, it wraps midnight when `start` is after `end`.
+ */
+export type QuietHours = {
+    end: string;
+    start: string;
+};
+
+export type ResponseChangeTaskStatusForUser = {
+    data: string;
+    status: string;
+};
+
 export type ResponseCreateTask = {
-    data: Task;
+    data: ResponseListTasksData;
+    status: string;
+};
+
+export type ResponseCreateWebhook = {
+    data: ResponseWebhookData;
     status: string;
 };
 
@@ -32,8 +184,59 @@ export type ResponseDeleteTaskByIdForUser = {
     status: string;
 };
 
+export type ResponseDeleteWebhook = {
+    data: string;
+    status: string;
+};
+
+export type ResponseGetTask = {
+    data: ResponseListTasksData;
+    status: string;
+};
+
+export type ResponseListTaskChecks = {
+    data: Array<ResponseTaskCheckData>;
+    /**
+     * Pass as `cursor` to get the next page, absent on the last page.
+     */
+    next_cursor?: string | null;
+    status: string;
+};
+
 export type ResponseListTasks = {
-    data: Array<Task>;
+    data: Array<ResponseListTasksData>;
+    /**
+     * Pass as `cursor` to get the next page, absent on the last page.
+     */
+    next_cursor?: string | null;
+    status: string;
+};
+
+export type ResponseListTasksData = TaskData & {
+    check_interval?: number | null;
+    created_at: string;
+    filters: TaskFilters;
+    id: string;
+    last_check?: TaskCheck | null;
+    last_checked_at?: string | null;
+    next_check_at?: string | null;
+    notifications?: TaskNotificationOverrides | null;
+    price_alert?: PriceAlert | null;
+    status: TaskStatus;
+    user: string;
+};
+
+export type ResponseListWebhookDeliveries = {
+    data: Array<ResponseWebhookDeliveryData>;
+    /**
+     * Pass as `cursor` to get the next page, absent on the last page.
+     */
+    next_cursor?: string | null;
+    status: string;
+};
+
+export type ResponseListWebhooks = {
+    data: Array<ResponseWebhookData>;
     status: string;
 };
 
@@ -43,99 +246,885 @@ export type ResponseLogin = {
 };
 
 export type ResponseMe = {
-    data: UserReturn;
+    data: ResponseMeData;
+    status: string;
+};
+
+export type ResponseMeData = {
+    created_at: string;
+    email: string;
+    username: string;
+};
+
+export type ResponseNotificationSettings = {
+    data: NotificationSettings;
     status: string;
 };
 
-export type ResponseSignUp = {
-    data: UserReturn;
+export type ResponseSearchStations = {
+    data: Array<Station>;
     status: string;
 };
 
-export type SignUpData = {
+export type ResponseSignup = {
+    data: ResponseSignupData;
+    status: string;
+};
+
+export type ResponseSignupData = {
+    created_at: string;
     email: string;
-    password: string;
-    repeat_password: string;
     username: string;
 };
 
-export type Task = {
+export type ResponseTaskCheckData = TaskCheck & {
+    checked_at: string;
+    notified: boolean;
+};
+
+export type ResponseTaskPrices = {
+    data: ResponseTaskPricesData;
+    status: string;
+};
+
+export type ResponseTaskPricesData = {
+    series: Array<PriceSeries>;
+};
+
+export type ResponseTelegramChat = {
+    data: ResponseTelegramChatData;
+    status: string;
+};
+
+export type ResponseTelegramChatData = {
+    linked_at: string;
+    username?: string | null;
+};
+
+export type ResponseTelegramLink = {
+    data: ResponseTelegramLinkData;
+    status: string;
+};
+
+export type ResponseTelegramLinkData = {
+    /**
+     * Send `/start <code>` to the bot if the link can not be opened.
+     */
+    code: string;
+    link: string;
+    valid_until: string;
+};
+
+export type ResponseUnlinkTelegram = {
+    data: string;
+    status: string;
+};
+
+export type ResponseUpdateTask = {
+    data: ResponseListTasksData;
+    status: string;
+};
+
+export type ResponseWebhookData = {
     created_at: string;
-    data: unknown;
     id: string;
-    type_: string;
-    user_id: string;
+    url: string;
 };
 
-export type UserReturn = {
-    email: string;
+export type ResponseWebhookDeliveryData = {
+    attempts: number;
+    created_at: string;
+    error?: string | null;
+    event: string;
     id: string;
+    last_attempt_at?: string | null;
+    /**
+     * When the next attempt is made while the delivery is pending.
+     */
+    next_attempt_at: string;
+    payload: unknown;
+    response_status?: number | null;
+    status: WebhookDeliveryStatus;
+};
+
+export type ResponseWebhookSecret = {
+    data: ResponseWebhookSecretData;
+    status: string;
+};
+
+export type ResponseWebhookSecretData = {
+    /**
+     * Key of the HMAC-SHA256 signature sent in `X-MeTools-Signature`.
+     */
+    secret: string;
+};
+
+/**
+ * Watches an outbound and a return journey together.
+ */
+export type RoundTripTask = {
+    /**
+     * Notify as soon as any leg has seats instead of waiting for both.
+     */
+    alert_per_leg?: boolean;
+    outbound: DayTask;
+    return_leg: DayTask;
+};
+
+export type SeatType = 'lower' | 'upper' | 'side_lower' | 'side_upper';
+
+/**
+ * Message from the server.
+ */
+export type ServerMessage = {
+    task_ids: Array<string>;
+    type: 'subscribed';
+} | {
+    task_ids: Array<string>;
+    type: 'unsubscribed';
+} | {
+    task: ResponseListTasksData;
+    type: 'task';
+} | (TaskEvent & {
+    type: 'event';
+}) | {
+    skipped: number;
+    type: 'lagged';
+} | {
+    type: 'pong';
+} | {
+    error: string;
+    status: string;
+    task_id?: string | null;
+    type: 'error';
+};
+
+export type SignUpData = {
+    email: string;
+    password: string;
+    repeat_password: string;
     username: string;
 };
 
-export type ListTasksData = {
+export type SortOrder = 'asc' | 'desc';
+
+export type Station = {
+    /**
+     * RZD express code, e.g. 2000000 for Moscow
+     */
+    code: string;
+    name: string;
+    name_latin: string;
+};
+
+export type TaskCheck = {
+    /**
+     * Travel dates on which seats were found.
+     */
+    available_dates?: Array<string>;
+    error?: string | null;
+    /**
+     * Per-leg outcome of round-trip tasks.
+     */
+    legs?: Array<LegCheck>;
+    status: TaskCheckStatus;
+    trains: Array<AvailableTrain>;
+};
+
+export type TaskCheckStatus = 'seats_found' | 'no_seats' | 'error';
+
+export type TaskData = {
+    data: DayTask;
+    type: 'day';
+} | {
+    data: TrainTask;
+    type: 'train';
+} | {
+    data: DatesTask;
+    type: 'dates';
+} | {
+    data: DateRangeTask;
+    type: 'date_range';
+} | {
+    data: RoundTripTask;
+    type: 'round_trip';
+};
+
+/**
+ * Change of a task pushed to the streams of its owner.
+ */
+export type TaskEvent = TaskEventKind & {
+    /**
+     * Base64 encoded task id.
+     */
+    task_id: string;
+};
+
+export type TaskEventKind = {
+    check: TaskCheck;
+    event: 'checked';
+} | {
+    event: 'seats_found';
+    trains: Array<AvailableTrain>;
+} | {
+    event: 'prices_changed';
+    prices: Array<ClassPrice>;
+} | {
+    event: 'status_changed';
+    status: TaskStatus;
+};
+
+/**
+ * Optional criteria a car class has to meet before the user is notified about it.
+ */
+export type TaskFilters = {
+    /**
+     * Car classes as named by RZD, e.g. `Купе`. Any class matches when empty.
+     */
+    car_types?: Array<string>;
+    max_price?: number | null;
+    min_seats?: number | null;
+    /**
+     * Seat types are only known for train tasks. Any seat matches when empty.
+     */
+    seat_types?: Array<SeatType>;
+};
+
+/**
+ * Task level changes to the user's settings, omitted fields follow the user's settings.
+ */
+export type TaskNotificationOverrides = {
+    channels?: NotificationChannels | null;
+    delivery?: DeliveryMode | null;
+    /**
+     * `false` lets notifications of the task through during quiet hours.
+     */
+    quiet_hours?: boolean | null;
+};
+
+export type TaskStatus = 'active' | 'paused' | 'fulfilled' | 'expired' | 'failed';
+
+export type TaskType = 'day' | 'train' | 'dates' | 'date_range' | 'round_trip';
+
+/**
+ * Price state of a car class used to detect threshold crossings and drops.
+ */
+export type TrackedPrice = PriceKey & {
+    /**
+     * Price drops are measured from here: the highest price since the last alert.
+     */
+    baseline_price: number;
+    last_price: number;
+};
+
+export type TrainTask = {
+    date: string;
+    from_point_code: string;
+    time: string;
+    tnum: string;
+    to_point_code: string;
+};
+
+export type TripLeg = 'outbound' | 'return';
+
+/**
+ * Fields to change, omitted ones keep their current value.
+ */
+export type UpdateTaskData = (TaskData | null) & {
+    check_interval?: number | null;
+    filters?: TaskFilters | null;
+    price_alert?: PriceAlert | null;
+};
+
+export type WebhookDeliveryStatus = 'pending' | 'delivered' | 'failed';
+
+export type GetTelegramChatData = {
     /**
-     * Auth token
+     * JWT auth token
      */
     xApiAuthToken: string;
 };
 
-export type ListTasksResponse = ResponseListTasks;
+export type GetTelegramChatResponse = ResponseTelegramChat;
 
-export type CreateTaskData2 = {
-    requestBody: CreateTaskData;
+export type UnlinkTelegramChatData = {
     /**
-     * Auth token
+     * JWT auth token
      */
     xApiAuthToken: string;
 };
 
-export type CreateTaskResponse = ResponseCreateTask;
+export type UnlinkTelegramChatResponse = ResponseUnlinkTelegram;
 
-export type DeleteAllTasksForUserData = {
+export type CreateTelegramLinkData = {
     /**
-     * Auth token
+     * JWT auth token
      */
     xApiAuthToken: string;
 };
 
-export type DeleteAllTasksForUserResponse = ResponseDeleteAllTasksForUser;
+export type CreateTelegramLinkResponse = ResponseTelegramLink;
 
-export type DeleteTaskByIdForUserData = {
+export type ListWebhooksData = {
     /**
-     * Task id
+     * JWT auth token
      */
-    taskId: string;
+    xApiAuthToken: string;
+};
+
+export type ListWebhooksResponse = ResponseListWebhooks;
+
+export type CreateWebhookData2 = {
+    requestBody: CreateWebhookData;
     /**
-     * Auth token
+     * JWT auth token
      */
     xApiAuthToken: string;
 };
 
-export type DeleteTaskByIdForUserResponse = ResponseDeleteTaskByIdForUser;
+export type CreateWebhookResponse = ResponseCreateWebhook;
 
-export type LoginData2 = {
-    requestBody: LoginData;
+export type GetWebhookSecretData = {
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
 };
 
-export type LoginResponse = ResponseLogin;
+export type GetWebhookSecretResponse = ResponseWebhookSecret;
 
-export type MeData = {
+export type RotateWebhookSecretData = {
     /**
-     * Auth token
+     * JWT auth token
      */
     xApiAuthToken: string;
 };
 
-export type MeResponse = ResponseMe;
+export type RotateWebhookSecretResponse = ResponseWebhookSecret;
 
-export type SignupData = {
-    requestBody: SignUpData;
+export type DeleteWebhookData = {
+    /**
+     * Base64 encoded webhook id
+     */
+    webhookId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
 };
 
-export type SignupResponse = ResponseSignUp;
+export type DeleteWebhookResponse = ResponseDeleteWebhook;
 
-export type $OpenApiTs = {
+export type ListWebhookDeliveriesData = {
+    /**
+     * next_cursor of the previous page
+     */
+    cursor?: string | null;
+    /**
+     * Max number of deliveries, 20 by default
+     */
+    limit?: number | null;
+    /**
+     * Base64 encoded webhook id
+     */
+    webhookId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type ListWebhookDeliveriesResponse = ResponseListWebhookDeliveries;
+
+export type SearchStationsData = {
+    /**
+     * Max number of stations, 20 by default
+     */
+    limit?: number | null;
+    /**
+     * Part of a station name in Russian or Latin
+     */
+    q: string;
+};
+
+export type SearchStationsResponse = ResponseSearchStations;
+
+export type ListTasksData = {
+    /**
+     * next_cursor of the previous page
+     */
+    cursor?: string | null;
+    /**
+     * Only tasks with travel dates on or after this day
+     */
+    dateFrom?: string | null;
+    /**
+     * Only tasks with travel dates on or before this day
+     */
+    dateTo?: string | null;
+    /**
+     * Max number of tasks, 20 by default
+     */
+    limit?: number | null;
+    /**
+     * Order by creation time, desc by default
+     */
+    order?: SortOrder | null;
+    /**
+     * Only tasks departing from or arriving at this station
+     */
+    stationCode?: string | null;
+    /**
+     * Only tasks with this status
+     */
+    status?: TaskStatus | null;
+    /**
+     * Only tasks of this type
+     */
+    type?: TaskType | null;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type ListTasksResponse = ResponseListTasks;
+
+export type CreateTaskData2 = {
+    requestBody: CreateTaskData;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type CreateTaskResponse = ResponseCreateTask;
+
+export type DeleteAllTasksForUserData = {
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type DeleteAllTasksForUserResponse = ResponseDeleteAllTasksForUser;
+
+export type StreamTaskEventsData = {
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type StreamTaskEventsResponse = TaskEvent;
+
+export type MonitorTasksData = {
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type MonitorTasksResponse = void;
+
+export type GetTaskByIdForUserData = {
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type GetTaskByIdForUserResponse = ResponseGetTask;
+
+export type DeleteTaskByIdForUserData = {
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type DeleteTaskByIdForUserResponse = ResponseDeleteTaskByIdForUser;
+
+export type UpdateTaskByIdForUserData = {
+    requestBody: UpdateTaskData;
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type UpdateTaskByIdForUserResponse = ResponseUpdateTask;
+
+export type ListTaskChecksForUserData = {
+    /**
+     * next_cursor of the previous page
+     */
+    cursor?: string | null;
+    /**
+     * Max number of checks, 20 by default
+     */
+    limit?: number | null;
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type ListTaskChecksForUserResponse = ResponseListTaskChecks;
+
+export type SetTaskNotificationsForUserData = {
+    requestBody: TaskNotificationOverrides;
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type SetTaskNotificationsForUserResponse = ResponseUpdateTask;
+
+export type PauseTaskByIdForUserData = {
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type PauseTaskByIdForUserResponse = ResponseChangeTaskStatusForUser;
+
+export type ListTaskPricesForUserData = {
+    /**
+     * Only prices seen at or after this moment
+     */
+    since?: string | null;
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type ListTaskPricesForUserResponse = ResponseTaskPrices;
+
+export type ResumeTaskByIdForUserData = {
+    /**
+     * Base64 encoded task id
+     */
+    taskId: string;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type ResumeTaskByIdForUserResponse = ResponseChangeTaskStatusForUser;
+
+export type LoginData2 = {
+    requestBody: LoginData;
+};
+
+export type LoginResponse = ResponseLogin;
+
+export type MeData = {
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type MeResponse = ResponseMe;
+
+export type GetNotificationSettingsData = {
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type GetNotificationSettingsResponse = ResponseNotificationSettings;
+
+export type UpdateNotificationSettingsData = {
+    requestBody: NotificationSettings;
+    /**
+     * JWT auth token
+     */
+    xApiAuthToken: string;
+};
+
+export type UpdateNotificationSettingsResponse = ResponseNotificationSettings;
+
+export type SignupData = {
+    requestBody: SignUpData;
+};
+
+export type SignupResponse = ResponseSignup;
+
+export type VerifyUserData = {
+    /**
+     * Redirect link
+     */
+    redirect: string;
+    /**
+     * Verify token
+     */
+    verifyKey: string;
+};
+
+export type VerifyUserResponse = void;
+
+export type $OpenApiTs = {
+    '/api/v1/notifications/telegram': {
+        get: {
+            req: GetTelegramChatData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseTelegramChat;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Telegram chat is not linked
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+                /**
+                 * Telegram notifications are not configured
+                 */
+                503: ErrorResponse;
+            };
+        };
+        delete: {
+            req: UnlinkTelegramChatData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseUnlinkTelegram;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Telegram chat is not linked
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+                /**
+                 * Telegram notifications are not configured
+                 */
+                503: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/notifications/telegram/link': {
+        post: {
+            req: CreateTelegramLinkData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseTelegramLink;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+                /**
+                 * Telegram notifications are not configured
+                 */
+                503: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/notifications/webhooks': {
+        get: {
+            req: ListWebhooksData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseListWebhooks;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+        post: {
+            req: CreateWebhookData2;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseCreateWebhook;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Webhooks limit is reached
+                 */
+                429: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/notifications/webhooks/secret': {
+        get: {
+            req: GetWebhookSecretData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseWebhookSecret;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/notifications/webhooks/secret/rotate': {
+        post: {
+            req: RotateWebhookSecretData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseWebhookSecret;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/notifications/webhooks/{webhook_id}': {
+        delete: {
+            req: DeleteWebhookData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseDeleteWebhook;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Webhook not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/notifications/webhooks/{webhook_id}/deliveries': {
+        get: {
+            req: ListWebhookDeliveriesData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseListWebhookDeliveries;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Webhook not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/stations': {
+        get: {
+            req: SearchStationsData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseSearchStations;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
     '/api/v1/rzd/tasks': {
         get: {
             req: ListTasksData;
@@ -144,6 +1133,10 @@ export type $OpenApiTs = {
                  * OK
                  */
                 200: ResponseListTasks;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
                 /**
                  * Unauthorized
                  */
@@ -169,6 +1162,14 @@ export type $OpenApiTs = {
                  * Unauthorized
                  */
                 401: ErrorResponse;
+                /**
+                 * Check interval is shorter than the plan allows
+                 */
+                403: ErrorResponse;
+                /**
+                 * Active tasks limit of the plan is reached
+                 */
+                429: ErrorResponse;
                 /**
                  * INTERNAL_SERVER_ERROR
                  */
@@ -193,7 +1194,62 @@ export type $OpenApiTs = {
             };
         };
     };
+    '/api/v1/rzd/tasks/stream': {
+        get: {
+            req: StreamTaskEventsData;
+            res: {
+                /**
+                 * Server-Sent Events about checks, found seats and status changes of the user's tasks, the event name is the `event` field
+                 */
+                200: TaskEvent;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/tasks/ws': {
+        get: {
+            req: MonitorTasksData;
+            res: {
+                /**
+                 * WebSocket with JSON messages, the client sends ClientMessage and receives ServerMessage
+                 */
+                101: void;
+                /**
+                 * Not a WebSocket handshake
+                 */
+                400: void;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+            };
+        };
+    };
     '/api/v1/rzd/tasks/{task_id}': {
+        get: {
+            req: GetTaskByIdForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseGetTask;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
         delete: {
             req: DeleteTaskByIdForUserData;
             res: {
@@ -215,6 +1271,174 @@ export type $OpenApiTs = {
                 500: ErrorResponse;
             };
         };
+        patch: {
+            req: UpdateTaskByIdForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseUpdateTask;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Check interval is shorter than the plan allows
+                 */
+                403: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * Active tasks limit of the plan is reached
+                 */
+                429: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/tasks/{task_id}/checks': {
+        get: {
+            req: ListTaskChecksForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseListTaskChecks;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/tasks/{task_id}/notifications': {
+        put: {
+            req: SetTaskNotificationsForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseUpdateTask;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/tasks/{task_id}/pause': {
+        post: {
+            req: PauseTaskByIdForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseChangeTaskStatusForUser;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * Task is not active
+                 */
+                409: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/tasks/{task_id}/prices': {
+        get: {
+            req: ListTaskPricesForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseTaskPrices;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
+    '/api/v1/rzd/tasks/{task_id}/resume': {
+        post: {
+            req: ResumeTaskByIdForUserData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseChangeTaskStatusForUser;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Task not found for user
+                 */
+                404: ErrorResponse;
+                /**
+                 * Task is not paused or failed
+                 */
+                409: ErrorResponse;
+                /**
+                 * Active tasks limit of the plan is reached
+                 */
+                429: ErrorResponse;
+                /**
+                 * INTERNAL_SERVER_ERROR
+                 */
+                500: ErrorResponse;
+            };
+        };
     };
     '/api/v1/users/login': {
         post: {
@@ -250,6 +1474,46 @@ export type $OpenApiTs = {
             };
         };
     };
+    '/api/v1/users/me/notifications': {
+        get: {
+            req: GetNotificationSettingsData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseNotificationSettings;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Internal server error
+                 */
+                500: ErrorResponse;
+            };
+        };
+        put: {
+            req: UpdateNotificationSettingsData;
+            res: {
+                /**
+                 * OK
+                 */
+                200: ResponseNotificationSettings;
+                /**
+                 * Data is not valid
+                 */
+                400: ErrorResponse;
+                /**
+                 * Unauthorized
+                 */
+                401: ErrorResponse;
+                /**
+                 * Internal server error
+                 */
+                500: ErrorResponse;
+            };
+        };
+    };
     '/api/v1/users/signup': {
         post: {
             req: SignupData;
@@ -257,7 +1521,7 @@ export type $OpenApiTs = {
                 /**
                  * OK
                  */
-                200: ResponseSignUp;
+                200: ResponseSignup;
                 /**
                  * Data is not valid
                  */
@@ -265,4 +1529,19 @@ export type $OpenApiTs = {
             };
         };
     };
+    '/api/v1/users/verify': {
+        get: {
+            req: VerifyUserData;
+            res: {
+                /**
+                 * Redirect
+                 */
+                308: void;
+                /**
+                 * Verify token not found or expired
+                 */
+                404: ErrorResponse;
+            };
+        };
+    };
 };
\ No newline at end of file